@@ -0,0 +1,87 @@
+#![feature(test)]
+extern crate test;
+
+use test::Bencher;
+
+use rustpass::suites::KdfSettings;
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_aes_kdf_100000(b: &mut Bencher) {
+    _transform_key(b, 100_000)
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_aes_kdf_1000000(b: &mut Bencher) {
+    _transform_key(b, 1_000_000)
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_aes_kdf_6000000(b: &mut Bencher) {
+    _transform_key(b, 6_000_000)
+}
+
+// KeePass 2.x default: 1 MiB, 2 iterations, 2 lanes
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_argon2_keepass_default(b: &mut Bencher) {
+    _transform_key_argon2(b, 1024 * 1024, 2, 2)
+}
+
+// KeePassXC default memory with one, two and four threads
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_argon2_keepassxc_64mib_lanes_1(b: &mut Bencher) {
+    _transform_key_argon2(b, 64 * 1024 * 1024, 2, 1)
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_argon2_keepassxc_64mib_lanes_2(b: &mut Bencher) {
+    _transform_key_argon2(b, 64 * 1024 * 1024, 2, 2)
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_argon2_keepassxc_64mib_lanes_4(b: &mut Bencher) {
+    _transform_key_argon2(b, 64 * 1024 * 1024, 2, 4)
+}
+
+fn _transform_key_argon2(
+    b: &mut Bencher,
+    memory: u64,
+    iterations: u64,
+    lanes: u32,
+) {
+    let settings = KdfSettings::Argon2 {
+        memory,
+        salt: [2u8; 32].to_vec(),
+        iterations,
+        lanes,
+        version: argon2::Version::Version13,
+    };
+
+    let composite_key = [1u8; 32];
+
+    b.iter(|| {
+        let _ = settings.transform_key(&composite_key);
+    })
+}
+
+fn _transform_key(
+    b: &mut Bencher,
+    rounds: u64,
+) {
+    let settings = KdfSettings::Aes {
+        seed: [2u8; 32].to_vec(),
+        rounds,
+    };
+
+    let composite_key = [1u8; 32];
+
+    b.iter(|| {
+        let _ = settings.transform_key(&composite_key);
+    })
+}
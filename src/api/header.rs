@@ -19,3 +19,24 @@ pub enum InnerHeader {
     None,
     KDBX4(KDBX4InnerHeader),
 }
+
+/// The file formats a database can be converted to with `Database::convert_to`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FormatVersion {
+    /// KeePass 1.x
+    KDB,
+    /// KDBX 3.1
+    KDBX3,
+    /// KDBX 4.0
+    KDBX4,
+}
+
+impl Header {
+    pub fn format_version(&self) -> FormatVersion {
+        match self {
+            Header::KDB(_) => FormatVersion::KDB,
+            Header::KDBX3(_) => FormatVersion::KDBX3,
+            Header::KDBX4(_) => FormatVersion::KDBX4,
+        }
+    }
+}
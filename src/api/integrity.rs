@@ -0,0 +1,33 @@
+/// How damaged a KDBX 4 file is, as found by `Database::verify`. Unlike `Database::open` the
+/// whole file is checked, so every corrupted block is listed and not only the first one.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IntegrityReport {
+    /// The SHA-256 of the outer header matches
+    pub header_hash_valid: bool,
+
+    /// The HMAC of the outer header matches, so the key is correct and the header unchanged
+    pub header_hmac_valid: bool,
+
+    /// Number of HMAC blocks found, including the terminating empty block
+    pub block_count: usize,
+
+    /// Indices of the blocks whose HMAC does not match
+    pub corrupted_blocks: Vec<usize>,
+
+    /// The file ends inside a block or before the terminating empty block
+    pub truncated: bool,
+
+    /// The XML document up to the first damaged block, decrypted and decompressed as far as
+    /// possible. Protected values are still encrypted with the inner stream.
+    pub recovered_xml: Vec<u8>,
+}
+
+impl IntegrityReport {
+    /// Whether the file can be opened without any loss
+    pub fn is_intact(&self) -> bool {
+        self.header_hash_valid
+            && self.header_hmac_valid
+            && self.corrupted_blocks.is_empty()
+            && !self.truncated
+    }
+}
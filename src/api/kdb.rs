@@ -11,3 +11,48 @@ pub struct KDBHeader {
     pub transform_seed: Vec<u8>,
     pub transform_rounds: u32,
 }
+
+/// Something a KeePass 1.x file cannot store, reported when writing one. Entries are named by
+/// their group path and title, e.g. `Internet/mail`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DroppedFeature {
+    /// The database name from the metadata
+    DatabaseName,
+    /// The database description from the metadata
+    DatabaseDescription,
+    /// A string field besides title, user name, password, URL and notes
+    CustomField { entry: String, field: String },
+    /// The previous versions of an entry
+    History { entry: String, items: usize },
+    /// A custom auto-type sequence or window associations
+    AutoType { entry: String },
+    /// The tags of an entry
+    Tags { entry: String },
+    /// The foreground and background colors of an entry
+    Colors { entry: String },
+    /// The override URL of an entry
+    OverrideUrl { entry: String },
+    /// The notes of a group
+    GroupNotes { group: String },
+    /// The attachments of an entry besides the first one
+    Attachments { entry: String, items: usize },
+    /// A KeePass 1.x meta-stream read from the file without a KDBX equivalent
+    MetaStream { name: String },
+    /// The name, notes, icon or times of the root group, which KDB does not have. Its child
+    /// groups become top level groups.
+    RootGroupFlattened { group: String },
+    /// The root group, written as a top level group because it has entries of its own
+    RootGroupWrapped { group: String },
+    /// Whether the recycle bin is enabled and which group it is
+    RecycleBin,
+    /// The tombstones of deleted entries and groups
+    DeletedObjects { items: usize },
+    /// The public custom data of a KDBX 4 header
+    CustomData { items: usize },
+    /// The default user name, its meta-stream needs a group to live in
+    DefaultUserName,
+    /// The database color, its meta-stream needs a group to live in
+    DatabaseColor,
+    /// The custom icons, their meta-stream needs a group to live in
+    CustomIcons { items: usize },
+}
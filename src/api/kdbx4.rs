@@ -5,7 +5,8 @@ use super::{
         KdfSettings,
         OuterCipherSuite,
     },
-    traits::Sizable
+    traits::Sizable,
+    variant_dictionary::VariantDictionary,
 };
 
 #[derive(Debug)]
@@ -18,6 +19,7 @@ pub struct KDBX4Header {
     pub(crate) master_seed: Vec<u8>,
     pub(crate) outer_iv: Vec<u8>,
     pub kdf: KdfSettings,
+    pub public_custom_data: VariantDictionary,
     pub body_start: usize,
 }
 
@@ -31,6 +33,7 @@ impl Sizable for KDBX4Header {
         + self.master_seed.len()
         + self.outer_iv.len()
         + self.kdf.size()
+        + self.public_custom_data.size()
     }
 }
 
@@ -54,7 +57,7 @@ impl Sizable for KDBX4InnerHeader {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BinaryAttachment {
     flags: u8,
     content: Vec<u8>,
@@ -67,6 +70,14 @@ impl BinaryAttachment {
             content: content.to_vec()
         }
     }
+
+    pub(crate) fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub(crate) fn content(&self) -> &[u8] {
+        &self.content
+    }
 }
 
 impl Sizable for BinaryAttachment {
@@ -1,5 +1,6 @@
 pub mod compression;
 pub mod header;
+pub mod integrity;
 pub mod suites;
 
 pub mod kdb;
@@ -8,3 +9,7 @@ pub mod kdbx4;
 
 pub mod settings;
 pub mod traits;
+pub mod variant_dictionary;
+
+// `kdb` is also a module of `database`, so the glob re-export of the crate root can't reach it
+pub use self::kdb::DroppedFeature;
@@ -7,13 +7,12 @@ use crate::{
             OuterCipherSuite,
         }
     },
-    internal::random::generate_random_bytes
+    internal::random::generate_secret_bytes
 };
 
 #[derive(Debug)]
 pub struct Settings {
     compression: Compression,
-    transform_rounds: u64,
     kdf_settings: KdfSettings,
     outer_cipher_suite: OuterCipherSuite,
     inner_cipher_suite: Option<InnerCipherSuite>
@@ -21,17 +20,16 @@ pub struct Settings {
 
 impl Settings {
 
+    /// KDBX3 always uses AES-KDF, `rounds` is stored as the header's transform rounds
     pub fn for_kdbx3(
         compression: Compression,
-        transform_rounds: u64,
         outer_cipher_suite: OuterCipherSuite,
         rounds: u64,
     ) -> Self {
         Self {
             compression,
-            transform_rounds,
             kdf_settings: KdfSettings::Aes {
-                seed: generate_random_bytes(32),
+                seed: generate_secret_bytes(32),
                 rounds
             },
             outer_cipher_suite,
@@ -41,14 +39,12 @@ impl Settings {
 
     pub fn for_kdbx4(
         compression: Compression,
-        transform_rounds: u64,
         kdf_settings: KdfSettings,
         outer_cipher_suite: OuterCipherSuite,
         inner_cipher_suite: InnerCipherSuite
     ) -> Self {
         Self {
             compression,
-            transform_rounds,
             kdf_settings,
             outer_cipher_suite,
             inner_cipher_suite: Some(inner_cipher_suite)
@@ -59,8 +55,12 @@ impl Settings {
         self.compression.clone()
     }
 
+    /// The AES-KDF rounds, or `0` if a different key derivation function is configured
     pub fn transform_rounds(&self) -> u64 {
-        self.transform_rounds
+        match self.kdf_settings {
+            KdfSettings::Aes { rounds, .. } => rounds,
+            _ => 0,
+        }
     }
 
     pub fn kdf_settings(&self) -> KdfSettings {
@@ -1,6 +1,8 @@
 #[derive(Debug, Clone)]
 pub enum InnerCipherSuite {
     Plain,
+    /// Read-only, the inner stream of KDBX 2.x/3.0 files from early KeePass 2 versions
+    ArcFourVariant,
     Salsa20,
     ChaCha20,
 }
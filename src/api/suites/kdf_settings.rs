@@ -1,5 +1,12 @@
 use crate::api::traits::Sizable;
 
+/// The key derivation functions supported by KDBX4
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KdfKind {
+    Aes,
+    Argon2,
+}
+
 #[derive(Debug, Clone)]
 pub enum KdfSettings {
     Aes {
@@ -15,6 +22,33 @@ pub enum KdfSettings {
     },
 }
 
+/// Upper bounds for the key derivation parameters of a database that is being opened.
+///
+/// The KDF parameters are read from the unauthenticated header, so a crafted database could
+/// otherwise make us allocate an arbitrary amount of memory or spawn an arbitrary number of
+/// threads before the credentials are checked.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct KdfLimits {
+    /// Maximum Argon2 memory in bytes
+    pub max_memory: u64,
+
+    /// Maximum number of Argon2 iterations
+    pub max_iterations: u64,
+
+    /// Maximum number of Argon2 lanes, every lane is processed on its own thread
+    pub max_lanes: u32,
+}
+
+impl Default for KdfLimits {
+    fn default() -> Self {
+        KdfLimits {
+            max_memory: 1024 * 1024 * 1024,
+            max_iterations: u64::from(u32::MAX),
+            max_lanes: 256,
+        }
+    }
+}
+
 impl Sizable for KdfSettings {
     fn size_in_bytes(&self) -> usize {
         match self {
@@ -43,13 +77,13 @@ impl Sizable for KdfSettings {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::internal::random::generate_random_bytes;
+    use crate::internal::random::generate_secret_bytes;
 
     #[test]
     fn test_aes_kdf_settings_has_size_40() {
         let settings = KdfSettings::Aes {
             rounds: 1_000_000,
-            seed: generate_random_bytes(32).to_vec()
+            seed: generate_secret_bytes(32).to_vec()
         };
         let size = settings.size();
 
@@ -60,7 +94,7 @@ mod tests {
     fn test_aes_kdf_settings_has_size_20() {
         let settings = KdfSettings::Aes {
             rounds: 1_000_000,
-            seed: generate_random_bytes(12).to_vec()
+            seed: generate_secret_bytes(12).to_vec()
         };
         let size = settings.size();
 
@@ -71,7 +105,7 @@ mod tests {
     fn test_argon2_kdf_settings_has_size_53() {
         let settings = KdfSettings::Argon2 {
             memory: 128_000,
-            salt: generate_random_bytes(32).to_vec(),
+            salt: generate_secret_bytes(32).to_vec(),
             iterations: 2_000,
             lanes: 4,
             version: argon2::Version::Version13
@@ -3,5 +3,9 @@ mod kdf_settings;
 mod outer_cipher;
 
 pub use inner_cipher::InnerCipherSuite;
-pub use kdf_settings::KdfSettings;
+pub use kdf_settings::{
+    KdfKind,
+    KdfLimits,
+    KdfSettings,
+};
 pub use outer_cipher::OuterCipherSuite;
@@ -0,0 +1,5 @@
+pub use crate::internal::structures::variant_dictionary::{
+    FromVariantDictionaryValue,
+    VariantDictionary,
+    VariantDictionaryValue,
+};
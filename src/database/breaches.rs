@@ -0,0 +1,344 @@
+use super::{
+    items::{
+        Entry,
+        Group,
+        Meta,
+    },
+    recycle_bin,
+};
+use crate::{
+    errors::Error,
+    internal::cryptopraphy::{
+        md4,
+        sha1,
+    },
+    results::Result,
+};
+
+use zeroize::Zeroizing;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{
+        Read,
+        Seek,
+        SeekFrom,
+    },
+    path::Path,
+};
+
+// bytes read at a time while looking for the end of a line, more than a line of either list
+const LINE_CHUNK: usize = 64;
+
+/// The hashes a Have I Been Pwned password list is made of
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BreachHash {
+    /// SHA-1 of the UTF-8 password
+    Sha1,
+    /// NTLM, MD4 of the UTF-16LE password
+    Ntlm,
+}
+
+impl BreachHash {
+    fn hex_length(self) -> usize {
+        match self {
+            BreachHash::Sha1 => 40,
+            BreachHash::Ntlm => 32,
+        }
+    }
+
+    fn hash(self, password: &str) -> Result<String> {
+        let digest = match self {
+            BreachHash::Sha1 => sha1(&[password.as_bytes()])?.to_vec(),
+            BreachHash::Ntlm => {
+                let utf16 = Zeroizing::new(
+                    password.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>()
+                );
+                md4(&[&utf16])?.to_vec()
+            }
+        };
+
+        Ok(hex::encode_upper(digest))
+    }
+}
+
+/// A downloaded Have I Been Pwned password list, the version ordered by hash with one
+/// `HASH:COUNT` line per breached password. Lookups binary search the file, so it is never
+/// loaded into memory and no request leaves the machine.
+pub struct BreachList<R> {
+    reader: R,
+    length: u64,
+    hash: BreachHash,
+}
+
+/// An entry whose password is in a `BreachList`
+#[derive(Debug)]
+pub struct BreachedEntry<'a> {
+    pub entry: &'a Entry,
+    /// How often the password was seen in breaches
+    pub occurrences: u64,
+}
+
+impl BreachList<File> {
+    pub fn open<P: AsRef<Path>>(path: P, hash: BreachHash) -> Result<Self> {
+        Self::new(File::open(path)?, hash)
+    }
+}
+
+impl<R: Read + Seek> BreachList<R> {
+    pub fn new(mut reader: R, hash: BreachHash) -> Result<Self> {
+        let length = reader.seek(SeekFrom::End(0))?;
+
+        Ok(Self { reader, length, hash })
+    }
+
+    /// How often `password` was seen in breaches, 0 if it is not in the list
+    pub fn occurrences(&mut self, password: &str) -> Result<u64> {
+        let hash = self.hash.hash(password)?;
+        self.find(&hash)
+    }
+
+    // Looks for the first line whose hash is not below `target`. Every line starting before
+    // `low` is below it and every line starting at or after `high` is not.
+    fn find(&mut self, target: &str) -> Result<u64> {
+        let target = target.to_ascii_uppercase();
+        let mut low = 0;
+        let mut high = self.length;
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let start = self.next_line_start(middle)?;
+            if start >= high {
+                high = middle;
+                continue;
+            }
+
+            let line = self.read_line(start)?;
+            if self.line_hash(&line)?.as_slice() < target.as_bytes() {
+                low = start + line.len() as u64;
+            } else {
+                high = start;
+            }
+        }
+
+        if low >= self.length {
+            return Ok(0);
+        }
+
+        let line = self.read_line(low)?;
+        if self.line_hash(&line)?.as_slice() != target.as_bytes() {
+            return Ok(0);
+        }
+
+        count(&line)
+    }
+
+    // The first line starting at or after `position`
+    fn next_line_start(&mut self, position: u64) -> Result<u64> {
+        if position == 0 {
+            return Ok(0);
+        }
+
+        let previous = self.read_line(position - 1)?;
+        Ok(position - 1 + previous.len() as u64)
+    }
+
+    // The bytes from `start` up to and including the next line feed
+    fn read_line(&mut self, start: u64) -> Result<Vec<u8>> {
+        self.reader.seek(SeekFrom::Start(start))?;
+
+        let mut line = Vec::new();
+        let mut chunk = [0u8; LINE_CHUNK];
+        loop {
+            let read = self.reader.read(&mut chunk)?;
+            if read == 0 {
+                return Ok(line);
+            }
+
+            match chunk[..read].iter().position(|byte| *byte == b'\n') {
+                Some(end) => {
+                    line.extend_from_slice(&chunk[..=end]);
+                    return Ok(line);
+                }
+                None => line.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    // Lists are upper case hex, but compare lower case ones the same way
+    fn line_hash(&self, line: &[u8]) -> Result<Vec<u8>> {
+        let hash = line.split(|byte| *byte == b':' || byte.is_ascii_whitespace())
+            .next()
+            .unwrap_or_default();
+
+        if hash.len() != self.hash.hex_length() || !hash.iter().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(Error::InvalidBreachList {
+                reason: format!("{} is not a {:?} hash", String::from_utf8_lossy(hash), self.hash),
+            });
+        }
+
+        Ok(hash.to_ascii_uppercase())
+    }
+}
+
+// The count after the hash, lists without counts only hold breached passwords
+fn count(line: &[u8]) -> Result<u64> {
+    let text = String::from_utf8_lossy(line);
+
+    match text.trim_end().split_once(':') {
+        Some((_, count)) => count.trim().parse().map_err(|_| Error::InvalidBreachList {
+            reason: format!("{} is not an occurrence count", count),
+        }),
+        None => Ok(1),
+    }
+}
+
+/// Looks up the password of every entry under `root` in `list`, the most breached first.
+/// Entries in the recycle bin are left out.
+pub(crate) fn check<'a, R: Read + Seek>(
+    root: &'a Group,
+    meta: &Meta,
+    list: &mut BreachList<R>,
+) -> Result<Vec<BreachedEntry<'a>>> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut breached = Vec::new();
+
+    for found in recycle_bin::entries_outside_recycle_bin(root, meta) {
+        let entry = found.entry;
+        let password = match entry.password() {
+            Some(password) if !password.is_empty() => password,
+            _ => continue,
+        };
+
+        // reused passwords are looked up once, by hash
+        let hash = list.hash.hash(&password)?;
+        let occurrences = match counts.get(&hash) {
+            Some(occurrences) => *occurrences,
+            None => {
+                let occurrences = list.find(&hash)?;
+                counts.insert(hash, occurrences);
+                occurrences
+            }
+        };
+
+        if occurrences > 0 {
+            breached.push(BreachedEntry { entry, occurrences });
+        }
+    }
+
+    breached.sort_by(|a, b| {
+        b.occurrences.cmp(&a.occurrences)
+            .then_with(|| a.entry.title().as_deref().cmp(&b.entry.title().as_deref()))
+    });
+
+    Ok(breached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_helpers::{
+        entry,
+        recycle_bin,
+    };
+    use spectral::prelude::*;
+
+    use std::io::Cursor;
+
+    fn list(hash: BreachHash, passwords: &[(&str, u64)]) -> BreachList<Cursor<Vec<u8>>> {
+        let mut lines: Vec<String> = passwords.iter()
+            .map(|(password, count)| format!("{}:{}\r\n", hash.hash(password).unwrap(), count))
+            .collect();
+        lines.sort();
+
+        BreachList::new(Cursor::new(lines.concat().into_bytes()), hash).unwrap()
+    }
+
+    #[test]
+    fn test_hashes() {
+        assert_that(&BreachHash::Sha1.hash("password").unwrap())
+            .is_equal_to("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8".to_owned());
+        assert_that(&BreachHash::Ntlm.hash("password").unwrap())
+            .is_equal_to("8846F7EAEE8FB117AD06BDD830B7586C".to_owned());
+    }
+
+    #[test]
+    fn test_occurrences_finds_every_line() {
+        let passwords: Vec<(String, u64)> = (0..200).map(|i| (format!("password{}", i), i + 1)).collect();
+        let passwords: Vec<(&str, u64)> = passwords.iter().map(|(p, c)| (p.as_str(), *c)).collect();
+
+        for hash in &[BreachHash::Sha1, BreachHash::Ntlm] {
+            let mut list = list(*hash, &passwords);
+
+            for (password, count) in &passwords {
+                assert_that(&list.occurrences(password).unwrap()).is_equal_to(*count);
+            }
+            assert_that(&list.occurrences("not breached").unwrap()).is_equal_to(0);
+        }
+    }
+
+    #[test]
+    fn test_occurrences_in_small_lists() {
+        let hash = BreachHash::Sha1;
+
+        assert_that(&list(hash, &[]).occurrences("password").unwrap()).is_equal_to(0);
+        assert_that(&list(hash, &[("password", 3)]).occurrences("password").unwrap()).is_equal_to(3);
+
+        let mut bare = BreachList::new(Cursor::new(hash.hash("hunter2").unwrap().into_bytes()), hash).unwrap();
+        assert_that(&bare.occurrences("hunter2").unwrap()).is_equal_to(1);
+    }
+
+    #[test]
+    fn test_occurrences_in_lower_case_lists() {
+        let line = format!("{}:5\n", BreachHash::Sha1.hash("password").unwrap().to_ascii_lowercase());
+        let mut list = BreachList::new(Cursor::new(line.into_bytes()), BreachHash::Sha1).unwrap();
+
+        assert_that(&list.occurrences("password").unwrap()).is_equal_to(5);
+    }
+
+    #[test]
+    fn test_malformed_list_fails() {
+        let mut list = BreachList::new(Cursor::new(b"not a hash:1\n".to_vec()), BreachHash::Sha1).unwrap();
+
+        assert_that(&matches!(list.occurrences("password"), Err(Error::InvalidBreachList { .. }))).is_true();
+    }
+
+    #[test]
+    fn test_check_reports_breached_entries() {
+        let mut root = Group::root();
+        root.add_entry("mail", &entry("mail", "", "hunter2", ""));
+        root.add_entry("forum", &entry("forum", "", "hunter2", ""));
+        root.add_entry("bank", &entry("bank", "", "7Gq!x9#Lr2$wZp4&vB8^", ""));
+        let mut work = Group::new("Work", &Default::default(), &Default::default());
+        work.add_entry("vpn", &entry("vpn", "", "password", ""));
+        root.add_group("Work", &work);
+
+        let mut list = list(BreachHash::Ntlm, &[("password", 9_545_824), ("hunter2", 17_043)]);
+        let breached = check(&root, &Meta::default(), &mut list).unwrap();
+
+        let found: Vec<(String, u64)> = breached.iter()
+            .map(|breached| (breached.entry.title().unwrap().to_string(), breached.occurrences))
+            .collect();
+        assert_that(&found).is_equal_to(vec![
+            ("vpn".to_owned(), 9_545_824),
+            ("forum".to_owned(), 17_043),
+            ("mail".to_owned(), 17_043),
+        ]);
+    }
+
+    #[test]
+    fn test_check_skips_recycle_bin() {
+        let mut root = Group::root();
+        root.add_entry("mail", &entry("mail", "", "hunter2", ""));
+        let (mut bin, meta) = recycle_bin("Recycle Bin");
+        bin.add_entry("old", &entry("old", "", "password", ""));
+        root.add_group("Recycle Bin", &bin);
+
+        let mut list = list(BreachHash::Sha1, &[("password", 9_545_824), ("hunter2", 17_043)]);
+        let breached = check(&root, &meta, &mut list).unwrap();
+
+        assert_that(&breached).has_length(1);
+        assert_that(&breached[0].entry.title().as_deref()).is_equal_to(Some("mail"));
+    }
+}
@@ -0,0 +1,280 @@
+use super::items::ValueGuard;
+use crate::{
+    errors::Error,
+    internal::random,
+    results::Result,
+};
+
+use secstr::SecStr;
+use zeroize::Zeroizing;
+
+mod passphrase;
+mod pattern;
+
+pub use passphrase::{
+    PassphraseProfile,
+    Wordlist,
+};
+pub use pattern::Pattern;
+
+const UPPER_CASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER_CASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+// the characters KeePass leaves out when asked to avoid look-alikes
+const LOOK_ALIKES: &str = "O0Il1|";
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// A password from one of the generators, with the entropy of the process that produced it
+#[derive(Debug)]
+pub struct GeneratedPassword {
+    pub password: ValueGuard<'static>,
+    /// Bits of entropy, the base 2 logarithm of the number of equally likely outcomes
+    pub entropy: f64,
+}
+
+/// The character classes of `PasswordProfile`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CharClass {
+    UpperCase,
+    LowerCase,
+    Digits,
+    /// The printable ASCII punctuation
+    Symbols,
+    /// The printable Latin-1 characters above ASCII, what KeePass calls high ANSI
+    Extended,
+}
+
+impl CharClass {
+    pub fn chars(self) -> Vec<char> {
+        match self {
+            CharClass::UpperCase => UPPER_CASE.chars().collect(),
+            CharClass::LowerCase => LOWER_CASE.chars().collect(),
+            CharClass::Digits => DIGITS.chars().collect(),
+            CharClass::Symbols => SYMBOLS.chars().collect(),
+            CharClass::Extended => ('\u{a1}'..='\u{ff}').filter(|c| *c != SOFT_HYPHEN).collect(),
+        }
+    }
+}
+
+/// Random passwords of a fixed length drawn from a set of character classes
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PasswordProfile {
+    pub length: usize,
+    pub upper_case: bool,
+    pub lower_case: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub extended: bool,
+    /// Leaves out the characters that are easily confused with each other, such as `O` and `0`
+    pub exclude_look_alikes: bool,
+    /// Every selected class appears at least once
+    pub include_each_class: bool,
+}
+
+impl Default for PasswordProfile {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            upper_case: true,
+            lower_case: true,
+            digits: true,
+            symbols: false,
+            extended: false,
+            exclude_look_alikes: false,
+            include_each_class: true,
+        }
+    }
+}
+
+impl PasswordProfile {
+    pub fn generate(&self) -> Result<GeneratedPassword> {
+        let classes = self.classes()?;
+        let pool: Vec<char> = classes.iter().flatten().copied().collect();
+
+        // drawing until every class shows up keeps the result uniform over the valid passwords
+        let mut indices = Zeroizing::new(vec![0usize; self.length]);
+        loop {
+            for index in indices.iter_mut() {
+                *index = random::random_index(pool.len());
+            }
+
+            if !self.include_each_class || covers_classes(&classes, &indices) {
+                break;
+            }
+        }
+
+        Ok(GeneratedPassword {
+            password: assemble(indices.iter().map(|index| pool[*index]), self.length),
+            entropy: self.entropy()?,
+        })
+    }
+
+    /// The entropy of every password the profile generates
+    pub fn entropy(&self) -> Result<f64> {
+        let classes = self.classes()?;
+        let pool = classes.iter().map(Vec::len).sum::<usize>() as f64;
+        let length = self.length as i32;
+        let all = self.length as f64 * pool.log2();
+
+        if !self.include_each_class {
+            return Ok(all);
+        }
+
+        // inclusion-exclusion over the classes left out, relative to all pool^length passwords
+        let mut share = 0.0;
+        for subset in 0..1usize << classes.len() {
+            let missing: usize = classes.iter()
+                .enumerate()
+                .filter(|(i, _)| subset & 1 << i != 0)
+                .map(|(_, class)| class.len())
+                .sum();
+            let sign = if subset.count_ones() % 2 == 0 { 1.0 } else { -1.0 };
+            share += sign * ((pool - missing as f64) / pool).powi(length);
+        }
+
+        Ok(all + share.log2())
+    }
+
+    fn classes(&self) -> Result<Vec<Vec<char>>> {
+        let selected = [
+            (self.upper_case, CharClass::UpperCase),
+            (self.lower_case, CharClass::LowerCase),
+            (self.digits, CharClass::Digits),
+            (self.symbols, CharClass::Symbols),
+            (self.extended, CharClass::Extended),
+        ];
+
+        let classes: Vec<Vec<char>> = selected.iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, class)| {
+                class.chars()
+                    .into_iter()
+                    .filter(|c| !self.exclude_look_alikes || !LOOK_ALIKES.contains(*c))
+                    .collect()
+            })
+            .collect();
+
+        if classes.is_empty() {
+            return Err(invalid("no character class selected"));
+        }
+        if self.length == 0 {
+            return Err(invalid("the length must be at least 1"));
+        }
+        if self.include_each_class && self.length < classes.len() {
+            return Err(invalid(&format!(
+                "{} characters cannot include each of the {} classes",
+                self.length,
+                classes.len()
+            )));
+        }
+
+        Ok(classes)
+    }
+}
+
+// the pool lists the classes one after the other
+fn covers_classes(classes: &[Vec<char>], indices: &[usize]) -> bool {
+    let mut start = 0;
+    classes.iter().all(|class| {
+        let range = start..start + class.len();
+        start = range.end;
+        indices.iter().any(|index| range.contains(index))
+    })
+}
+
+// builds the password straight into the buffer that is wiped on drop
+fn assemble(chars: impl Iterator<Item = char>, capacity: usize) -> ValueGuard<'static> {
+    let mut bytes = Vec::with_capacity(capacity * 4);
+    let mut buffer = [0u8; 4];
+    for c in chars {
+        bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+    }
+
+    ValueGuard::owned(SecStr::new(bytes))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidGeneratorSettings { reason: reason.to_owned() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn has_class(password: &str, class: CharClass) -> bool {
+        let chars = class.chars();
+        password.chars().any(|c| chars.contains(&c))
+    }
+
+    #[test]
+    fn test_generate_default_profile() {
+        let generated = PasswordProfile::default().generate().unwrap();
+
+        assert_that(&generated.password.chars().count()).is_equal_to(20);
+        assert_that(&generated.password.chars().all(|c| c.is_ascii_alphanumeric())).is_true();
+        assert_that(&has_class(&generated.password, CharClass::UpperCase)).is_true();
+        assert_that(&has_class(&generated.password, CharClass::LowerCase)).is_true();
+        assert_that(&has_class(&generated.password, CharClass::Digits)).is_true();
+    }
+
+    #[test]
+    fn test_generate_includes_each_class_in_short_passwords() {
+        let profile = PasswordProfile {
+            length: 5,
+            symbols: true,
+            extended: true,
+            ..PasswordProfile::default()
+        };
+
+        for _ in 0..50 {
+            let generated = profile.generate().unwrap();
+
+            assert_that(&has_class(&generated.password, CharClass::Symbols)).is_true();
+            assert_that(&has_class(&generated.password, CharClass::Extended)).is_true();
+        }
+    }
+
+    #[test]
+    fn test_generate_excludes_look_alikes() {
+        let profile = PasswordProfile {
+            length: 200,
+            exclude_look_alikes: true,
+            ..PasswordProfile::default()
+        };
+
+        let generated = profile.generate().unwrap();
+
+        assert_that(&generated.password.chars().any(|c| LOOK_ALIKES.contains(c))).is_false();
+    }
+
+    #[test]
+    fn test_entropy() {
+        let free = PasswordProfile { length: 10, digits: false, include_each_class: false, ..Default::default() };
+        let each = PasswordProfile { include_each_class: true, ..free.clone() };
+        let two = PasswordProfile { length: 2, ..each.clone() };
+
+        assert_that(&((free.entropy().unwrap() - (10.0 * 52f64.log2())).abs() < 1e-9)).is_true();
+        assert_that(&(each.entropy().unwrap() < free.entropy().unwrap())).is_true();
+        // one upper and one lower case letter in either order
+        assert_that(&((two.entropy().unwrap() - ((2.0 * 26.0 * 26.0f64).log2())).abs() < 1e-9)).is_true();
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        let none = PasswordProfile {
+            upper_case: false,
+            lower_case: false,
+            digits: false,
+            ..Default::default()
+        };
+        let empty = PasswordProfile { length: 0, include_each_class: false, ..Default::default() };
+        let short = PasswordProfile { length: 2, ..Default::default() };
+
+        for profile in &[none, empty, short] {
+            assert_that(&matches!(profile.generate(), Err(Error::InvalidGeneratorSettings { .. })))
+                .is_true();
+        }
+    }
+}
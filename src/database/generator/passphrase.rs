@@ -0,0 +1,166 @@
+use super::{
+    assemble,
+    invalid,
+    GeneratedPassword,
+};
+use crate::{
+    internal::random,
+    results::Result,
+    wordlists::BIP39_ENGLISH,
+};
+
+use zeroize::Zeroizing;
+
+/// The words a passphrase is drawn from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    /// The 2048 words of the BIP-39 English list, 11 bits per word. They are short, common
+    /// and unique in their first four letters.
+    pub fn bundled() -> Self {
+        Self::parse(BIP39_ENGLISH).expect("the bundled wordlist is valid")
+    }
+
+    /// Reads one word per line. Diceware lists such as the EFF ones, which put the dice
+    /// roll in front of each word, are accepted too. Blank lines are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut words: Vec<String> = Vec::new();
+
+        for line in text.lines() {
+            let mut columns = line.split_whitespace();
+            let word = match (columns.next(), columns.next(), columns.next()) {
+                (None, _, _) => continue,
+                (Some(word), None, _) => word,
+                (Some(roll), Some(word), None) if roll.chars().all(|c| c.is_ascii_digit()) => word,
+                _ => return Err(invalid(&format!("the wordlist line {} is not a single word", line.trim()))),
+            };
+
+            if words.iter().any(|known| known == word) {
+                return Err(invalid(&format!("the wordlist contains {} twice", word)));
+            }
+            words.push(word.to_owned());
+        }
+
+        if words.len() < 2 {
+            return Err(invalid("a wordlist needs at least two words"));
+        }
+
+        Ok(Self { words })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+/// Diceware passphrases, a number of words picked at random from a `Wordlist`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PassphraseProfile {
+    pub words: usize,
+    pub separator: String,
+    /// Upper-cases the first letter of each word, which adds no entropy
+    pub capitalize: bool,
+}
+
+impl Default for PassphraseProfile {
+    fn default() -> Self {
+        Self {
+            words: 7,
+            separator: "-".to_owned(),
+            capitalize: false,
+        }
+    }
+}
+
+impl PassphraseProfile {
+    pub fn generate(&self, wordlist: &Wordlist) -> Result<GeneratedPassword> {
+        if self.words == 0 {
+            return Err(invalid("a passphrase needs at least one word"));
+        }
+
+        let mut chars = Zeroizing::new(Vec::new());
+        for i in 0..self.words {
+            if i > 0 {
+                chars.extend(self.separator.chars());
+            }
+
+            let word = &wordlist.words[random::random_index(wordlist.len())];
+            let mut letters = word.chars();
+            if let Some(first) = letters.next() {
+                match self.capitalize {
+                    true => chars.extend(first.to_uppercase()),
+                    false => chars.push(first),
+                }
+            }
+            chars.extend(letters);
+        }
+
+        Ok(GeneratedPassword {
+            password: assemble(chars.iter().copied(), chars.len()),
+            entropy: self.entropy(wordlist),
+        })
+    }
+
+    /// The entropy of every passphrase the profile generates from `wordlist`
+    pub fn entropy(&self, wordlist: &Wordlist) -> f64 {
+        self.words as f64 * (wordlist.len() as f64).log2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_bundled_wordlist() {
+        let wordlist = Wordlist::bundled();
+
+        assert_that(&wordlist.len()).is_equal_to(2048);
+        assert_that(&((PassphraseProfile::default().entropy(&wordlist) - 77.0).abs() < 1e-9)).is_true();
+    }
+
+    #[test]
+    fn test_parse_diceware_list() {
+        let wordlist = Wordlist::parse("11111\tabacus\n11112\tabdomen\n\n11113 abdominal\n").unwrap();
+
+        assert_that(&wordlist.words).is_equal_to(vec![
+            "abacus".to_owned(),
+            "abdomen".to_owned(),
+            "abdominal".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_invalid_lists() {
+        for text in &["alpha", "alpha\nalpha", "alpha\nbeta gamma", "alpha\n1 beta gamma"] {
+            assert_that(&matches!(Wordlist::parse(text), Err(Error::InvalidGeneratorSettings { .. })))
+                .is_true();
+        }
+    }
+
+    #[test]
+    fn test_generate_passphrase() {
+        let wordlist = Wordlist::parse("alpha\nbeta\ngamma\ndelta").unwrap();
+        let profile = PassphraseProfile {
+            words: 5,
+            separator: " ".to_owned(),
+            capitalize: true,
+        };
+
+        let generated = profile.generate(&wordlist).unwrap();
+        let words: Vec<&str> = generated.password.split(' ').collect();
+
+        assert_that(&words).has_length(5);
+        assert_that(&words.iter().all(|word| ["Alpha", "Beta", "Gamma", "Delta"].contains(word))).is_true();
+        assert_that(&((generated.entropy - 10.0).abs() < 1e-9)).is_true();
+    }
+}
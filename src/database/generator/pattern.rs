@@ -0,0 +1,228 @@
+use super::{
+    assemble,
+    invalid,
+    GeneratedPassword,
+    DIGITS,
+    LOWER_CASE,
+    SYMBOLS,
+    UPPER_CASE,
+};
+use crate::{
+    internal::random,
+    results::Result,
+};
+
+use zeroize::Zeroizing;
+
+const LOWER_VOWELS: &str = "aeiou";
+const UPPER_VOWELS: &str = "AEIOU";
+const LOWER_CONSONANTS: &str = "bcdfghjklmnpqrstvwxyz";
+const UPPER_CONSONANTS: &str = "BCDFGHJKLMNPQRSTVWXYZ";
+const LOWER_HEX: &str = "0123456789abcdef";
+const UPPER_HEX: &str = "0123456789ABCDEF";
+const PUNCTUATION: &str = ",.;:";
+const BRACKETS: &str = "()[]{}<>";
+
+// far beyond any real password, but keeps `{n}` from asking for gigabytes
+const MAX_LENGTH: usize = 10_000;
+
+// One position of the password
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Slot {
+    Literal(char),
+    Set(Vec<char>),
+}
+
+/// A KeePass password pattern such as `u{4}d{4}` or `[dh^0]{8}`.
+///
+/// Each placeholder stands for one random character: `u`/`l`/`L` upper, lower and mixed case
+/// letters, `d` digits, `a`/`A`/`U` lower, mixed and upper case letters with digits, `h`/`H`
+/// hex digits, `v`/`V`/`Z` and `c`/`C`/`z` vowels and consonants, `s` ASCII symbols, `S`
+/// letters, digits and symbols, `p` punctuation, `b` brackets and `x` high ANSI characters.
+/// `{n}` repeats the previous position, `[...]` draws from the union of the placeholders and
+/// escaped characters inside, where everything after a `^` is taken out again, and `\` inserts
+/// the next character literally. Any other character is kept as it is.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pattern {
+    slots: Vec<Slot>,
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut slots: Vec<Slot> = Vec::new();
+        let mut chars = pattern.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => slots.push(Slot::Literal(escaped(&mut chars)?)),
+                '{' => {
+                    let count = repetition(&mut chars)?;
+                    let previous = slots.pop()
+                        .ok_or_else(|| invalid("a repetition needs a preceding placeholder"))?;
+                    if slots.len().saturating_add(count) > MAX_LENGTH {
+                        return Err(invalid(&format!("the pattern is longer than {} characters", MAX_LENGTH)));
+                    }
+                    slots.extend(std::iter::repeat(previous).take(count));
+                }
+                '[' => slots.push(Slot::Set(custom_set(&mut chars)?)),
+                c => match placeholder(c) {
+                    Some(set) => slots.push(Slot::Set(set)),
+                    None => slots.push(Slot::Literal(c)),
+                },
+            }
+        }
+
+        if slots.is_empty() {
+            return Err(invalid("the pattern is empty"));
+        }
+
+        Ok(Self { slots })
+    }
+
+    pub fn generate(&self) -> GeneratedPassword {
+        let chars = Zeroizing::new(
+            self.slots.iter()
+                .map(|slot| match slot {
+                    Slot::Literal(c) => *c,
+                    Slot::Set(set) => set[random::random_index(set.len())],
+                })
+                .collect::<Vec<_>>()
+        );
+
+        GeneratedPassword {
+            password: assemble(chars.iter().copied(), chars.len()),
+            entropy: self.entropy(),
+        }
+    }
+
+    /// The entropy of every password the pattern generates, literals add nothing
+    pub fn entropy(&self) -> f64 {
+        self.slots.iter()
+            .map(|slot| match slot {
+                Slot::Literal(_) => 0.0,
+                Slot::Set(set) => (set.len() as f64).log2(),
+            })
+            .sum()
+    }
+}
+
+fn placeholder(code: char) -> Option<Vec<char>> {
+    let parts: &[&str] = match code {
+        'a' => &[LOWER_CASE, DIGITS],
+        'A' => &[LOWER_CASE, UPPER_CASE, DIGITS],
+        'U' => &[UPPER_CASE, DIGITS],
+        'c' => &[LOWER_CONSONANTS],
+        'C' => &[LOWER_CONSONANTS, UPPER_CONSONANTS],
+        'z' => &[UPPER_CONSONANTS],
+        'd' => &[DIGITS],
+        'h' => &[LOWER_HEX],
+        'H' => &[UPPER_HEX],
+        'l' => &[LOWER_CASE],
+        'L' => &[LOWER_CASE, UPPER_CASE],
+        'u' => &[UPPER_CASE],
+        'p' => &[PUNCTUATION],
+        'b' => &[BRACKETS],
+        's' => &[SYMBOLS],
+        'S' => &[UPPER_CASE, LOWER_CASE, DIGITS, SYMBOLS],
+        'v' => &[LOWER_VOWELS],
+        'V' => &[LOWER_VOWELS, UPPER_VOWELS],
+        'Z' => &[UPPER_VOWELS],
+        'x' => return Some(super::CharClass::Extended.chars()),
+        _ => return None,
+    };
+
+    Some(parts.iter().flat_map(|part| part.chars()).collect())
+}
+
+fn escaped(chars: &mut std::str::Chars) -> Result<char> {
+    chars.next().ok_or_else(|| invalid("the pattern ends with an escape"))
+}
+
+fn repetition(chars: &mut std::str::Chars) -> Result<usize> {
+    let mut digits = String::new();
+    for c in chars {
+        if c == '}' {
+            return digits.parse()
+                .map_err(|_| invalid(&format!("{{{}}} is not a repetition count", digits)));
+        }
+        digits.push(c);
+    }
+
+    Err(invalid("a repetition is not closed"))
+}
+
+fn custom_set(chars: &mut std::str::Chars) -> Result<Vec<char>> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    let mut excluding = false;
+
+    loop {
+        let target = if excluding { &mut excluded } else { &mut included };
+
+        match chars.next() {
+            None => return Err(invalid("a custom character set is not closed")),
+            Some(']') => break,
+            Some('^') => excluding = true,
+            Some('\\') => target.push(escaped(chars)?),
+            Some(c) => match placeholder(c) {
+                Some(set) => target.extend(set),
+                None => target.push(c),
+            },
+        }
+    }
+
+    included.sort_unstable();
+    included.dedup();
+    included.retain(|c| !excluded.contains(c));
+
+    if included.is_empty() {
+        return Err(invalid("a custom character set is empty"));
+    }
+
+    Ok(included)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_generate_pattern() {
+        let generated = Pattern::parse("u{4}d{4}").unwrap().generate();
+        let password: Vec<char> = generated.password.chars().collect();
+
+        assert_that(&password).has_length(8);
+        assert_that(&password[..4].iter().all(char::is_ascii_uppercase)).is_true();
+        assert_that(&password[4..].iter().all(char::is_ascii_digit)).is_true();
+        assert_that(&((generated.entropy - (4.0 * 26f64.log2() + 4.0 * 10f64.log2())).abs() < 1e-9)).is_true();
+    }
+
+    #[test]
+    fn test_literals_and_escapes() {
+        let generated = Pattern::parse(r"ID-\d\{h{2}").unwrap().generate();
+
+        assert_that(&generated.password.len()).is_equal_to(7);
+        assert_that(&generated.password[..5].to_owned()).is_equal_to("ID-d{".to_owned());
+        assert_that(&generated.password[5..].chars().all(|c| c.is_ascii_hexdigit())).is_true();
+        assert_that(&((generated.entropy - 8.0).abs() < 1e-9)).is_true();
+    }
+
+    #[test]
+    fn test_custom_sets() {
+        let pattern = Pattern::parse(r"[dH\]^0\A\B]{6}").unwrap();
+
+        assert_that(&pattern.slots).has_length(6);
+        assert_that(&pattern.slots[5]).is_equal_to(Slot::Set("123456789CDEF]".chars().collect()));
+        assert_that(&((pattern.entropy() - (6.0 * 14f64.log2())).abs() < 1e-9)).is_true();
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in &["", "{3}", "d{x}", "d{3", "[d", "[^d]", "d\\", "a{4000000000}", "d{9999}d{2}"] {
+            assert_that(&matches!(Pattern::parse(pattern), Err(Error::InvalidGeneratorSettings { .. })))
+                .is_true();
+        }
+    }
+}
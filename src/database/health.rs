@@ -0,0 +1,220 @@
+use super::{
+    items::{
+        Entry,
+        Group,
+        Meta,
+    },
+    recycle_bin,
+    search::is_expired,
+};
+use crate::{
+    internal::{
+        cryptopraphy::sha256,
+        random,
+    },
+    results::Result,
+};
+
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+
+use std::collections::HashMap;
+
+const DEFAULT_STALE_DAYS: i64 = 365;
+const SALT_LENGTH: usize = 32;
+
+/// What `Database::health_report` counts as a problem
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthPolicy {
+    /// Entries not modified for longer are reported as stale
+    pub stale_after: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self { stale_after: Duration::days(DEFAULT_STALE_DAYS) }
+    }
+}
+
+/// An entry in a `HealthReport`
+#[derive(Debug)]
+pub struct HealthEntry<'a> {
+    /// Names of the groups between the root group and the entry
+    pub path: Vec<&'a str>,
+    pub entry: &'a Entry,
+}
+
+/// The problems with the entries outside the recycle bin, like KeePassXC's database reports
+#[derive(Debug, Default)]
+pub struct HealthReport<'a> {
+    /// Entries sharing a password, one list per password
+    pub reused: Vec<Vec<HealthEntry<'a>>>,
+    /// Entries not modified within `HealthPolicy::stale_after`
+    pub stale: Vec<HealthEntry<'a>>,
+    pub expired: Vec<HealthEntry<'a>>,
+    pub missing_username: Vec<HealthEntry<'a>>,
+    pub missing_url: Vec<HealthEntry<'a>>,
+}
+
+impl HealthReport<'_> {
+    /// Whether no entry has any of the problems, for policy checks
+    pub fn is_healthy(&self) -> bool {
+        self.reused.is_empty()
+            && self.stale.is_empty()
+            && self.expired.is_empty()
+            && self.missing_username.is_empty()
+            && self.missing_url.is_empty()
+    }
+}
+
+pub(crate) fn report<'a>(
+    root: &'a Group,
+    meta: &Meta,
+    policy: &HealthPolicy,
+    now: DateTime<Utc>,
+) -> Result<HealthReport<'a>> {
+    let entries: Vec<_> = recycle_bin::entries_outside_recycle_bin(root, meta)
+        .map(|found| (found.path, found.entry))
+        .collect();
+
+    let mut report = HealthReport::default();
+    let stale_before = now - policy.stale_after;
+
+    // passwords are compared by a salted hash, the salt keeps the hashes useless elsewhere
+    let salt = random::generate_secret_bytes(SALT_LENGTH);
+    let mut by_password: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+
+    for (index, (path, entry)) in entries.iter().enumerate() {
+        if let Some(password) = entry.password().filter(|password| !password.is_empty()) {
+            let hash = sha256(&[&salt, password.as_bytes()])?.to_vec();
+            by_password.entry(hash).or_default().push(index);
+        }
+
+        let report_entry = || HealthEntry { path: path.clone(), entry };
+        let modified = entry.times()
+            .and_then(|times| times.last_modification_time)
+            .and_then(|time| time.as_datetime());
+
+        if modified.is_some_and(|modified| modified < stale_before) {
+            report.stale.push(report_entry());
+        }
+        if is_expired(entry, now) {
+            report.expired.push(report_entry());
+        }
+        if is_blank(entry, "UserName") {
+            report.missing_username.push(report_entry());
+        }
+        if is_blank(entry, "URL") {
+            report.missing_url.push(report_entry());
+        }
+    }
+
+    // in the order of their first entry, the map has none
+    let mut reused: Vec<&Vec<usize>> = by_password.values().filter(|indices| indices.len() > 1).collect();
+    reused.sort_by_key(|indices| indices[0]);
+    report.reused = reused.into_iter()
+        .map(|indices| {
+            indices.iter()
+                .map(|index| HealthEntry { path: entries[*index].0.clone(), entry: entries[*index].1 })
+                .collect()
+        })
+        .collect();
+
+    Ok(report)
+}
+
+fn is_blank(entry: &Entry, key: &str) -> bool {
+    entry.get(key).map_or(true, |value| value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        items::{
+            TimestampValue,
+            Times,
+        },
+        test_helpers::{
+            entry,
+            recycle_bin,
+        },
+    };
+    use chrono::TimeZone;
+    use spectral::prelude::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 1).and_hms(12, 0, 0)
+    }
+
+    fn modified_entry(title: &str, user: &str, password: &str, url: &str, modified: DateTime<Utc>) -> Entry {
+        let mut entry = entry(title, user, password, url);
+
+        let mut times = Times::new();
+        times.last_modification_time = Some(TimestampValue::from(modified));
+        entry.set_times(&times);
+        entry
+    }
+
+    fn titles(entries: &[HealthEntry]) -> Vec<String> {
+        entries.iter().map(|found| found.entry.title().unwrap().to_string()).collect()
+    }
+
+    // root: mail, forum / Work: vpn, wiki / Bin: old
+    fn sample() -> (Group, Meta) {
+        let recent = now() - Duration::days(10);
+
+        let mut root = Group::root();
+        root.add_entry("mail", &modified_entry("mail", "alice", "hunter2", "https://mail.example.com", recent));
+        root.add_entry("forum", &modified_entry("forum", "alice", "hunter2", "", now() - Duration::days(400)));
+
+        let mut expired = modified_entry("vpn", "", "s3cret", "vpn.example.org", recent);
+        let mut times = expired.times().unwrap();
+        times.expires = Some(true);
+        times.expiry_time = Some(TimestampValue::from(now() - Duration::days(1)));
+        expired.set_times(&times);
+
+        let mut work = Group::new("Work", &Default::default(), &Default::default());
+        work.add_entry("vpn", &expired);
+        work.add_entry("wiki", &modified_entry("wiki", "bob", "s3cret", "https://wiki.example.com", recent));
+        root.add_group("Work", &work);
+
+        let (mut bin, meta) = recycle_bin("Bin");
+        bin.add_entry("old", &modified_entry("old", "", "hunter2", "", now() - Duration::days(900)));
+        root.add_group("Bin", &bin);
+
+        (root, meta)
+    }
+
+    #[test]
+    fn test_report_finds_each_problem() {
+        let (root, meta) = sample();
+
+        let report = report(&root, &meta, &HealthPolicy::default(), now()).unwrap();
+
+        let reused: Vec<Vec<String>> = report.reused.iter().map(|entries| titles(entries)).collect();
+        assert_that(&reused).is_equal_to(vec![
+            vec!["forum".to_owned(), "mail".to_owned()],
+            vec!["vpn".to_owned(), "wiki".to_owned()],
+        ]);
+        assert_that(&report.reused[1][0].path).is_equal_to(vec!["Work"]);
+        assert_that(&titles(&report.stale)).is_equal_to(vec!["forum".to_owned()]);
+        assert_that(&titles(&report.expired)).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles(&report.missing_username)).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles(&report.missing_url)).is_equal_to(vec!["forum".to_owned()]);
+        assert_that(&report.is_healthy()).is_false();
+    }
+
+    #[test]
+    fn test_report_uses_the_policy_age() {
+        let (root, meta) = sample();
+        let policy = HealthPolicy { stale_after: Duration::days(5) };
+
+        let report = report(&root, &meta, &policy, now()).unwrap();
+
+        assert_that(&report.stale).has_length(4);
+    }
+}
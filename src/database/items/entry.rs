@@ -6,21 +6,46 @@ use super::{
     UuidValue,
     Times,
     Binary,
+    MemoryProtection,
+    ProtectedValue,
+    ValueGuard,
+};
+use super::otp::{
+    self,
+    TotpSettings,
+};
+use crate::{
+    database::{
+        quality::{
+            self,
+            PasswordQuality,
+        },
+        references::{
+            reference_to,
+            ReferenceField,
+        },
+    },
+    errors::Error,
+    results::Result,
 };
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::SystemTime,
+};
 
 /// A types entry containing several key-value fields.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Entry {
     uuid: UuidValue,
     icon_id: i32,
+    custom_icon_uuid: Option<UuidValue>,
     foreground_color: Option<ColorValue>,
     background_color: Option<ColorValue>,
     override_url: Option<String>,
     tags: Option<String>,
     times: Option<Times>,
-    binary: Option<Binary>,
+    binaries: Vec<Binary>,
     fields: HashMap<String, StringValue>,
     autotype: Option<AutoType>,
     history: Vec<Entry>,
@@ -31,12 +56,13 @@ impl Entry {
         Self {
             uuid: uuid.to_owned(),
             icon_id: -1,
+            custom_icon_uuid: None,
             foreground_color: None,
             background_color: None,
             override_url: None,
             tags: None,
             times: None,
-            binary: None,
+            binaries: vec![],
             fields: HashMap::new(),
             autotype: None,
             history: vec![],
@@ -77,6 +103,15 @@ impl Entry {
         self
     }
 
+    pub fn custom_icon_uuid(&self) -> Option<UuidValue> {
+        self.custom_icon_uuid.clone()
+    }
+
+    pub fn set_custom_icon_uuid(&mut self, value: &UuidValue) -> &mut Self {
+        self.custom_icon_uuid = Some(value.to_owned());
+        self
+    }
+
     pub fn foreground_color(&self) -> Option<ColorValue> {
         self.foreground_color.clone()
     }
@@ -107,15 +142,48 @@ impl Entry {
         self
     }
 
+    pub fn times(&self) -> Option<Times> {
+        self.times.clone()
+    }
+
+    pub fn set_times(&mut self, value: &Times) -> &mut Self {
+        self.times = Some(value.to_owned());
+        self
+    }
+
+    pub fn tags(&self) -> Option<String> {
+        self.tags.clone()
+    }
+
+    pub fn set_tags(&mut self, value: &str) -> &mut Self {
+        self.tags = Some(value.to_owned());
+        self
+    }
+
+    /// The first attachment, `binaries` has all of them
     pub fn binary(&self) -> Option<Binary> {
-        self.binary.clone()
+        self.binaries.first().cloned()
     }
 
+    /// The attachments, the ID of each is its file name
+    pub fn binaries(&self) -> Vec<Binary> {
+        self.binaries.clone()
+    }
+
+    /// Attaches `value`, replacing the attachment with the same name
     pub fn set_binary(
         &mut self,
         value: &Binary) -> &mut Self
     {
-        self.binary = Some(value.to_owned());
+        match self.binaries.iter_mut().find(|binary| binary.id() == value.id()) {
+            Some(binary) => *binary = value.to_owned(),
+            None => self.binaries.push(value.to_owned()),
+        }
+        self
+    }
+
+    pub fn remove_binary(&mut self, name: &str) -> &mut Self {
+        self.binaries.retain(|binary| binary.id() != name);
         self
     }
 
@@ -128,6 +196,55 @@ impl Entry {
         self
     }
 
+    /// Makes the field `key` a reference to `field` of `target`, resolved with
+    /// `Database::resolve_field`
+    pub fn set_reference(
+        &mut self,
+        key: &str,
+        target: &Entry,
+        field: ReferenceField) -> &mut Self
+    {
+        self.add(key, &StringValue::UnprotectedString(reference_to(target, field)))
+    }
+
+    /// The TOTP code valid at `at`, `None` if the entry has no TOTP settings. They are read
+    /// from the KeePassXC `otp` URI or else from the KeePass `TimeOtp-*` fields.
+    pub fn totp(&self, at: SystemTime) -> Result<Option<String>> {
+        otp::totp_settings(self)?
+            .map(|settings| settings.code(at))
+            .transpose()
+    }
+
+    /// The HOTP code for the stored counter, `None` if the entry has no HOTP settings.
+    /// The counter is incremented, so the entry has to be saved afterwards.
+    pub fn hotp(&mut self) -> Result<Option<String>> {
+        let settings = match otp::hotp_settings(self)? {
+            Some(settings) => settings,
+            None => return Ok(None),
+        };
+
+        let counter = settings.counter.checked_add(1).ok_or_else(|| Error::InvalidOtp {
+            reason: "the HOTP counter is exhausted".to_owned(),
+        })?;
+
+        let code = settings.code()?;
+        otp::set_hotp_counter(self, counter)?;
+
+        Ok(Some(code))
+    }
+
+    /// Stores `settings` as a protected `otpauth://` URI in the `otp` field, like KeePassXC.
+    /// Fails with `Error::InvalidOtp` if the settings have an unsupported number of digits.
+    pub fn set_totp(
+        &mut self,
+        settings: &TotpSettings,
+        issuer: &str,
+        account: &str) -> Result<&mut Self>
+    {
+        let uri = settings.uri(issuer, account)?;
+        Ok(self.add(otp::OTP_FIELD, &StringValue::ProtectedString(ProtectedValue::from(uri))))
+    }
+
     pub fn remove(
         &mut self,
         key: &str) -> &mut Self
@@ -176,17 +293,58 @@ impl Entry {
         self.autotype = Some(autotype.clone());
         self
     }
+
+    pub fn autotype(&self) -> Option<AutoType> {
+        self.autotype.clone()
+    }
+
+    /// Protects or unprotects the standard fields of this entry and its history as
+    /// `protection` says, custom fields keep their state
+    pub fn apply_memory_protection(&mut self, protection: &MemoryProtection) -> &mut Self {
+        for (key, value) in self.fields.iter_mut() {
+            let protect = protection.is_protected(key);
+
+            let changed = match value {
+                StringValue::UnprotectedString(plain) if protect => {
+                    Some(StringValue::ProtectedString(ProtectedValue::from(plain.as_str())))
+                }
+                StringValue::ProtectedString(protected)
+                    if !protect && MemoryProtection::is_standard_field(key) =>
+                {
+                    ValueGuard::decrypt(protected)
+                        .map(|plain| StringValue::UnprotectedString(plain.to_string()))
+                }
+                _ => None,
+            };
+
+            if let Some(changed) = changed {
+                *value = changed;
+            }
+        }
+
+        for item in self.history.iter_mut() {
+            item.apply_memory_protection(protection);
+        }
+
+        self
+    }
+
+    pub(crate) fn fields(&self) -> impl Iterator<Item = (&String, &StringValue)> {
+        self.fields.iter()
+    }
 }
 
 impl<'a> Entry {
+    /// Returns the string field `key`. Protected values are decrypted into the returned guard,
+    /// which wipes the plaintext when dropped, so keep it short-lived.
     pub fn get(
         &'a self,
         key: &str,
-    ) -> Option<&'a str> {
+    ) -> Option<ValueGuard<'a>> {
         match self.fields.get(key) {
-            Some(&StringValue::Bytes(_)) => None,
-            Some(&StringValue::ProtectedString(ref pv)) => std::str::from_utf8(pv.unsecure()).ok(),
-            Some(&StringValue::UnprotectedString(ref uv)) => Some(&uv),
+            Some(StringValue::Bytes(_)) => None,
+            Some(StringValue::ProtectedString(pv)) => ValueGuard::decrypt(pv),
+            Some(StringValue::UnprotectedString(uv)) => Some(ValueGuard::plain(uv)),
             None => None,
         }
     }
@@ -196,9 +354,9 @@ impl<'a> Entry {
         key: &str,
     ) -> Option<&'a [u8]> {
         match self.fields.get(key) {
-            Some(&StringValue::Bytes(ref b)) => Some(&b),
-            Some(&StringValue::ProtectedString(_)) => None,
-            Some(&StringValue::UnprotectedString(_)) => None,
+            Some(StringValue::Bytes(b)) => Some(b),
+            Some(StringValue::ProtectedString(_)) => None,
+            Some(StringValue::UnprotectedString(_)) => None,
             None => None,
         }
     }
@@ -210,30 +368,93 @@ impl<'a> Entry {
         self.history.get(index)
     }
 
-    pub fn get_title(&'a self) -> Option<&'a str> {
+    pub fn get_title(&'a self) -> Option<ValueGuard<'a>> {
         self.get("Title")
     }
 
-    pub fn title(&'a self) -> Option<&'a str> {
+    pub fn title(&'a self) -> Option<ValueGuard<'a>> {
         self.get("Title")
     }
 
-    pub fn get_username(&'a self) -> Option<&'a str> {
+    pub fn get_username(&'a self) -> Option<ValueGuard<'a>> {
         self.get("UserName")
     }
-    pub fn username(&'a self) -> Option<&'a str> {
+    pub fn username(&'a self) -> Option<ValueGuard<'a>> {
         self.get("UserName")
     }
 
-    pub fn get_password(&'a self) -> Option<&'a str> {
+    pub fn get_password(&'a self) -> Option<ValueGuard<'a>> {
         self.get("Password")
     }
 
-    pub fn password(&'a self) -> Option<&'a str> {
+    pub fn password(&'a self) -> Option<ValueGuard<'a>> {
         self.get("Password")
     }
+
+    /// Estimates the strength of the password, `None` if the entry has none
+    pub fn password_quality(&self) -> Option<PasswordQuality> {
+        self.password().map(|password| quality::estimate(&password))
+    }
 }
 
 impl Identifier for Entry {
     const IDENTIFIER: &'static [u8] = b"Entry";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_get_protected_value() {
+        let mut entry = Entry::default();
+        entry.add("Title", &StringValue::UnprotectedString("mail".to_owned()));
+        entry.add("Password", &StringValue::ProtectedString(ProtectedValue::from("secret")));
+
+        assert_that(&entry.title().as_deref()).is_equal_to(Some("mail"));
+        assert_that(&entry.password().as_deref()).is_equal_to(Some("secret"));
+        assert_that(&entry.get_password().as_deref()).is_equal_to(Some("secret"));
+        assert_that(&entry.username().is_none()).is_true();
+        assert_that(&entry.get("Missing").is_none()).is_true();
+    }
+
+    #[test]
+    fn test_get_does_not_keep_plaintext() {
+        let mut entry = Entry::default();
+        entry.add("Password", &StringValue::ProtectedString(ProtectedValue::from("secret")));
+
+        let before = format!("{:?}", entry);
+        drop(entry.password());
+
+        // nothing but the ciphertext is left on the entry after the guard is gone
+        assert_that(&format!("{:?}", entry)).is_equal_to(before);
+        assert_that(&format!("{:?}", entry).contains("secret")).is_false();
+    }
+
+    #[test]
+    fn test_apply_memory_protection() {
+        let mut entry = Entry::default();
+        entry.add("Title", &StringValue::UnprotectedString("mail".to_owned()));
+        entry.add("Password", &StringValue::UnprotectedString("secret".to_owned()));
+        entry.add("Notes", &StringValue::ProtectedString(ProtectedValue::from("notes")));
+        entry.add("PIN", &StringValue::ProtectedString(ProtectedValue::from("1234")));
+
+        let protection = MemoryProtection {
+            protect_title: true,
+            protect_notes: false,
+            ..MemoryProtection::default()
+        };
+
+        entry.apply_memory_protection(&protection);
+
+        assert_that(&entry.fields["Title"])
+            .is_equal_to(StringValue::ProtectedString(ProtectedValue::from("mail")));
+        assert_that(&entry.fields["Password"])
+            .is_equal_to(StringValue::ProtectedString(ProtectedValue::from("secret")));
+        assert_that(&entry.fields["Notes"])
+            .is_equal_to(StringValue::UnprotectedString("notes".to_owned()));
+        assert_that(&entry.fields["PIN"])
+            .is_equal_to(StringValue::ProtectedString(ProtectedValue::from("1234")));
+    }
+}
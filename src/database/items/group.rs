@@ -3,6 +3,7 @@ use super::{
     Node,
     NodeIter,
     Entry,
+    MemoryProtection,
     Notes,
     Times,
     UuidValue
@@ -17,10 +18,12 @@ pub struct Group {
     pub name: String,
     pub notes: Option<Notes>,
     pub icon_id: u32,
+    pub custom_icon_uuid: Option<UuidValue>,
     pub times: Times,
     pub is_expanded: bool,
     pub enable_auto_type: bool,
-    pub enable_searching: bool,
+    /// `None` inherits the setting of the parent group, see `searching_enabled`
+    pub enable_searching: Option<bool>,
     pub last_top_visible_entry: Option<String>,
 
     pub child_groups: HashMap<String, Group>,
@@ -39,13 +42,14 @@ impl Group {
             name: name.to_owned(),
             notes: None,
             icon_id: 0,
+            custom_icon_uuid: None,
             times: Times::default(),
             is_expanded: false,
             enable_auto_type: false,
-            enable_searching: false,
+            enable_searching: None,
             last_top_visible_entry: None,
             child_groups: child_groups.clone(),
-            entries: entries.clone()
+            entries: entries.clone(),
         }
     }
 
@@ -110,9 +114,10 @@ impl Group {
         self
     }
 
-    pub fn set_enable_searching(&mut self, value: bool) -> &mut Self {
-        self.enable_searching = value;
-        self
+    /// Whether the entries of this group are searched, given the resolved setting of its
+    /// parent. Searching is enabled for the root group unless it is set otherwise.
+    pub fn searching_enabled(&self, parent_enabled: bool) -> bool {
+        self.enable_searching.unwrap_or(parent_enabled)
     }
 
     pub fn add_group(&mut self, name: &str, group: &Group) -> &mut Self {
@@ -152,6 +157,19 @@ impl Group {
     pub fn has_entries(&self) -> bool {
         !self.entries.is_empty()
     }
+
+    /// Applies `protection` to all entries of this group and its child groups
+    pub fn apply_memory_protection(&mut self, protection: &MemoryProtection) -> &mut Self {
+        for entry in self.entries.values_mut() {
+            entry.apply_memory_protection(protection);
+        }
+
+        for group in self.child_groups.values_mut() {
+            group.apply_memory_protection(protection);
+        }
+
+        self
+    }
 }
 
 impl<'a> Group {
@@ -211,14 +229,15 @@ mod tests {
         group
             .set_name("other")
             .set_enable_auto_type(true)
-            .set_enable_searching(true)
             .set_expanded(true)
-            .set_notes(&Notes {});
+            .set_notes(&Notes::new("some notes"));
+        group.enable_searching = Some(true);
 
         assert_that(&group.name).is_equal_to("other".to_owned());
         assert_that(&group.enable_auto_type).is_true();
-        assert_that(&group.enable_searching).is_true();
+        assert_that(&group.enable_searching).is_equal_to(Some(true));
+        assert_that(&group.searching_enabled(false)).is_true();
         assert_that(&group.is_expanded).is_true();
-        assert_that(&group.notes).is_some().is_equal_to(Notes{});
+        assert_that(&group.notes).is_some().is_equal_to(Notes::new("some notes"));
     }
 }
@@ -21,6 +21,27 @@ impl Default for MemoryProtection {
     }
 }
 
+impl MemoryProtection {
+    /// The fields whose protection is controlled by these flags
+    pub const STANDARD_FIELDS: [&'static str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+    pub fn is_standard_field(key: &str) -> bool {
+        Self::STANDARD_FIELDS.contains(&key)
+    }
+
+    /// Whether the standard field `key` has to be protected, custom fields never are
+    pub fn is_protected(&self, key: &str) -> bool {
+        match key {
+            "Title" => self.protect_title,
+            "UserName" => self.protect_user_name,
+            "Password" => self.protect_password,
+            "URL" => self.protect_url,
+            "Notes" => self.protect_notes,
+            _ => false,
+        }
+    }
+}
+
 impl Identifier for MemoryProtection {
     const IDENTIFIER: &'static [u8] = b"MemoryProtection";
 }
@@ -43,4 +64,20 @@ mod tests {
                 protect_notes: true
             });
     }
+
+    #[test]
+    fn test_is_protected() {
+        let memory_protection = MemoryProtection {
+            protect_title: true,
+            protect_password: false,
+            ..MemoryProtection::default()
+        };
+
+        assert_that(&memory_protection.is_protected("Title")).is_true();
+        assert_that(&memory_protection.is_protected("UserName")).is_false();
+        assert_that(&memory_protection.is_protected("Password")).is_false();
+        assert_that(&memory_protection.is_protected("URL")).is_true();
+        assert_that(&memory_protection.is_protected("Notes")).is_true();
+        assert_that(&memory_protection.is_protected("Custom")).is_false();
+    }
 }
@@ -12,7 +12,7 @@ use crate::database::items::values::{
 };
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) struct Meta {
+pub struct Meta {
     generator: String,
     database_name: String,
     database_name_change: Option<TimestampValue>,
@@ -36,6 +36,7 @@ pub(crate) struct Meta {
     history_max_items: i32,
     history_max_size: usize,
     binaries: Vec<Binary>,
+    unknown_meta_streams: Vec<String>,
 }
 
 impl Meta {
@@ -62,6 +63,11 @@ impl Meta {
         self
     }
 
+    pub fn set_database_name_change(&mut self, value: Option<TimestampValue>) -> &mut Self {
+        self.database_name_change = value;
+        self
+    }
+
     pub fn database_description(&self) -> String {
         self.database_description.clone()
     }
@@ -76,6 +82,11 @@ impl Meta {
         self
     }
 
+    pub fn set_database_description_changed(&mut self, value: Option<TimestampValue>) -> &mut Self {
+        self.database_description_changed = value;
+        self
+    }
+
     pub fn default_user_name(&self) -> String {
         self.default_user_name.clone()
     }
@@ -90,6 +101,20 @@ impl Meta {
         self
     }
 
+    pub fn set_default_user_name_changed(&mut self, value: Option<TimestampValue>) -> &mut Self {
+        self.default_user_name_changed = value;
+        self
+    }
+
+    pub fn maintenance_history_days(&self) -> i32 {
+        self.maintenance_history_days
+    }
+
+    pub fn set_maintenance_history_days(&mut self, value: i32) -> &mut Self {
+        self.maintenance_history_days = value;
+        self
+    }
+
     pub fn color(&self) -> Option<ColorValue> {
         self.color.clone()
     }
@@ -120,6 +145,30 @@ impl Meta {
         self
     }
 
+    pub fn set_master_key_changed_time(&mut self, value: Option<TimestampValue>) -> &mut Self {
+        self.master_key_changed = value;
+        self
+    }
+
+    pub fn set_master_key_change_rec(&mut self, value: i32) -> &mut Self {
+        self.master_key_change_rec = value;
+        self
+    }
+
+    pub fn set_master_key_change_force(&mut self, value: i32) -> &mut Self {
+        self.master_key_change_force = value;
+        self
+    }
+
+    pub fn memory_protection(&self) -> &MemoryProtection {
+        &self.memory_protection
+    }
+
+    pub fn set_memory_protection(&mut self, value: &MemoryProtection) -> &mut Self {
+        self.memory_protection = value.to_owned();
+        self
+    }
+
     pub fn protect_title(&self) -> bool {
         self.memory_protection.protect_title
     }
@@ -224,6 +273,11 @@ impl Meta {
         self
     }
 
+    pub fn set_entry_templates_group_changed(&mut self, value: Option<TimestampValue>) -> &mut Self {
+        self.entry_templates_group_changed = value;
+        self
+    }
+
     pub fn last_selected_group(&self) -> Option<UuidValue> {
         self.last_selected_group.clone()
     }
@@ -286,6 +340,16 @@ impl Meta {
         self.binaries.clear();
         self
     }
+
+    /// The names of the KeePass 1.x meta-streams read without a KDBX equivalent
+    pub fn unknown_meta_streams(&self) -> Vec<String> {
+        self.unknown_meta_streams.clone()
+    }
+
+    pub(crate) fn add_unknown_meta_stream(&mut self, name: &str) -> &mut Self {
+        self.unknown_meta_streams.push(name.to_owned());
+        self
+    }
 }
 
 impl Default for Meta {
@@ -314,6 +378,7 @@ impl Default for Meta {
             history_max_items: -1,
             history_max_size: 512 * 1024,
             binaries: vec![],
+            unknown_meta_streams: vec![],
         }
     }
 }
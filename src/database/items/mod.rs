@@ -6,6 +6,8 @@ pub(crate) mod history;
 pub(crate) mod metadata;
 pub(crate) mod node;
 pub(crate) mod notes;
+pub mod otp;
+pub mod placeholders;
 pub(crate) mod times;
 pub(crate) mod values;
 
@@ -41,9 +43,12 @@ pub(crate) use times::Times;
 pub(crate) use values::{
     Base64Value,
     ColorValue,
+    IconValue,
+    ProtectedValue,
     StringValue,
     TimestampValue,
     UuidValue,
+    ValueGuard,
 };
 
 pub struct Database {
@@ -1,7 +1,15 @@
 use super::Identifier;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Notes {}
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Notes {
+    pub text: String,
+}
+
+impl Notes {
+    pub fn new(text: &str) -> Self {
+        Self { text: text.to_owned() }
+    }
+}
 
 impl Identifier for Notes {
     const IDENTIFIER: &'static [u8] = b"Notes";
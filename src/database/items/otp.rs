@@ -0,0 +1,557 @@
+use super::{
+    Entry,
+    ProtectedValue,
+    StringValue,
+};
+use crate::{
+    errors::Error,
+    internal::{
+        cryptopraphy::{
+            hmac,
+            hmac_sha1,
+            hmac_sha512,
+        },
+        random,
+    },
+    results::Result,
+};
+
+use base32::Alphabet;
+use url::{
+    form_urlencoded,
+    Position,
+    Url,
+};
+use zeroize::Zeroizing;
+
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+/// The field KeePassXC keeps the `otpauth://` URI in
+pub const OTP_FIELD: &str = "otp";
+
+const DEFAULT_PERIOD: u64 = 30;
+const DEFAULT_DIGITS: u32 = 6;
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+const STEAM_LENGTH: usize = 5;
+
+// the size RFC 4226 recommends for HMAC-SHA-1 secrets
+const GENERATED_SECRET_LENGTH: usize = 20;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// How the truncated HMAC is turned into a code
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OtpFormat {
+    /// 6 to 10 decimal digits
+    Digits(u32),
+    /// The five characters of Steam Guard
+    Steam,
+}
+
+/// A time-based one-time password configuration (RFC 6238)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TotpSettings {
+    pub secret: Zeroizing<Vec<u8>>,
+    pub period: u64,
+    pub algorithm: OtpAlgorithm,
+    pub format: OtpFormat,
+}
+
+/// A counter-based one-time password configuration (RFC 4226)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HotpSettings {
+    pub secret: Zeroizing<Vec<u8>>,
+    pub counter: u64,
+    pub algorithm: OtpAlgorithm,
+    pub format: OtpFormat,
+}
+
+impl TotpSettings {
+    /// A fresh random secret with the defaults every authenticator app understands:
+    /// SHA-1, 6 digits and 30 seconds
+    pub fn generate() -> Self {
+        Self {
+            secret: Zeroizing::new(random::generate_secret_bytes(GENERATED_SECRET_LENGTH)),
+            period: DEFAULT_PERIOD,
+            algorithm: OtpAlgorithm::Sha1,
+            format: OtpFormat::Digits(DEFAULT_DIGITS),
+        }
+    }
+
+    /// The code valid at `at`
+    pub fn code(&self, at: SystemTime) -> Result<String> {
+        if self.period == 0 {
+            return Err(invalid("the period must not be zero"));
+        }
+
+        let seconds = at
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| invalid("time before 1970"))?
+            .as_secs();
+
+        code(&self.secret, seconds / self.period, self.algorithm, self.format)
+    }
+
+    /// The `otpauth://totp/` URI authenticator apps import, usually from a QR code
+    pub fn uri(&self, issuer: &str, account: &str) -> Result<String> {
+        checked(self.format)?;
+
+        let mut url = Url::parse("otpauth://totp/").expect("static URI is valid");
+        url.set_path(&format!("{}:{}", issuer, account));
+
+        {
+            let mut query = url.query_pairs_mut();
+            let secret = Zeroizing::new(base32::encode(Alphabet::RFC4648 { padding: false }, &self.secret));
+            query
+                .append_pair("secret", &secret)
+                .append_pair("period", &self.period.to_string())
+                .append_pair("algorithm", algorithm_name(self.algorithm))
+                .append_pair("issuer", issuer);
+
+            match self.format {
+                OtpFormat::Digits(digits) => query.append_pair("digits", &digits.to_string()),
+                OtpFormat::Steam => query.append_pair("encoder", "steam"),
+            };
+        }
+
+        Ok(url.to_string())
+    }
+}
+
+impl HotpSettings {
+    /// The code for the current counter
+    pub fn code(&self) -> Result<String> {
+        code(&self.secret, self.counter, self.algorithm, self.format)
+    }
+}
+
+/// Reads the TOTP settings of `entry` from its `otp` URI or else from the `TimeOtp-*` fields
+pub(crate) fn totp_settings(entry: &Entry) -> Result<Option<TotpSettings>> {
+    if let Some(uri) = entry.get(OTP_FIELD) {
+        let uri = OtpUri::parse(&uri)?;
+
+        return if uri.kind == "totp" {
+            Ok(Some(TotpSettings {
+                secret: uri.secret()?,
+                period: uri.number("period")?.unwrap_or(DEFAULT_PERIOD),
+                algorithm: uri.algorithm()?,
+                format: uri.format()?,
+            }))
+        } else {
+            Ok(None)
+        };
+    }
+
+    let secret = match keepass_secret(entry, "TimeOtp-Secret")? {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    let algorithm = match entry.get("TimeOtp-Algorithm").as_deref() {
+        None | Some("HMAC-SHA-1") => OtpAlgorithm::Sha1,
+        Some("HMAC-SHA-256") => OtpAlgorithm::Sha256,
+        Some("HMAC-SHA-512") => OtpAlgorithm::Sha512,
+        Some(other) => return Err(invalid(&format!("unknown algorithm {}", other))),
+    };
+
+    Ok(Some(TotpSettings {
+        secret,
+        period: keepass_number(entry, "TimeOtp-Period")?.unwrap_or(DEFAULT_PERIOD),
+        algorithm,
+        format: digits(keepass_number(entry, "TimeOtp-Length")?.unwrap_or(u64::from(DEFAULT_DIGITS)))?,
+    }))
+}
+
+/// Reads the HOTP settings of `entry` from its `otp` URI or else from the `HmacOtp-*` fields
+pub(crate) fn hotp_settings(entry: &Entry) -> Result<Option<HotpSettings>> {
+    if let Some(uri) = entry.get(OTP_FIELD) {
+        let uri = OtpUri::parse(&uri)?;
+
+        return if uri.kind == "hotp" {
+            Ok(Some(HotpSettings {
+                secret: uri.secret()?,
+                counter: uri.number("counter")?.unwrap_or(0),
+                algorithm: uri.algorithm()?,
+                format: uri.format()?,
+            }))
+        } else {
+            Ok(None)
+        };
+    }
+
+    let secret = match keepass_secret(entry, "HmacOtp-Secret")? {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    Ok(Some(HotpSettings {
+        secret,
+        counter: keepass_number(entry, "HmacOtp-Counter")?.unwrap_or(0),
+        algorithm: OtpAlgorithm::Sha1,
+        format: OtpFormat::Digits(DEFAULT_DIGITS),
+    }))
+}
+
+/// Stores `counter` where `hotp_settings` found the settings, keeping the field's protection
+pub(crate) fn set_hotp_counter(entry: &mut Entry, counter: u64) -> Result<()> {
+    if let Some(uri) = entry.get(OTP_FIELD) {
+        let url = Url::parse(&uri).map_err(|e| invalid(&e.to_string()))?;
+
+        // encoding takes at most three bytes for every byte of the URI, so the buffers never
+        // grow and leave a copy of the secret behind
+        let capacity = 3 * uri.len() + 32;
+        let mut query = Zeroizing::new(String::with_capacity(capacity));
+        {
+            let mut serializer = form_urlencoded::Serializer::new(&mut *query);
+            for (key, value) in url.query_pairs().filter(|(key, _)| key != "counter") {
+                serializer.append_pair(&key, &Zeroizing::new(value.into_owned()));
+            }
+            serializer.append_pair("counter", &counter.to_string());
+        }
+
+        let mut updated = Zeroizing::new(String::with_capacity(2 * capacity));
+        updated.push_str(&url[..Position::AfterPath]);
+        updated.push('?');
+        updated.push_str(&query);
+        updated.push_str(&url[Position::AfterQuery..]);
+
+        // the serialized URI still holds the secret
+        drop(Zeroizing::new(String::from(url)));
+
+        set_keeping_protection(entry, OTP_FIELD, &updated);
+    } else {
+        set_keeping_protection(entry, "HmacOtp-Counter", &counter.to_string());
+    }
+
+    Ok(())
+}
+
+fn set_keeping_protection(entry: &mut Entry, key: &str, value: &str) {
+    let protected = entry
+        .fields()
+        .any(|(k, v)| k == key && matches!(v, StringValue::ProtectedString(_)));
+
+    let value = if protected {
+        StringValue::ProtectedString(ProtectedValue::from(value))
+    } else {
+        StringValue::UnprotectedString(value.to_owned())
+    };
+
+    entry.add(key, &value);
+}
+
+// RFC 4226 dynamic truncation of the HMAC over the big-endian counter
+fn code(secret: &[u8], counter: u64, algorithm: OtpAlgorithm, format: OtpFormat) -> Result<String> {
+    if secret.is_empty() {
+        return Err(invalid("the secret is empty"));
+    }
+
+    let message = counter.to_be_bytes();
+    let mac = match algorithm {
+        OtpAlgorithm::Sha1 => hmac_sha1(&[&message], secret)?.to_vec(),
+        OtpAlgorithm::Sha256 => hmac(&[&message], secret)?.to_vec(),
+        OtpAlgorithm::Sha512 => hmac_sha512(&[&message], secret)?.to_vec(),
+    };
+
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let mut value = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]])
+        & 0x7fff_ffff;
+
+    Ok(match checked(format)? {
+        OtpFormat::Digits(digits) => format!(
+            "{:0width$}",
+            u64::from(value) % 10u64.pow(digits),
+            width = digits as usize
+        ),
+        OtpFormat::Steam => (0..STEAM_LENGTH)
+            .map(|_| {
+                let c = STEAM_ALPHABET[value as usize % STEAM_ALPHABET.len()] as char;
+                value /= STEAM_ALPHABET.len() as u32;
+                c
+            })
+            .collect(),
+    })
+}
+
+fn digits(digits: u64) -> Result<OtpFormat> {
+    match digits {
+        6..=10 => Ok(OtpFormat::Digits(digits as u32)),
+        _ => Err(invalid(&format!("{} digits are not supported", digits))),
+    }
+}
+
+// the fields of the settings are public, so the digits may not have come through `digits`
+fn checked(format: OtpFormat) -> Result<OtpFormat> {
+    match format {
+        OtpFormat::Digits(count) => digits(u64::from(count)),
+        OtpFormat::Steam => Ok(format),
+    }
+}
+
+fn algorithm_name(algorithm: OtpAlgorithm) -> &'static str {
+    match algorithm {
+        OtpAlgorithm::Sha1 => "SHA1",
+        OtpAlgorithm::Sha256 => "SHA256",
+        OtpAlgorithm::Sha512 => "SHA512",
+    }
+}
+
+// KeePass stores the secret in one of four encodings, the field suffix says which
+fn keepass_secret(entry: &Entry, prefix: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    if let Some(secret) = entry.get(prefix) {
+        return Ok(Some(Zeroizing::new(secret.as_bytes().to_vec())));
+    }
+    if let Some(secret) = entry.get(&format!("{}-Hex", prefix)) {
+        let secret: Zeroizing<String> = Zeroizing::new(secret.chars().filter(|c| !c.is_whitespace()).collect());
+        return hex::decode(&*secret)
+            .map(|secret| Some(Zeroizing::new(secret)))
+            .map_err(|e| invalid(&e.to_string()));
+    }
+    if let Some(secret) = entry.get(&format!("{}-Base32", prefix)) {
+        return decode_base32(&secret).map(Some);
+    }
+    if let Some(secret) = entry.get(&format!("{}-Base64", prefix)) {
+        return base64::decode(secret.trim())
+            .map(|secret| Some(Zeroizing::new(secret)))
+            .map_err(|e| invalid(&e.to_string()));
+    }
+
+    Ok(None)
+}
+
+fn keepass_number(entry: &Entry, key: &str) -> Result<Option<u64>> {
+    entry
+        .get(key)
+        .map(|value| value.trim().parse().map_err(|_| invalid(&format!("{} is not a number", key))))
+        .transpose()
+}
+
+// Authenticators print secrets in lower case, in groups and sometimes padded
+fn decode_base32(value: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let normalized: Zeroizing<String> = Zeroizing::new(
+        value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    );
+
+    base32::decode(Alphabet::RFC4648 { padding: false }, &normalized)
+        .map(Zeroizing::new)
+        .ok_or_else(|| invalid("the secret is not valid base32"))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidOtp { reason: reason.to_owned() }
+}
+
+// The parts of an `otpauth://<kind>/<label>?<parameters>` URI
+struct OtpUri {
+    kind: String,
+    parameters: Vec<(String, Zeroizing<String>)>,
+}
+
+impl OtpUri {
+    fn parse(uri: &str) -> Result<Self> {
+        let url = Url::parse(uri.trim()).map_err(|e| invalid(&e.to_string()))?;
+
+        if url.scheme() != "otpauth" {
+            return Err(invalid("not an otpauth URI"));
+        }
+
+        let parsed = Self {
+            kind: url.host_str().unwrap_or_default().to_ascii_lowercase(),
+            parameters: url
+                .query_pairs()
+                .map(|(key, value)| (key.to_ascii_lowercase(), Zeroizing::new(value.into_owned())))
+                .collect(),
+        };
+
+        // the serialized URI still holds the secret
+        drop(Zeroizing::new(String::from(url)));
+
+        Ok(parsed)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn secret(&self) -> Result<Zeroizing<Vec<u8>>> {
+        decode_base32(self.get("secret").ok_or_else(|| invalid("the URI has no secret"))?)
+    }
+
+    fn number(&self, key: &str) -> Result<Option<u64>> {
+        self.get(key)
+            .map(|value| value.parse().map_err(|_| invalid(&format!("{} is not a number", key))))
+            .transpose()
+    }
+
+    fn algorithm(&self) -> Result<OtpAlgorithm> {
+        match self.get("algorithm").map(str::to_ascii_uppercase).as_deref() {
+            None | Some("SHA1") => Ok(OtpAlgorithm::Sha1),
+            Some("SHA256") => Ok(OtpAlgorithm::Sha256),
+            Some("SHA512") => Ok(OtpAlgorithm::Sha512),
+            Some(other) => Err(invalid(&format!("unknown algorithm {}", other))),
+        }
+    }
+
+    fn format(&self) -> Result<OtpFormat> {
+        if self.get("encoder").is_some_and(|encoder| encoder.eq_ignore_ascii_case("steam")) {
+            return Ok(OtpFormat::Steam);
+        }
+
+        digits(self.number("digits")?.unwrap_or(u64::from(DEFAULT_DIGITS)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::time::Duration;
+
+    // the RFC 4226 and RFC 6238 test secret, "12345678901234567890"
+    const SECRET_BASE32: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn entry_with(fields: &[(&str, &str)]) -> Entry {
+        let mut entry = Entry::default();
+        for (key, value) in fields {
+            entry.add(key, &StringValue::UnprotectedString(value.to_string()));
+        }
+        entry
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let sha1 = entry_with(&[(OTP_FIELD, &format!("otpauth://totp/x?secret={}&digits=8", SECRET_BASE32))]);
+        let sha256 = entry_with(&[
+            ("TimeOtp-Secret", "12345678901234567890123456789012"),
+            ("TimeOtp-Algorithm", "HMAC-SHA-256"),
+            ("TimeOtp-Length", "8"),
+        ]);
+        let sha512 = entry_with(&[
+            ("TimeOtp-Secret-Hex", &hex::encode(b"1234567890123456789012345678901234567890123456789012345678901234")),
+            ("TimeOtp-Algorithm", "HMAC-SHA-512"),
+            ("TimeOtp-Length", "8"),
+        ]);
+
+        assert_that(&sha1.totp(at(59)).unwrap()).is_equal_to(Some("94287082".to_owned()));
+        assert_that(&sha1.totp(at(1111111109)).unwrap()).is_equal_to(Some("07081804".to_owned()));
+        assert_that(&sha256.totp(at(59)).unwrap()).is_equal_to(Some("46119246".to_owned()));
+        assert_that(&sha512.totp(at(59)).unwrap()).is_equal_to(Some("90693936".to_owned()));
+    }
+
+    #[test]
+    fn test_totp_keepass_fields_and_steam() {
+        let keepass = entry_with(&[
+            ("TimeOtp-Secret-Base32", "gezd gnbv gy3t qojq gezd gnbv gy3t qojq"),
+            ("TimeOtp-Period", "60"),
+        ]);
+        let steam = entry_with(&[(OTP_FIELD, &format!("otpauth://totp/Steam:me?secret={}&encoder=steam", SECRET_BASE32))]);
+
+        assert_that(&keepass.totp(at(119)).unwrap()).is_equal_to(Some("287082".to_owned()));
+        assert_that(&steam.totp(at(59)).unwrap()).is_equal_to(Some("PV9M4".to_owned()));
+        assert_that(&Entry::default().totp(at(59)).unwrap()).is_none();
+    }
+
+    #[test]
+    fn test_totp_rejects_invalid_settings() {
+        let digits = entry_with(&[(OTP_FIELD, &format!("otpauth://totp/x?secret={}&digits=4", SECRET_BASE32))]);
+        let secret = entry_with(&[("TimeOtp-Secret-Base32", "not base32!")]);
+        let algorithm = entry_with(&[(OTP_FIELD, &format!("otpauth://totp/x?secret={}&algorithm=MD5", SECRET_BASE32))]);
+
+        for entry in [digits, secret, algorithm].iter() {
+            assert_that(&matches!(entry.totp(at(59)), Err(Error::InvalidOtp { .. }))).is_true();
+        }
+    }
+
+    #[test]
+    fn test_hotp_increments_uri_counter() {
+        let mut entry = Entry::default();
+        entry.add(OTP_FIELD, &StringValue::ProtectedString(ProtectedValue::from(
+            format!("otpauth://hotp/x?secret={}&counter=1", SECRET_BASE32)
+        )));
+
+        assert_that(&entry.hotp().unwrap()).is_equal_to(Some("287082".to_owned()));
+        assert_that(&entry.hotp().unwrap()).is_equal_to(Some("359152".to_owned()));
+        assert_that(&entry.get(OTP_FIELD).unwrap().ends_with("counter=3")).is_true();
+        assert_that(&entry.fields().any(|(k, v)| k == OTP_FIELD && matches!(v, StringValue::ProtectedString(_))))
+            .is_true();
+        assert_that(&entry.totp(at(59)).unwrap()).is_none();
+    }
+
+    #[test]
+    fn test_hotp_increments_keepass_counter() {
+        let mut entry = entry_with(&[("HmacOtp-Secret", "12345678901234567890")]);
+
+        assert_that(&entry.hotp().unwrap()).is_equal_to(Some("755224".to_owned()));
+        assert_that(&entry.get("HmacOtp-Counter").as_deref()).is_equal_to(Some("1"));
+        assert_that(&entry.hotp().unwrap()).is_equal_to(Some("287082".to_owned()));
+    }
+
+    #[test]
+    fn test_hotp_keeps_uri_fragment_and_rejects_exhausted_counter() {
+        let mut entry = Entry::default();
+        entry.add(OTP_FIELD, &StringValue::UnprotectedString(
+            format!("otpauth://hotp/x?secret={}&counter={}#note", SECRET_BASE32, u64::MAX)
+        ));
+
+        assert_that(&matches!(entry.hotp(), Err(Error::InvalidOtp { .. }))).is_true();
+
+        set_hotp_counter(&mut entry, 7).unwrap();
+
+        assert_that(&entry.get(OTP_FIELD).unwrap().to_string())
+            .is_equal_to(format!("otpauth://hotp/x?secret={}&counter=7#note", SECRET_BASE32));
+    }
+
+    #[test]
+    fn test_unsupported_digits_are_rejected() {
+        for count in [0, 5, 11, 20] {
+            let settings = TotpSettings {
+                format: OtpFormat::Digits(count),
+                ..TotpSettings::generate()
+            };
+            let hotp = HotpSettings {
+                secret: settings.secret.clone(),
+                counter: 0,
+                algorithm: settings.algorithm,
+                format: settings.format,
+            };
+
+            assert_that(&matches!(settings.code(at(59)), Err(Error::InvalidOtp { .. }))).is_true();
+            assert_that(&matches!(settings.uri("ACME Co", "alice"), Err(Error::InvalidOtp { .. }))).is_true();
+            assert_that(&matches!(hotp.code(), Err(Error::InvalidOtp { .. }))).is_true();
+        }
+    }
+
+    #[test]
+    fn test_provisioned_secret_round_trips() {
+        let settings = TotpSettings::generate();
+        let mut entry = Entry::default();
+        entry.set_totp(&settings, "ACME Co", "alice@example.com").unwrap();
+
+        let uri = entry.get(OTP_FIELD).unwrap().to_string();
+        let parsed = totp_settings(&entry).unwrap();
+
+        assert_that(&settings.secret.len()).is_equal_to(GENERATED_SECRET_LENGTH);
+        assert_that(&uri.starts_with("otpauth://totp/ACME%20Co:alice@example.com?secret=")).is_true();
+        assert_that(&parsed).is_equal_to(Some(settings.clone()));
+        assert_that(&entry.totp(at(59)).unwrap()).is_equal_to(Some(settings.code(at(59)).unwrap()));
+    }
+}
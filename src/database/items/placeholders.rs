@@ -0,0 +1,600 @@
+use super::{
+    Entry,
+    Group,
+    Meta,
+    ValueGuard,
+};
+use crate::{
+    database::references::{
+        self,
+        push_wiped,
+        MAX_DEPTH,
+    },
+    errors::Error,
+    results::Result,
+};
+
+use chrono::{
+    DateTime,
+    Datelike,
+    FixedOffset,
+    Local,
+    TimeZone,
+    Timelike,
+    Utc,
+};
+use regex::Regex;
+use secstr::SecStr;
+use url::Url;
+use zeroize::Zeroizing;
+
+use std::collections::HashMap;
+
+/// What placeholders are evaluated against: the entry, the names of the groups from the root
+/// down to the entry, the clock and the environment. The clock and environment default to the
+/// system's, variables are only read from the process when a placeholder asks for them;
+/// `{REF:...}` placeholders are only resolved once a root group is set.
+pub struct PlaceholderContext<'a> {
+    entry: &'a Entry,
+    group_path: &'a [&'a str],
+    now: DateTime<Utc>,
+    offset: FixedOffset,
+    environment: Option<HashMap<String, String>>,
+    root: Option<(&'a Group, &'a Meta)>,
+}
+
+impl<'a> PlaceholderContext<'a> {
+    pub fn new(entry: &'a Entry, group_path: &'a [&'a str]) -> Self {
+        let now = Utc::now();
+
+        Self {
+            entry,
+            group_path,
+            now,
+            offset: Local.offset_from_utc_datetime(&now.naive_utc()),
+            environment: None,
+            root: None,
+        }
+    }
+
+    /// Sets the time and the local UTC offset used by the `{DT_...}` placeholders
+    pub fn set_clock(&mut self, now: DateTime<Utc>, offset: FixedOffset) -> &mut Self {
+        self.now = now;
+        self.offset = offset;
+        self
+    }
+
+    /// Replaces the environment of the process with `environment`
+    pub fn set_environment(&mut self, environment: &HashMap<String, String>) -> &mut Self {
+        self.environment = Some(environment.clone());
+        self
+    }
+
+    // non-UTF-8 values are read lossily instead of failing like `std::env::var`
+    fn variable(&self, name: &str) -> Option<String> {
+        match &self.environment {
+            Some(environment) => environment.get(name).cloned(),
+            None => std::env::var_os(name).map(|value| value.to_string_lossy().into_owned()),
+        }
+    }
+
+    /// Sets the tree `{REF:...}` placeholders look up their target entries in, and the meta data
+    /// naming its recycle bin, which is left out
+    pub fn set_root(&mut self, root: &'a Group, meta: &'a Meta) -> &mut Self {
+        self.root = Some((root, meta));
+        self
+    }
+
+    /// Compiles and evaluates `text` in one go
+    pub fn expand(&self, text: &str) -> Result<ValueGuard<'static>> {
+        Template::compile(text)?.evaluate(self)
+    }
+}
+
+/// A text with KeePass placeholders, compiled once and evaluated against any number of
+/// entries. Supported are
+///
+/// * `{TITLE}`, `{USERNAME}`, `{PASSWORD}`, `{URL}`, `{NOTES}`, `{UUID}` and `{S:<field>}`
+/// * `{URL:RMVSCM}`, `{URL:SCM}`, `{URL:HOST}`, `{URL:PORT}`, `{URL:PATH}`, `{URL:QUERY}`,
+///   `{URL:USERINFO}`, `{URL:USERNAME}` and `{URL:PASSWORD}`
+/// * `{DT_SIMPLE}`, `{DT_YEAR}`, `{DT_MONTH}`, `{DT_DAY}`, `{DT_HOUR}`, `{DT_MINUTE}`,
+///   `{DT_SECOND}` and their `{DT_UTC_...}` counterparts
+/// * `{GROUP}`, `{GROUP_PATH}`, `{ENV:<variable>}` and `{REF:...}`
+/// * `{T-REPLACE-RX:/text/pattern/replacement/}`, any character can take the place of `/`
+///
+/// Placeholders are case-insensitive. Unknown ones, like the auto-type keys, are kept as text.
+#[derive(Debug)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Text(String),
+    Field(String),
+    Uuid,
+    Url(UrlPart),
+    DateTime(DateTimePart, bool),
+    Group,
+    GroupPath,
+    Environment(String),
+    Reference(String),
+    ReplaceRx(Template, Regex, String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum UrlPart {
+    WithoutScheme,
+    Scheme,
+    Host,
+    Port,
+    Path,
+    Query,
+    UserInfo,
+    UserName,
+    Password,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DateTimePart {
+    Simple,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl Template {
+    pub fn compile(text: &str) -> Result<Template> {
+        Self::compile_at(text, 0)
+    }
+
+    // `depth` counts the `{T-REPLACE-RX:...}` placeholders the text is nested in
+    fn compile_at(text: &str, depth: usize) -> Result<Template> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+
+            let segment = match closing_brace(&rest[start..]) {
+                Some(length) => {
+                    let end = start + length;
+                    compile_placeholder(&rest[start..=end], &rest[start + 1..end], depth)?
+                        .map(|segment| (segment, end))
+                }
+                None => None,
+            };
+
+            match segment {
+                Some((segment, end)) => {
+                    if !literal.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    // unknown or unclosed placeholders stay, the ones inside are still expanded
+                    literal.push('{');
+                    rest = &rest[start + 1..];
+                }
+            }
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            segments.push(Segment::Text(literal));
+        }
+
+        Ok(Template { segments })
+    }
+
+    /// Evaluates the placeholders against `context`. The result may hold protected fields and
+    /// is wiped when dropped.
+    pub fn evaluate(&self, context: &PlaceholderContext) -> Result<ValueGuard<'static>> {
+        let value = self.evaluate_at(context, 0)?;
+
+        Ok(ValueGuard::owned(SecStr::from(value.as_str())))
+    }
+
+    // Built in a buffer that is wiped when dropped, the fields may be passwords
+    fn evaluate_at(&self, context: &PlaceholderContext, depth: usize) -> Result<Zeroizing<String>> {
+        let mut value = Zeroizing::new(String::new());
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Text(text) => push_wiped(&mut value, text),
+                Segment::Field(key) => push_wiped(&mut value, &field(context, key, depth)?),
+                Segment::Uuid => push_wiped(&mut value, &hex::encode_upper(context.entry.uuid().as_bytes())),
+                Segment::Url(part) => push_wiped(&mut value, &url_part(&field(context, "URL", depth)?, *part)),
+                Segment::DateTime(part, utc) => {
+                    let offset = if *utc { FixedOffset::east(0) } else { context.offset };
+                    let time = context.now.with_timezone(&offset);
+                    push_wiped(&mut value, &date_time_part(&time, *part));
+                }
+                Segment::Group => push_wiped(&mut value, context.group_path.last().copied().unwrap_or_default()),
+                Segment::GroupPath => push_wiped(&mut value, &context.group_path.join(".")),
+                Segment::Environment(name) => push_wiped(&mut value, &context.variable(name).unwrap_or_default()),
+                Segment::Reference(placeholder) => match context.root {
+                    Some((root, meta)) => push_wiped(&mut value, &references::resolve_text(root, meta, placeholder)?),
+                    None => push_wiped(&mut value, placeholder),
+                },
+                Segment::ReplaceRx(text, regex, replacement) => {
+                    let text = text.evaluate_at(context, depth)?;
+                    let replaced = Zeroizing::new(regex.replace_all(&text, replacement.as_str()).into_owned());
+                    push_wiped(&mut value, &replaced);
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+// The length up to the brace closing the one `text` starts with, counting nested ones
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (position, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Compiles `{<body>}`, `None` for placeholders this engine does not know
+fn compile_placeholder(placeholder: &str, body: &str, depth: usize) -> Result<Option<Segment>> {
+    let (name, argument) = match body.find(':') {
+        Some(colon) => (body[..colon].to_uppercase(), Some(&body[colon + 1..])),
+        None => (body.to_uppercase(), None),
+    };
+
+    let segment = match (name.as_str(), argument) {
+        ("TITLE", None) => Segment::Field("Title".to_owned()),
+        ("USERNAME", None) => Segment::Field("UserName".to_owned()),
+        ("PASSWORD", None) => Segment::Field("Password".to_owned()),
+        ("URL", None) => Segment::Field("URL".to_owned()),
+        ("NOTES", None) => Segment::Field("Notes".to_owned()),
+        ("UUID", None) => Segment::Uuid,
+        ("S", Some(key)) => Segment::Field(key.to_owned()),
+        ("URL", Some(part)) => match url_part_from_name(&part.to_uppercase()) {
+            Some(part) => Segment::Url(part),
+            None => return Ok(None),
+        },
+        ("GROUP", None) => Segment::Group,
+        ("GROUP_PATH", None) => Segment::GroupPath,
+        ("ENV", Some(variable)) => Segment::Environment(variable.to_owned()),
+        ("REF", Some(_)) => Segment::Reference(placeholder.to_owned()),
+        ("T-REPLACE-RX", Some(arguments)) => compile_replace_rx(placeholder, arguments, depth)?,
+        (name, None) if name.starts_with("DT_UTC_") => match date_time_part_from_name(&name[7..]) {
+            Some(part) => Segment::DateTime(part, true),
+            None => return Ok(None),
+        },
+        (name, None) if name.starts_with("DT_") => match date_time_part_from_name(&name[3..]) {
+            Some(part) => Segment::DateTime(part, false),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(segment))
+}
+
+// `<separator>text<separator>pattern<separator>replacement<separator>`, the text may hold
+// placeholders with the separator in them
+fn compile_replace_rx(placeholder: &str, arguments: &str, depth: usize) -> Result<Segment> {
+    let invalid = |reason: &str| Error::InvalidPlaceholder {
+        placeholder: placeholder.to_owned(),
+        reason: reason.to_owned(),
+    };
+
+    if depth >= MAX_DEPTH {
+        return Err(invalid("replacements nested too deeply"));
+    }
+
+    let separator = arguments.chars().next().ok_or_else(|| invalid("missing separator"))?;
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut braces = 0usize;
+
+    for c in arguments[separator.len_utf8()..].chars() {
+        match c {
+            c if c == separator && braces == 0 => parts.push(std::mem::take(&mut part)),
+            '{' => {
+                braces += 1;
+                part.push(c);
+            }
+            '}' => {
+                braces = braces.saturating_sub(1);
+                part.push(c);
+            }
+            c => part.push(c),
+        }
+    }
+
+    if parts.len() != 3 || !part.is_empty() {
+        return Err(invalid("expected text, pattern and replacement"));
+    }
+
+    let regex = Regex::new(&parts[1]).map_err(|e| invalid(&e.to_string()))?;
+
+    Ok(Segment::ReplaceRx(Template::compile_at(&parts[0], depth + 1)?, regex, parts[2].clone()))
+}
+
+fn url_part_from_name(name: &str) -> Option<UrlPart> {
+    match name {
+        "RMVSCM" => Some(UrlPart::WithoutScheme),
+        "SCM" => Some(UrlPart::Scheme),
+        "HOST" => Some(UrlPart::Host),
+        "PORT" => Some(UrlPart::Port),
+        "PATH" => Some(UrlPart::Path),
+        "QUERY" => Some(UrlPart::Query),
+        "USERINFO" => Some(UrlPart::UserInfo),
+        "USERNAME" => Some(UrlPart::UserName),
+        "PASSWORD" => Some(UrlPart::Password),
+        _ => None,
+    }
+}
+
+fn date_time_part_from_name(name: &str) -> Option<DateTimePart> {
+    match name {
+        "SIMPLE" => Some(DateTimePart::Simple),
+        "YEAR" => Some(DateTimePart::Year),
+        "MONTH" => Some(DateTimePart::Month),
+        "DAY" => Some(DateTimePart::Day),
+        "HOUR" => Some(DateTimePart::Hour),
+        "MINUTE" => Some(DateTimePart::Minute),
+        "SECOND" => Some(DateTimePart::Second),
+        _ => None,
+    }
+}
+
+// The expanded field `key` of the context's entry, empty if it does not have one
+fn field(context: &PlaceholderContext, key: &str, depth: usize) -> Result<Zeroizing<String>> {
+    let mut expanded = Zeroizing::new(String::new());
+    let value = match context.entry.get(key) {
+        Some(value) => value,
+        None => return Ok(expanded),
+    };
+
+    if !value.contains('{') {
+        push_wiped(&mut expanded, &value);
+        return Ok(expanded);
+    }
+
+    if depth >= MAX_DEPTH {
+        return Err(Error::InvalidPlaceholder {
+            placeholder: value.to_string(),
+            reason: "placeholders nested too deeply".to_owned(),
+        });
+    }
+
+    Template::compile(&value)?.evaluate_at(context, depth + 1)
+}
+
+// URLs that do not parse have no parts, except for the one without the scheme. The parts
+// may hold the password of the URL, so they are wiped when dropped.
+fn url_part(value: &str, part: UrlPart) -> Zeroizing<String> {
+    if part == UrlPart::WithoutScheme {
+        return Zeroizing::new(match value.find(':') {
+            Some(colon) => value[colon + 1..].trim_start_matches('/').to_owned(),
+            None => value.to_owned(),
+        });
+    }
+
+    let url = match Url::parse(value) {
+        Ok(url) => url,
+        Err(_) => return Zeroizing::new(String::new()),
+    };
+
+    let value = Zeroizing::new(match part {
+        UrlPart::WithoutScheme => unreachable!(),
+        UrlPart::Scheme => url.scheme().to_owned(),
+        UrlPart::Host => url.host_str().unwrap_or_default().to_owned(),
+        UrlPart::Port => url.port_or_known_default().map(|port| port.to_string()).unwrap_or_default(),
+        UrlPart::Path => url.path().to_owned(),
+        UrlPart::Query => url.query().map(|query| format!("?{}", query)).unwrap_or_default(),
+        UrlPart::UserInfo => match url.password() {
+            Some(password) => format!("{}:{}", url.username(), password),
+            None => url.username().to_owned(),
+        },
+        UrlPart::UserName => url.username().to_owned(),
+        UrlPart::Password => url.password().unwrap_or_default().to_owned(),
+    });
+
+    // the serialized URL still holds the password
+    drop(Zeroizing::new(String::from(url)));
+
+    value
+}
+
+fn date_time_part(time: &DateTime<FixedOffset>, part: DateTimePart) -> String {
+    match part {
+        DateTimePart::Simple => time.format("%Y%m%d%H%M%S").to_string(),
+        DateTimePart::Year => format!("{:04}", time.year()),
+        DateTimePart::Month => format!("{:02}", time.month()),
+        DateTimePart::Day => format!("{:02}", time.day()),
+        DateTimePart::Hour => format!("{:02}", time.hour()),
+        DateTimePart::Minute => format!("{:02}", time.minute()),
+        DateTimePart::Second => format!("{:02}", time.second()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        items::StringValue,
+        test_helpers::entry,
+    };
+    use chrono::NaiveDate;
+    use spectral::prelude::*;
+
+    const PATH: [&str; 2] = ["Servers", "Databases"];
+
+    fn sample_entry() -> Entry {
+        let mut entry = entry(
+            "db01",
+            "admin@corp.example",
+            "s3cret",
+            "postgres://svc:pw@db01.example.com:5433/sales?sslmode=require",
+        );
+        entry.add("Database", &StringValue::UnprotectedString("sales".to_owned()));
+        entry
+    }
+
+    fn expand(entry: &Entry, text: &str) -> Result<String> {
+        let environment: HashMap<String, String> =
+            vec![("PGHOST".to_owned(), "localhost".to_owned())].into_iter().collect();
+        let now = DateTime::from_utc(NaiveDate::from_ymd(2021, 6, 15).and_hms(23, 45, 30), Utc);
+
+        let mut context = PlaceholderContext::new(entry, &PATH);
+        context
+            .set_clock(now, FixedOffset::east(2 * 3600))
+            .set_environment(&environment);
+
+        context.expand(text).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn test_expand_fields() {
+        let entry = sample_entry();
+
+        assert_that(&expand(&entry, "{TITLE}/{username}:{PASSWORD} on {S:Database}").unwrap())
+            .is_equal_to("db01/admin@corp.example:s3cret on sales".to_owned());
+        assert_that(&expand(&entry, "{NOTES}{S:Missing}").unwrap()).is_equal_to(String::new());
+        assert_that(&expand(&entry, "{UUID}").unwrap())
+            .is_equal_to(hex::encode_upper(entry.uuid().as_bytes()));
+    }
+
+    #[test]
+    fn test_expand_url_parts() {
+        let entry = sample_entry();
+
+        assert_that(&expand(&entry, "{URL:SCM}|{URL:HOST}|{URL:PORT}|{URL:PATH}|{URL:QUERY}").unwrap())
+            .is_equal_to("postgres|db01.example.com|5433|/sales|?sslmode=require".to_owned());
+        assert_that(&expand(&entry, "{URL:USERINFO}|{URL:USERNAME}|{URL:PASSWORD}").unwrap())
+            .is_equal_to("svc:pw|svc|pw".to_owned());
+        assert_that(&expand(&entry, "{URL:RMVSCM}").unwrap())
+            .is_equal_to("svc:pw@db01.example.com:5433/sales?sslmode=require".to_owned());
+    }
+
+    #[test]
+    fn test_expand_default_port_and_unparsable_url() {
+        let mut entry = sample_entry();
+        entry.add("URL", &StringValue::UnprotectedString("https://example.com".to_owned()));
+        assert_that(&expand(&entry, "{URL:PORT}").unwrap()).is_equal_to("443".to_owned());
+
+        entry.add("URL", &StringValue::UnprotectedString("example.com".to_owned()));
+        assert_that(&expand(&entry, "{URL:HOST}{URL:PORT}").unwrap()).is_equal_to(String::new());
+    }
+
+    #[test]
+    fn test_expand_date_time_group_and_environment() {
+        let entry = sample_entry();
+
+        assert_that(&expand(&entry, "{DT_UTC_YEAR}-{DT_UTC_MONTH}-{DT_UTC_DAY} {DT_UTC_HOUR}").unwrap())
+            .is_equal_to("2021-06-15 23".to_owned());
+        assert_that(&expand(&entry, "{DT_SIMPLE}").unwrap()).is_equal_to("20210616014530".to_owned());
+        assert_that(&expand(&entry, "{GROUP} in {GROUP_PATH}").unwrap())
+            .is_equal_to("Databases in Servers.Databases".to_owned());
+        assert_that(&expand(&entry, "{ENV:PGHOST}{ENV:UNSET}").unwrap()).is_equal_to("localhost".to_owned());
+    }
+
+    #[test]
+    fn test_expand_replace_rx() {
+        let entry = sample_entry();
+
+        assert_that(&expand(&entry, "{T-REPLACE-RX:/{USERNAME}/(.*)@(.*)/$2\\$1/}").unwrap())
+            .is_equal_to("corp.example\\admin".to_owned());
+        assert_that(&expand(&entry, "{T-REPLACE-RX:|{URL}|:\\d+/|:6432/|}").unwrap())
+            .is_equal_to("postgres://svc:pw@db01.example.com:6432/sales?sslmode=require".to_owned());
+    }
+
+    #[test]
+    fn test_invalid_replace_rx_fails() {
+        let result = Template::compile("{T-REPLACE-RX:/text/(/x/}");
+        let missing = Template::compile("{T-REPLACE-RX:/text/x}");
+
+        assert_that(&matches!(result, Err(Error::InvalidPlaceholder { .. }))).is_true();
+        assert_that(&matches!(missing, Err(Error::InvalidPlaceholder { .. }))).is_true();
+    }
+
+    #[test]
+    fn test_unknown_placeholders_stay() {
+        let entry = sample_entry();
+
+        assert_that(&expand(&entry, "{TAB}{ENTER} {X:{TITLE}} {TITLE").unwrap())
+            .is_equal_to("{TAB}{ENTER} {X:db01} {TITLE".to_owned());
+    }
+
+    #[test]
+    fn test_expand_nested_fields_and_references() {
+        let mut target = sample_entry();
+        target.add("Title", &StringValue::UnprotectedString("shared".to_owned()));
+        let mut entry = sample_entry();
+        entry.add("Notes", &StringValue::UnprotectedString("{USERNAME} for {TITLE}".to_owned()));
+        entry.set_reference("UserName", &target, crate::database::references::ReferenceField::Title);
+
+        let mut root = Group::root();
+        root.add_entry("shared", &target);
+
+        assert_that(&expand(&entry, "{NOTES}").unwrap())
+            .is_equal_to("{REF:T@I:".to_owned() + &hex::encode_upper(target.uuid().as_bytes()) + "} for db01");
+
+        let meta = Meta::default();
+        let mut context = PlaceholderContext::new(&entry, &PATH);
+        context.set_root(&root, &meta);
+        assert_that(&context.expand("{NOTES}").unwrap().to_string()).is_equal_to("shared for db01".to_owned());
+    }
+
+    #[test]
+    fn test_self_referencing_field_fails() {
+        let mut entry = sample_entry();
+        entry.add("Notes", &StringValue::UnprotectedString("again {NOTES}".to_owned()));
+
+        assert_that(&matches!(expand(&entry, "{NOTES}"), Err(Error::InvalidPlaceholder { .. }))).is_true();
+    }
+
+    #[test]
+    fn test_deeply_nested_replace_rx_fails() {
+        let nest = |levels: usize| {
+            (0..levels).fold("x".to_owned(), |text, _| format!("{{T-REPLACE-RX:/{}/x/y/}}", text))
+        };
+
+        assert_that(&expand(&sample_entry(), &nest(2)).unwrap()).is_equal_to("y".to_owned());
+        assert_that(&matches!(Template::compile(&nest(1000)), Err(Error::InvalidPlaceholder { .. })))
+            .is_true();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_environment_is_read_lazily() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        // a variable std::env::vars() would panic on
+        std::env::set_var("RUSTPASS_TEST_NON_UTF8", OsStr::from_bytes(b"caf\xe9"));
+        std::env::set_var("RUSTPASS_TEST_HOST", "db01");
+
+        let entry = sample_entry();
+        let context = PlaceholderContext::new(&entry, &PATH);
+
+        assert_that(&context.expand("{ENV:RUSTPASS_TEST_HOST}").unwrap().to_string())
+            .is_equal_to("db01".to_owned());
+        assert_that(&context.expand("{ENV:RUSTPASS_TEST_NON_UTF8}").unwrap().to_string())
+            .is_equal_to("caf\u{fffd}".to_owned());
+    }
+}
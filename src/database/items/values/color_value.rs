@@ -2,7 +2,10 @@ use super::Identifier;
 
 use extfmt::Hexlify;
 
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    str::FromStr,
+};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ColorValue(u8, u8, u8);
@@ -62,6 +65,18 @@ impl TryFrom<&[u8]> for ColorValue {
     }
 }
 
+// the `#RRGGBB` form of `to_hex`
+impl FromStr for ColorValue {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let hex = value.strip_prefix('#').ok_or(())?;
+        let bytes = hex::decode(hex).map_err(|_| ())?;
+
+        ColorValue::try_from(bytes.as_slice())
+    }
+}
+
 impl Identifier for ColorValue {
     const IDENTIFIER: &'static [u8] = b"Color";
 }
@@ -94,4 +109,11 @@ mod tests {
         assert_that(&ColorValue::new(240, 240, 240).to_hex())
             .is_equal_to("#F0F0F0".to_owned());
     }
+
+    #[test]
+    fn test_from_hex() {
+        assert_that(&"#F0A0FF".parse::<ColorValue>()).is_equal_to(Ok(ColorValue::new(240, 160, 255)));
+        assert_that(&"F0A0FF".parse::<ColorValue>()).is_err();
+        assert_that(&"#F0A0".parse::<ColorValue>()).is_err();
+    }
 }
@@ -11,6 +11,17 @@ pub struct IconValue {
 }
 
 impl IconValue {
+    pub fn new(uuid: &UuidValue, data: &[u8]) -> Self {
+        IconValue {
+            uuid: uuid.to_owned(),
+            data: Base64Value::from(data),
+        }
+    }
+
+    pub fn uuid_value(&self) -> UuidValue {
+        self.uuid.clone()
+    }
+
     pub fn uuid(&self) -> String {
         self.uuid.to_string()
     }
@@ -1,6 +1,7 @@
 mod base64_value;
 mod color_value;
 mod icon_value;
+mod protected_value;
 mod string_value;
 mod timestamp_value;
 mod uuid_value;
@@ -10,6 +11,10 @@ pub(super) use super::Identifier;
 pub(crate) use base64_value::Base64Value;
 pub(crate) use color_value::ColorValue;
 pub(crate) use icon_value::IconValue;
+pub(crate) use protected_value::{
+    ProtectedValue,
+    ValueGuard,
+};
 pub(crate) use string_value::StringValue;
 pub(crate) use timestamp_value::TimestampValue;
 pub(crate) use uuid_value::UuidValue;
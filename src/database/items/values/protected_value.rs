@@ -0,0 +1,244 @@
+use chacha20::{
+    ChaCha20,
+    Key,
+    Nonce,
+};
+use cipher::{
+    NewStreamCipher,
+    SyncStreamCipher,
+};
+use rand::{
+    RngCore,
+    rngs::OsRng,
+};
+use secstr::SecStr;
+
+use std::sync::OnceLock;
+
+const NONCE_LENGTH: usize = 12;
+
+// Random key shared by all protected values of this process, never written anywhere
+static PROCESS_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+fn process_key() -> &'static [u8; 32] {
+    PROCESS_KEY.get_or_init(|| {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        key
+    })
+}
+
+fn apply_keystream(nonce: &[u8; NONCE_LENGTH], buffer: &mut [u8]) {
+    ChaCha20::new(
+        Key::from_slice(process_key()),
+        Nonce::from_slice(nonce),
+    ).apply_keystream(buffer);
+}
+
+/// A secret that is kept XOR-encrypted with a per-process key while it is in memory,
+/// like KeePass' `ProtectedString`. The plaintext only exists inside the buffer returned
+/// by `unprotect` or the `ValueGuard` from `Entry::get`, both zeroed when they are dropped.
+#[derive(Clone)]
+pub struct ProtectedValue {
+    nonce: [u8; NONCE_LENGTH],
+    data: Vec<u8>,
+}
+
+impl ProtectedValue {
+    pub fn new(plaintext: &[u8]) -> Self {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut data = plaintext.to_vec();
+        apply_keystream(&nonce, &mut data);
+
+        ProtectedValue {
+            nonce,
+            data,
+        }
+    }
+
+    /// Decrypts the value into a buffer that is zeroed on drop
+    pub fn unprotect(&self) -> SecStr {
+        let mut plaintext = SecStr::new(self.data.clone());
+        apply_keystream(&self.nonce, plaintext.unsecure_mut());
+        plaintext
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl Default for ProtectedValue {
+    fn default() -> Self {
+        ProtectedValue::new(&[])
+    }
+}
+
+impl From<&str> for ProtectedValue {
+    fn from(value: &str) -> Self {
+        ProtectedValue::new(value.as_bytes())
+    }
+}
+
+impl From<String> for ProtectedValue {
+    fn from(value: String) -> Self {
+        // take over the buffer so the plaintext copy is wiped
+        let plaintext = SecStr::from(value);
+        ProtectedValue::new(plaintext.unsecure())
+    }
+}
+
+impl PartialEq for ProtectedValue {
+    fn eq(&self, other: &Self) -> bool {
+        // SecStr compares in constant time
+        self.unprotect() == other.unprotect()
+    }
+}
+
+impl Eq for ProtectedValue {}
+
+impl std::fmt::Debug for ProtectedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("ProtectedValue(***)")
+    }
+}
+
+/// A string field borrowed from an entry. Protected values are decrypted into a buffer
+/// owned by the guard and zeroed when it goes out of scope, so keep it short-lived.
+pub struct ValueGuard<'a> {
+    inner: GuardInner<'a>,
+}
+
+enum GuardInner<'a> {
+    Plain(&'a str),
+    Decrypted(SecStr),
+}
+
+impl<'a> ValueGuard<'a> {
+    pub(crate) fn plain(value: &'a str) -> Self {
+        ValueGuard { inner: GuardInner::Plain(value) }
+    }
+
+    /// Decrypts a protected value, `None` if the plaintext is not valid UTF-8
+    pub(crate) fn decrypt(value: &ProtectedValue) -> Option<Self> {
+        let plaintext = value.unprotect();
+
+        std::str::from_utf8(plaintext.unsecure()).ok()?;
+
+        Some(ValueGuard { inner: GuardInner::Decrypted(plaintext) })
+    }
+
+    /// Wraps a value derived from protected fields, zeroed when dropped like a decrypted one.
+    /// `value` must be valid UTF-8.
+    pub(crate) fn owned(value: SecStr) -> Self {
+        ValueGuard { inner: GuardInner::Decrypted(value) }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.inner {
+            GuardInner::Plain(value) => value,
+            // validated in `decrypt`
+            GuardInner::Decrypted(plaintext) => std::str::from_utf8(plaintext.unsecure())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl std::ops::Deref for ValueGuard<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ValueGuard<'_> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for ValueGuard<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ValueGuard<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl std::fmt::Debug for ValueGuard<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.inner {
+            GuardInner::Plain(value) => f.debug_tuple("ValueGuard").field(value).finish(),
+            GuardInner::Decrypted(_) => f.write_str("ValueGuard(***)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_unprotect() {
+        let value = ProtectedValue::from("correct horse battery staple");
+
+        assert_that(&value.unprotect().unsecure())
+            .is_equal_to(b"correct horse battery staple".as_ref());
+    }
+
+    #[test]
+    fn test_plaintext_is_not_stored() {
+        let value = ProtectedValue::from("correct horse battery staple");
+
+        assert_that(&value.len()).is_equal_to(28);
+        assert_that(&value.data).is_not_equal_to(b"correct horse battery staple".to_vec());
+    }
+
+    #[test]
+    fn test_equal_plaintexts_differ_in_memory() {
+        let a = ProtectedValue::from("secret");
+        let b = ProtectedValue::from("secret");
+
+        assert_that(&a.data).is_not_equal_to(&b.data);
+        assert_that(&a).is_equal_to(&b);
+        assert_that(&a).is_not_equal_to(ProtectedValue::from("Secret"));
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let value = ProtectedValue::from("secret");
+        let guard = ValueGuard::decrypt(&value).unwrap();
+
+        assert_that(&format!("{:?}", value)).is_equal_to("ProtectedValue(***)".to_owned());
+        assert_that(&format!("{:?}", guard)).is_equal_to("ValueGuard(***)".to_owned());
+    }
+
+    #[test]
+    fn test_guard_derefs_to_plaintext() {
+        let value = ProtectedValue::from("secret");
+
+        let guard = ValueGuard::decrypt(&value).unwrap();
+
+        assert_that(&guard.len()).is_equal_to(6);
+        assert_that(&(guard == "secret")).is_true();
+    }
+
+    #[test]
+    fn test_guard_rejects_invalid_utf8() {
+        let value = ProtectedValue::new(&[0xff, 0xfe]);
+
+        assert_that(&ValueGuard::decrypt(&value).is_none()).is_true();
+    }
+}
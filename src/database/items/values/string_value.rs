@@ -1,13 +1,49 @@
-use super::Identifier;
-use secstr::SecStr;
+use super::{
+    Identifier,
+    ProtectedValue,
+};
+use crate::database::items::MemoryProtection;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum StringValue {
     Bytes(Vec<u8>),
     UnprotectedString(String),
-    ProtectedString(SecStr),
+    ProtectedString(ProtectedValue),
+}
+
+impl StringValue {
+    /// Creates the value of the field `key`, protected in memory if `protection` says so
+    pub fn for_field(
+        key: &str,
+        value: &str,
+        protection: &MemoryProtection,
+    ) -> Self {
+        if protection.is_protected(key) {
+            StringValue::ProtectedString(ProtectedValue::from(value))
+        } else {
+            StringValue::UnprotectedString(value.to_owned())
+        }
+    }
 }
 
 impl Identifier for StringValue {
     const IDENTIFIER: &'static [u8] = b"String";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_for_field_default_protection() {
+        let protection = MemoryProtection::default();
+
+        assert_that(&StringValue::for_field("Password", "secret", &protection))
+            .matches(|v| matches!(v, StringValue::ProtectedString(_)));
+        assert_that(&StringValue::for_field("UserName", "user", &protection))
+            .is_equal_to(StringValue::UnprotectedString("user".to_owned()));
+        assert_that(&StringValue::for_field("Custom", "value", &protection))
+            .is_equal_to(StringValue::UnprotectedString("value".to_owned()));
+    }
+}
@@ -18,6 +18,10 @@ impl TimestampValue {
     pub fn now() -> Self {
         TimestampValue(format!("{:?}", Utc::now()))
     }
+
+    pub fn as_datetime(&self) -> Option<DateTime<Utc>> {
+        DateTime::<Utc>::from_str(&self.0).ok()
+    }
 }
 
 impl Default for TimestampValue {
@@ -54,6 +58,12 @@ impl ToString for TimestampValue {
     }
 }
 
+impl From<DateTime<Utc>> for TimestampValue {
+    fn from(value: DateTime<Utc>) -> Self {
+        TimestampValue(format!("{:?}", value))
+    }
+}
+
 impl TryFrom<String> for TimestampValue {
     type Error = ();
 
@@ -88,6 +98,13 @@ mod tests {
             .is_equal_to(sample.to_owned());
     }
 
+    #[test]
+    fn test_as_datetime() {
+        let now = Utc::now();
+
+        assert_that(&TimestampValue::from(now).as_datetime()).is_equal_to(Some(now));
+    }
+
     #[test]
     fn test_deref() {
         let sample = "2012-01-12T18:42:00Z";
@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
-    convert::TryInto,
+    convert::{
+        TryFrom,
+        TryInto,
+    },
     str,
 };
 
@@ -8,7 +11,9 @@ use byteorder::{
     ByteOrder,
     LittleEndian,
 };
+use chrono::Utc;
 use generic_array::GenericArray;
+use zeroize::Zeroizing;
 
 use crate::{
     api::{
@@ -16,8 +21,14 @@ use crate::{
             Header,
             InnerHeader
         },
-        kdb::KDBHeader,
-        suites::OuterCipherSuite
+        kdb::{
+            DroppedFeature,
+            KDBHeader,
+        },
+        suites::{
+            KdfSettings,
+            OuterCipherSuite,
+        },
     },
     database::{
         Database,
@@ -29,27 +40,50 @@ use crate::{
         Error,
     },
     internal::{
-        database::binary::header::kdb::{
-            self,
-            HEADER_SIZE,
+        database::binary::{
+            constants::KDB_MAGIC,
+            header::kdb::{
+                self,
+                HEADER_SIZE,
+            },
         },
+        random,
     },
     results::Result,
 };
 use crate::internal::cryptopraphy::{
     self,
-    kdf::Kdf
+    kdf::Kdf,
+    wipe::{
+        into_secret,
+        SecretBytes,
+    },
 };
 
-fn entry_name(field_type: u16) -> &'static str {
+// KeePass 1.x group flag for groups that are expanded in the tree view
+const GROUP_FLAG_EXPANDED: u32 = 1;
+
+fn entry_name(field_type: u16) -> Option<&'static str> {
     match field_type {
-        0x0004 => "Title",
-        0x0005 => "URL",
-        0x0006 => "UserName",
-        0x0008 => "Additional",
-        0x000d => "BinaryDesc",
+        0x0004 => Some("Title"),
+        0x0005 => Some("URL"),
+        0x0006 => Some("UserName"),
+        0x0008 => Some("Notes"),
+        _ => None,
+    }
+}
+
+// Stores the `index`th of the Creation/LastMod/LastAccess/Expire fields
+fn set_time(times: &mut items::Times, index: u16, field_value: &[u8]) {
+    let time = utils::from_packed_time(field_value);
+
+    match index {
+        0 => times.creation_time = time.map(items::TimestampValue::from),
+        1 => times.last_modification_time = time.map(items::TimestampValue::from),
+        2 => times.last_access_time = time.map(items::TimestampValue::from),
         _ => {
-            panic!("Unsupported field type!");
+            times.expires = Some(time.is_some_and(|t| t != utils::never_expires()));
+            times.expiry_time = time.map(items::TimestampValue::from);
         }
     }
 }
@@ -57,44 +91,33 @@ fn entry_name(field_type: u16) -> &'static str {
 // A map from a GroupId to a path identifying (by name) a group in the group tree.
 type GidMap = HashMap<u32, Vec<String>>;
 
-pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
+// KeePass 1.x stores metadata as entries with these field values and the stream name as notes
+const META_STREAM_TITLE: &str = "Meta-Info";
+const META_STREAM_USER_NAME: &str = "SYSTEM";
+const META_STREAM_URL: &str = "$";
+const META_STREAM_BINARY_DESC: &str = "bin-stream";
+
+// A KeePass 1.x meta-stream, hidden from the entry tree
+struct MetaStream {
+    name: String,
+    data: Vec<u8>,
+}
+
+pub(crate) fn parse(data: &[u8], key_elements: &[SecretBytes]) -> Result<Database> {
     let header = kdb::read_header(data)?;
 
     // Rest of file after header is payload
     let payload_encrypted = &data[HEADER_SIZE..];
 
-    // derive master key from composite key, transform_seed, transform_rounds and master_seed
-    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
-    let composite_key = if key_elements.len() == 1 {
-        let key_element: [u8; 32] = key_elements[0].try_into().unwrap();
-        GenericArray::from(key_element) // single pass of SHA256, already done before the call to database()
-    } else {
-        cryptopraphy::sha256(&key_elements)? // second pass of SHA256
-    };
-
-    // KDF the same as for KDBX
-    let transformed_key = cryptopraphy::kdf::AesKdf::new(
-        header.transform_seed.as_ref(),
-        header.transform_rounds as u64,
-    )
-        .transform_key(&composite_key)?;
-
-    let master_key = cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?;
-
-    let cipher = if header.flags & 2 != 0 {
-        OuterCipherSuite::AES256
-    } else if header.flags & 8 != 0 {
-        OuterCipherSuite::Twofish
-    } else {
-        return Err(DatabaseIntegrityError::InvalidFixedCipherID { cid: header.flags }.into());
-    };
+    let master_key = master_key(&header, key_elements)?;
+    let cipher = cipher_suite(header.flags)?;
 
-    // Decrypt payload
-    let payload_padded = cipher
+    // Decrypt payload, a wrong key almost always shows as broken padding
+    let payload = cipher
         .get_cipher(&master_key, header.encryption_iv.as_ref())?
-        .decrypt(payload_encrypted)?;
-    let padlen = payload_padded[payload_padded.len() - 1] as usize;
-    let payload = &payload_padded[..payload_padded.len() - padlen];
+        .decrypt(payload_encrypted)
+        .map_err(|_| Error::IncorrectKey)?;
+    let payload = Zeroizing::new(payload);
 
     // Check if we decrypted correctly
     let hash = cryptopraphy::sha256(&[&payload])?;
@@ -102,15 +125,51 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
         return Err(Error::IncorrectKey);
     }
 
-    let root_group = parse_db(&header, &payload)?;
+    let (meta, root) = parse_db(&header, &payload)?;
 
     Ok(Database {
         header: Header::KDB(header),
         inner_header: InnerHeader::None,
-        root: root_group,
+        meta,
+        root,
+        deleted_objects: Vec::new(),
     })
 }
 
+// Derives the master key from composite key, transform_seed, transform_rounds and master_seed
+fn master_key(header: &KDBHeader, key_elements: &[SecretBytes]) -> Result<SecretBytes> {
+    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
+    let composite_key = if key_elements.len() == 1 {
+        let key_element: [u8; 32] = key_elements[0].try_into().unwrap();
+        into_secret(GenericArray::from(key_element)) // single pass of SHA256, already done before the call to database()
+    } else {
+        into_secret(cryptopraphy::sha256(&key_elements)?) // second pass of SHA256
+    };
+
+    // KDF the same as for KDBX
+    let transformed_key = into_secret(
+        cryptopraphy::kdf::AesKdf::new(
+            header.transform_seed.as_ref(),
+            header.transform_rounds as u64,
+        )
+            .transform_key(GenericArray::from_slice(&composite_key))?
+    );
+
+    Ok(into_secret(
+        cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?
+    ))
+}
+
+pub(crate) fn cipher_suite(flags: u32) -> Result<OuterCipherSuite> {
+    if flags & kdb::FLAG_RIJNDAEL != 0 {
+        Ok(OuterCipherSuite::AES256)
+    } else if flags & kdb::FLAG_TWOFISH != 0 {
+        Ok(OuterCipherSuite::Twofish)
+    } else {
+        Err(DatabaseIntegrityError::InvalidFixedCipherID { cid: flags }.into())
+    }
+}
+
 fn parse_groups(root: &mut items::Group, header_num_groups: u32, data: &mut &[u8]) -> Result<GidMap> {
     // Loop over group TLVs
     let mut gid_map: HashMap<u32, Vec<String>> = HashMap::new(); // the gid to group path map
@@ -140,10 +199,12 @@ fn parse_groups(root: &mut items::Group, header_num_groups: u32, data: &mut &[u8
             0x0003..=0x0006 => {
                 // Creation/LastMod/LastAccess/Expire
                 utils::ensure_length(field_type, field_size, 5)?;
+                set_time(&mut group.times, field_type - 0x0003, field_value);
             }
             0x0007 => {
                 //ImageId
                 utils::ensure_length(field_type, field_size, 4)?;
+                group.icon_id = LittleEndian::read_u32(field_value);
             }
             0x0008 => {
                 // Level
@@ -153,6 +214,7 @@ fn parse_groups(root: &mut items::Group, header_num_groups: u32, data: &mut &[u8
             0x0009 => {
                 // Flags
                 utils::ensure_length(field_type as u16, field_size, 4)?;
+                group.is_expanded = LittleEndian::read_u32(field_value) & GROUP_FLAG_EXPANDED != 0;
             }
             0xffff => {
                 utils::ensure_length(field_type as u16, field_size, 0)?;
@@ -202,27 +264,36 @@ fn parse_groups(root: &mut items::Group, header_num_groups: u32, data: &mut &[u8
     Ok(gid_map)
 }
 
-fn parse_db(header: &KDBHeader, data: &[u8]) -> Result<items::Group> {
+fn parse_db(header: &KDBHeader, data: &[u8]) -> Result<(items::Meta, items::Group)> {
+    let mut meta = items::Meta::default();
     let mut root = items::Group::root();
 
     let mut pos = &data[..];
 
     let gid_map = parse_groups(&mut root, header.num_groups, &mut pos)?;
 
-    parse_entries(&mut root, gid_map, header.num_entries, &mut pos)?;
+    let meta_streams = parse_entries(&mut root, &gid_map, header.num_entries, &mut pos)?;
+
+    for stream in meta_streams.iter() {
+        apply_meta_stream(&mut meta, &mut root, &gid_map, stream);
+    }
 
-    Ok(root)
+    Ok((meta, root))
 }
 
 fn parse_entries(
     root: &mut items::Group,
-    gid_map: GidMap,
+    gid_map: &GidMap,
     header_num_entries: u32,
     data: &mut &[u8],
-) -> Result<()> {
+) -> Result<Vec<MetaStream>> {
     // Loop over entry TLVs
     let mut entry: items::Entry = Default::default(); // the current entry
+    let mut times = items::Times::default(); // the current entry's times
+    let mut binary_desc = String::new(); // the current entry's attachment name
+    let mut binary_data: Vec<u8> = Vec::new(); // the current entry's attachment
     let mut gid: Option<u32> = None; // the current entry's group id
+    let mut meta_streams = Vec::new(); // the meta-stream entries, not part of the tree
     let mut num_entries = 0;
     while num_entries < header_num_entries {
         // Read entry TLV
@@ -235,6 +306,7 @@ fn parse_entries(
             0x0001 => {
                 // uuid
                 utils::ensure_length(field_type, field_size, 16)?;
+                entry.set_uuid(&items::UuidValue::from(field_value));
             }
             0x0002 => {
                 // GroupId
@@ -244,13 +316,16 @@ fn parse_entries(
             0x0003 => {
                 // ImageId
                 utils::ensure_length(field_type, field_size, 4)?;
+                entry.set_icon_id(LittleEndian::read_u32(field_value) as i32);
             }
-            0x0004 | 0x0005 | 0x0006 | 0x0008 | 0x000d => {
-                // Title/URL/UserName/Additional/BinaryDesc
-                entry.add(
-                    entry_name(field_type),
-                    &items::StringValue::UnprotectedString(utils::from_utf8(field_value)?),
-                );
+            0x0004 | 0x0005 | 0x0006 | 0x0008 => {
+                // Title/URL/UserName/Additional
+                if let Some(name) = entry_name(field_type) {
+                    entry.add(
+                        name,
+                        &items::StringValue::UnprotectedString(utils::from_utf8(field_value)?),
+                    );
+                }
             }
             0x0007 => {
                 // Password
@@ -262,13 +337,15 @@ fn parse_entries(
             0x0009..=0x000c => {
                 // Creation/LastMod/LastAccess/Expire
                 utils::ensure_length(field_type, field_size, 5)?;
+                set_time(&mut times, field_type - 0x0009, field_value);
+            }
+            0x000d => {
+                // BinaryDesc
+                binary_desc = utils::from_utf8(field_value)?;
             }
             0x000e => {
                 // BinaryData
-                entry.add(
-                    "BinaryData",
-                    &items::StringValue::Bytes(field_value.to_vec()),
-                );
+                binary_data = field_value.to_vec();
             }
             0xffff => {
                 utils::ensure_length(field_type, field_size, 0)?;
@@ -279,18 +356,34 @@ fn parse_entries(
                     Error::from(DatabaseIntegrityError::InvalidKDBGroupId { group_id })
                 })?;
 
-                // Follow the group path to fetch the corresponding group
-                let mut group: &mut items::Group = root;
-                for g in group_path.iter() {
-                    group = group.child_groups.get_mut(g).unwrap(); // the group path was built to match the group tree
+                if is_meta_stream(&entry, &binary_desc, &binary_data) {
+                    meta_streams.push(MetaStream {
+                        name: entry.get("Notes").map(|notes| notes.to_string()).unwrap_or_default(),
+                        data: std::mem::take(&mut binary_data),
+                    });
+                } else {
+                    // Follow the group path to fetch the corresponding group
+                    let group = group_at(root, group_path).unwrap(); // the group path was built to match the group tree
+
+                    // KeePass 1.x writes empty attachment fields for entries without one
+                    entry.set_times(&times);
+                    if !binary_desc.is_empty() || !binary_data.is_empty() {
+                        entry.set_binary(&items::Binary::new(&binary_desc, false, &binary_data));
+                    }
+
+                    // Insert the entry
+                    let entry_title = entry
+                        .title()
+                        .map(|title| title.to_string())
+                        .ok_or_else(|| Error::from(DatabaseIntegrityError::MissingKDBEntryTitle))?;
+                    group.entries.insert(entry_title, entry);
                 }
 
-                // Insert the entry (and reset state for the next entry)
-                let entry_title = entry
-                    .get_title()
-                    .ok_or_else(|| Error::from(DatabaseIntegrityError::MissingKDBEntryTitle))?;
-                group.entries.insert(entry_title.to_owned(), entry);
+                // Reset state for the next entry
                 entry = Default::default();
+                times = items::Times::default();
+                binary_desc.clear();
+                binary_data.clear();
                 gid = None;
                 num_entries += 1;
             }
@@ -305,7 +398,142 @@ fn parse_entries(
         return Err(DatabaseIntegrityError::IncompleteKDBEntry.into());
     }
 
-    Ok(())
+    Ok(meta_streams)
+}
+
+fn is_meta_stream(entry: &items::Entry, binary_desc: &str, binary_data: &[u8]) -> bool {
+    let field_is = |key: &str, expected: &str| entry.get(key).is_some_and(|value| value == expected);
+
+    !binary_data.is_empty()
+        && binary_desc == META_STREAM_BINARY_DESC
+        && entry.get("Notes").is_some_and(|notes| !notes.is_empty())
+        && field_is("Title", META_STREAM_TITLE)
+        && field_is("UserName", META_STREAM_USER_NAME)
+        && field_is("URL", META_STREAM_URL)
+}
+
+// Decodes the meta-streams with a KDBX equivalent, only the names of the others are kept
+fn apply_meta_stream(
+    meta: &mut items::Meta,
+    root: &mut items::Group,
+    gid_map: &GidMap,
+    stream: &MetaStream,
+) {
+    match stream.name.as_str() {
+        "KPX_CUSTOM_ICONS_4" => {
+            let _ = apply_custom_icons(meta, root, gid_map, &stream.data);
+        }
+        "KPX_GROUP_TREE_STATE" => {
+            let _ = apply_group_tree_state(root, gid_map, &stream.data);
+        }
+        "Default User Name" => {
+            if let Ok(user_name) = utils::from_utf8(&stream.data) {
+                meta.set_default_user_name(&user_name);
+            }
+        }
+        "Database Color" if stream.data.len() == 4 => {
+            // a Windows COLORREF, 0x00bbggrr
+            meta.set_color(&items::ColorValue::new(stream.data[0], stream.data[1], stream.data[2]));
+        }
+        _ => {
+            // such as the UI state of KeePass 1.x, reported as dropped on save
+            meta.add_unknown_meta_stream(&stream.name);
+        }
+    }
+}
+
+// Reads a little endian u32 at `*pos` and advances it, `None` past the end of `data`
+fn read_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let value = data.get(*pos..*pos + 4).map(LittleEndian::read_u32)?;
+    *pos += 4;
+    Some(value)
+}
+
+// KeePassX' custom icons: the icon count, entry count and group count, the PNG icons with
+// their sizes, then (entry UUID, icon index) and (group id, icon index) pairs.
+fn apply_custom_icons(
+    meta: &mut items::Meta,
+    root: &mut items::Group,
+    gid_map: &GidMap,
+    data: &[u8],
+) -> Option<()> {
+    let mut pos = 0;
+    let num_icons = read_u32(data, &mut pos)?;
+    let num_entries = read_u32(data, &mut pos)?;
+    let num_groups = read_u32(data, &mut pos)?;
+
+    let mut icons = Vec::new();
+    for _ in 0..num_icons {
+        let size = read_u32(data, &mut pos)? as usize;
+        let icon = items::IconValue::new(&items::UuidValue::default(), data.get(pos..pos + size)?);
+        pos += size;
+
+        meta.add_custom_icon(&icon);
+        icons.push(icon.uuid_value());
+    }
+
+    for _ in 0..num_entries {
+        let uuid = items::UuidValue::from(data.get(pos..pos + 16)?);
+        pos += 16;
+        let icon = read_u32(data, &mut pos)? as usize;
+
+        if let (Some(entry), Some(icon_uuid)) = (entry_with_uuid(root, &uuid), icons.get(icon)) {
+            entry.set_custom_icon_uuid(icon_uuid);
+        }
+    }
+
+    for _ in 0..num_groups {
+        let group_id = read_u32(data, &mut pos)?;
+        let icon = read_u32(data, &mut pos)? as usize;
+
+        let group = gid_map.get(&group_id).and_then(|path| group_at(root, path));
+        if let (Some(group), Some(icon_uuid)) = (group, icons.get(icon)) {
+            group.custom_icon_uuid = Some(icon_uuid.clone());
+        }
+    }
+
+    Some(())
+}
+
+// KeePassX' expanded groups: the group count, then (group id, expanded flag byte) pairs
+fn apply_group_tree_state(root: &mut items::Group, gid_map: &GidMap, data: &[u8]) -> Option<()> {
+    let mut pos = 0;
+    let num_groups = read_u32(data, &mut pos)?;
+
+    for _ in 0..num_groups {
+        let group_id = read_u32(data, &mut pos)?;
+        let expanded = *data.get(pos)? != 0;
+        pos += 1;
+
+        if let Some(group) = gid_map.get(&group_id).and_then(|path| group_at(root, path)) {
+            group.is_expanded = expanded;
+        }
+    }
+
+    Some(())
+}
+
+// Follows a group path built by `parse_groups`
+fn group_at<'a>(root: &'a mut items::Group, path: &[String]) -> Option<&'a mut items::Group> {
+    let mut group = root;
+    for name in path.iter() {
+        group = group.child_groups.get_mut(name)?;
+    }
+    Some(group)
+}
+
+fn entry_with_uuid<'a>(
+    group: &'a mut items::Group,
+    uuid: &items::UuidValue,
+) -> Option<&'a mut items::Entry> {
+    if let Some(entry) = group.entries.values_mut().find(|entry| entry.uuid() == *uuid) {
+        return Some(entry);
+    }
+
+    group
+        .child_groups
+        .values_mut()
+        .find_map(|child| entry_with_uuid(child, uuid))
 }
 
 // Collapse the tail of a deque of Groups up to the given level
@@ -320,3 +548,867 @@ fn collapse_tail_groups(branch: &mut Vec<items::Group>, level: usize, root: &mut
         parent.child_groups.insert(name, leaf);
     }
 }
+
+// KeePass 1.x' default for new databases, used when the source has no AES-KDF rounds to keep
+const DEFAULT_TRANSFORM_ROUNDS: u32 = 6000;
+
+// The cipher flags and AES-KDF rounds closest to `header`, KDB only knows AES and Twofish
+fn kdb_parameters(header: &Header) -> (u32, u32) {
+    fn flags(cipher: &OuterCipherSuite) -> u32 {
+        match cipher {
+            OuterCipherSuite::Twofish => kdb::FLAG_SHA2 | kdb::FLAG_TWOFISH,
+            _ => kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL,
+        }
+    }
+
+    match header {
+        Header::KDB(header) => (header.flags, header.transform_rounds),
+        Header::KDBX3(header) => (
+            flags(&header.outer_cipher),
+            u32::try_from(header.transform_rounds).unwrap_or(DEFAULT_TRANSFORM_ROUNDS),
+        ),
+        Header::KDBX4(header) => (
+            flags(&header.outer_cipher),
+            match header.kdf {
+                KdfSettings::Aes { rounds, .. } => u32::try_from(rounds).unwrap_or(DEFAULT_TRANSFORM_ROUNDS),
+                _ => DEFAULT_TRANSFORM_ROUNDS,
+            },
+        ),
+    }
+}
+
+/// Rebuilds the header of `db` for KeePass 1.x. KDB keeps attachments only on the entries,
+/// so the binary pools of the metadata and the inner header are dropped.
+pub(crate) fn convert(db: &mut Database) -> Result<()> {
+    if let Header::KDB(_) = db.header {
+        return Ok(());
+    }
+
+    let (flags, transform_rounds) = kdb_parameters(&db.header);
+
+    // the counts and the contents hash are only known when the file is written
+    db.header = Header::KDB(KDBHeader {
+        version: KDB_MAGIC,
+        flags,
+        subversion: kdb::KDB_VERSION,
+        master_seed: random::generate_secret_bytes(16),
+        encryption_iv: random::generate_secret_bytes(16),
+        num_groups: 0,
+        num_entries: 0,
+        contents_hash: vec![],
+        transform_seed: random::generate_secret_bytes(32),
+        transform_rounds,
+    });
+    db.inner_header = InnerHeader::None;
+    db.meta.clear_binaries();
+
+    Ok(())
+}
+
+/// Serializes and encrypts `db` as a KeePass 1.x file. Also returns what could not be written.
+pub(crate) fn dump(
+    db: &Database,
+    key_elements: &[SecretBytes],
+) -> Result<(Vec<u8>, Vec<DroppedFeature>)> {
+    let mut writer = TreeWriter::new(db);
+    writer.write_tree(&db.root);
+    writer.write_meta_streams(&db.meta);
+
+    let mut dropped = Vec::new();
+    if !db.meta.database_name().is_empty() {
+        dropped.push(DroppedFeature::DatabaseName);
+    }
+    if !db.meta.database_description().is_empty() {
+        dropped.push(DroppedFeature::DatabaseDescription);
+    }
+    for name in db.meta.unknown_meta_streams() {
+        dropped.push(DroppedFeature::MetaStream { name });
+    }
+    if db.meta.recycle_bin_enabled() || db.meta.recycle_bin_uuid().is_some() {
+        dropped.push(DroppedFeature::RecycleBin);
+    }
+    if !db.deleted_objects.is_empty() {
+        dropped.push(DroppedFeature::DeletedObjects { items: db.deleted_objects.len() });
+    }
+    if let Header::KDBX4(header) = &db.header {
+        if !header.public_custom_data.is_empty() {
+            dropped.push(DroppedFeature::CustomData { items: header.public_custom_data.len() });
+        }
+    }
+    dropped.append(&mut writer.dropped);
+
+    let payload = Zeroizing::new([writer.groups.as_slice(), writer.entries.as_slice()].concat());
+
+    let (flags, transform_rounds) = kdb_parameters(&db.header);
+
+    let header = KDBHeader {
+        version: KDB_MAGIC,
+        flags,
+        subversion: kdb::KDB_VERSION,
+        master_seed: random::generate_secret_bytes(16),
+        encryption_iv: random::generate_secret_bytes(16),
+        num_groups: writer.num_groups,
+        num_entries: writer.num_entries,
+        contents_hash: cryptopraphy::sha256(&[&payload])?.to_vec(),
+        transform_seed: random::generate_secret_bytes(32),
+        transform_rounds,
+    };
+
+    let master_key = master_key(&header, key_elements)?;
+    let payload_encrypted = cipher_suite(header.flags)?
+        .get_cipher(&master_key, header.encryption_iv.as_ref())?
+        .encrypt(&payload)?;
+
+    let mut data = kdb::write_header(&header);
+    data.extend_from_slice(&payload_encrypted);
+
+    Ok((data, dropped))
+}
+
+// Writes the group and entry TLVs of a tree, counterpart of `parse_groups` and `parse_entries`
+struct TreeWriter {
+    custom_icons: Vec<items::UuidValue>,
+    groups: Vec<u8>,
+    entries: Vec<u8>,
+    num_groups: u32,
+    num_entries: u32,
+    // (entry UUID, icon index) and (group id, icon index) for KPX_CUSTOM_ICONS_4
+    entry_icons: Vec<(items::UuidValue, u32)>,
+    group_icons: Vec<(u32, u32)>,
+    dropped: Vec<DroppedFeature>,
+}
+
+impl TreeWriter {
+    fn new(db: &Database) -> Self {
+        TreeWriter {
+            custom_icons: db.meta.custom_icons().iter().map(|icon| icon.uuid_value()).collect(),
+            groups: Vec::new(),
+            entries: Vec::new(),
+            num_groups: 0,
+            num_entries: 0,
+            entry_icons: Vec::new(),
+            group_icons: Vec::new(),
+            dropped: Vec::new(),
+        }
+    }
+
+    fn write_tree(&mut self, root: &items::Group) {
+        // KDB has no root group, so one with entries is written as a top level group
+        if root.has_entries() {
+            self.dropped.push(DroppedFeature::RootGroupWrapped { group: root.name.clone() });
+            self.write_group(root, 0, &root.name);
+        } else {
+            if !is_default_root(root) {
+                self.dropped.push(DroppedFeature::RootGroupFlattened { group: root.name.clone() });
+            }
+            for (name, group) in sorted(&root.child_groups) {
+                self.write_group(group, 0, name);
+            }
+        }
+    }
+
+    fn write_group(&mut self, group: &items::Group, level: u16, path: &str) {
+        self.num_groups += 1;
+        let group_id = self.num_groups;
+
+        let flags = if group.is_expanded { GROUP_FLAG_EXPANDED } else { 0 };
+
+        write_field(&mut self.groups, 0x0001, &group_id.to_le_bytes());
+        write_field(&mut self.groups, 0x0002, &to_cstring(&group.name));
+        write_times(&mut self.groups, 0x0003, &group.times);
+        write_field(&mut self.groups, 0x0007, &group.icon_id.to_le_bytes());
+        write_field(&mut self.groups, 0x0008, &level.to_le_bytes());
+        write_field(&mut self.groups, 0x0009, &flags.to_le_bytes());
+        write_field(&mut self.groups, 0xffff, &[]);
+
+        if let Some(icon) = self.custom_icon_index(&group.custom_icon_uuid) {
+            self.group_icons.push((group_id, icon));
+        }
+
+        if group.notes.as_ref().is_some_and(|notes| !notes.text.is_empty()) {
+            self.dropped.push(DroppedFeature::GroupNotes { group: path.to_owned() });
+        }
+
+        for (title, entry) in sorted(&group.entries) {
+            self.write_entry(entry, group_id, &format!("{}/{}", path, title), title);
+        }
+
+        for (name, child) in sorted(&group.child_groups) {
+            self.write_group(child, level + 1, &format!("{}/{}", path, name));
+        }
+    }
+
+    fn write_entry(&mut self, entry: &items::Entry, group_id: u32, path: &str, title: &str) {
+        self.num_entries += 1;
+
+        let field = |key: &str| entry.get(key).map(|value| to_cstring(&value)).unwrap_or_else(|| vec![0]);
+        let title = entry.title().map(|value| to_cstring(&value)).unwrap_or_else(|| to_cstring(title));
+        let binary = entry.binary();
+
+        let out = &mut self.entries;
+        write_field(out, 0x0001, entry.uuid().as_bytes());
+        write_field(out, 0x0002, &group_id.to_le_bytes());
+        write_field(out, 0x0003, &(entry.icon_id().max(0) as u32).to_le_bytes());
+        write_field(out, 0x0004, &title);
+        write_field(out, 0x0005, &field("URL"));
+        write_field(out, 0x0006, &field("UserName"));
+        write_field(out, 0x0007, &Zeroizing::new(field("Password")));
+        write_field(out, 0x0008, &field("Notes"));
+        write_times(out, 0x0009, &entry.times().unwrap_or_default());
+        write_field(out, 0x000d, &to_cstring(&binary.as_ref().map(|b| b.id()).unwrap_or_default()));
+        write_field(out, 0x000e, &binary.map(|b| b.value()).unwrap_or_default());
+        write_field(out, 0xffff, &[]);
+
+        if let Some(icon) = self.custom_icon_index(&entry.custom_icon_uuid()) {
+            self.entry_icons.push((entry.uuid(), icon));
+        }
+
+        self.report_dropped(entry, path);
+    }
+
+    fn report_dropped(&mut self, entry: &items::Entry, path: &str) {
+        let entry_path = || path.to_owned();
+
+        let mut custom_fields: Vec<_> = entry
+            .fields()
+            .map(|(key, _)| key)
+            .filter(|key| !items::MemoryProtection::is_standard_field(key))
+            .collect();
+        custom_fields.sort();
+        for field in custom_fields {
+            self.dropped.push(DroppedFeature::CustomField { entry: entry_path(), field: field.clone() });
+        }
+
+        // KDB has room for a single attachment per entry
+        let attachments = entry.binaries().len();
+        if attachments > 1 {
+            self.dropped.push(DroppedFeature::Attachments { entry: entry_path(), items: attachments - 1 });
+        }
+
+        if entry.has_history_items() {
+            let items = entry.history_items().len();
+            self.dropped.push(DroppedFeature::History { entry: entry_path(), items });
+        }
+
+        if entry.autotype().is_some_and(|a| a.sequence.is_some() || !a.associations.is_empty()) {
+            self.dropped.push(DroppedFeature::AutoType { entry: entry_path() });
+        }
+
+        if entry.tags().is_some_and(|tags| !tags.is_empty()) {
+            self.dropped.push(DroppedFeature::Tags { entry: entry_path() });
+        }
+
+        if entry.foreground_color().is_some() || entry.background_color().is_some() {
+            self.dropped.push(DroppedFeature::Colors { entry: entry_path() });
+        }
+
+        if entry.override_url().is_some_and(|url| !url.is_empty()) {
+            self.dropped.push(DroppedFeature::OverrideUrl { entry: entry_path() });
+        }
+    }
+
+    fn custom_icon_index(&self, uuid: &Option<items::UuidValue>) -> Option<u32> {
+        let uuid = uuid.as_ref()?;
+        self.custom_icons.iter().position(|icon| icon == uuid).map(|index| index as u32)
+    }
+
+    // Stores the metadata KeePass 1.x has meta-streams for, counterpart of `apply_meta_stream`
+    fn write_meta_streams(&mut self, meta: &items::Meta) {
+        // meta-streams are entries and need a group to live in
+        let has_group = self.num_groups > 0;
+
+        let user_name = meta.default_user_name();
+        if !user_name.is_empty() {
+            if has_group {
+                self.write_meta_stream("Default User Name", &to_cstring(&user_name));
+            } else {
+                self.dropped.push(DroppedFeature::DefaultUserName);
+            }
+        }
+
+        if let Some(color) = meta.color() {
+            if has_group {
+                self.write_meta_stream("Database Color", &[color.red(), color.green(), color.blue(), 0]);
+            } else {
+                self.dropped.push(DroppedFeature::DatabaseColor);
+            }
+        }
+
+        let icons = meta.custom_icons();
+        if !icons.is_empty() {
+            if has_group {
+                let data = self.custom_icons_stream(&icons);
+                self.write_meta_stream("KPX_CUSTOM_ICONS_4", &data);
+            } else {
+                self.dropped.push(DroppedFeature::CustomIcons { items: icons.len() });
+            }
+        }
+    }
+
+    // The KPX_CUSTOM_ICONS_4 layout: counts, the PNGs, then the entry and group assignments
+    fn custom_icons_stream(&self, icons: &[items::IconValue]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(icons.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.entry_icons.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.group_icons.len() as u32).to_le_bytes());
+        for icon in icons.iter() {
+            let png = icon.data();
+            data.extend_from_slice(&(png.len() as u32).to_le_bytes());
+            data.extend_from_slice(&png);
+        }
+        for (uuid, icon) in self.entry_icons.iter() {
+            data.extend_from_slice(uuid.as_bytes());
+            data.extend_from_slice(&icon.to_le_bytes());
+        }
+        for (group_id, icon) in self.group_icons.iter() {
+            data.extend_from_slice(&group_id.to_le_bytes());
+            data.extend_from_slice(&icon.to_le_bytes());
+        }
+
+        data
+    }
+
+    fn write_meta_stream(&mut self, name: &str, data: &[u8]) {
+        self.num_entries += 1;
+
+        let out = &mut self.entries;
+        write_field(out, 0x0001, items::UuidValue::default().as_bytes());
+        write_field(out, 0x0002, &1u32.to_le_bytes());
+        write_field(out, 0x0003, &0u32.to_le_bytes());
+        write_field(out, 0x0004, &to_cstring(META_STREAM_TITLE));
+        write_field(out, 0x0005, &to_cstring(META_STREAM_URL));
+        write_field(out, 0x0006, &to_cstring(META_STREAM_USER_NAME));
+        write_field(out, 0x0007, &to_cstring(""));
+        write_field(out, 0x0008, &to_cstring(name));
+        write_times(out, 0x0009, &items::Times::default());
+        write_field(out, 0x000d, &to_cstring(META_STREAM_BINARY_DESC));
+        write_field(out, 0x000e, data);
+        write_field(out, 0xffff, &[]);
+    }
+}
+
+// Whether `root` holds nothing KDB loses by flattening it, as the root a KDB file is read into
+fn is_default_root(root: &items::Group) -> bool {
+    let default = items::Group::root();
+
+    root.name == default.name
+        && root.notes.as_ref().map_or(true, |notes| notes.text.is_empty())
+        && root.icon_id == default.icon_id
+        && root.custom_icon_uuid.is_none()
+        && root.times == default.times
+}
+
+fn write_field(out: &mut Vec<u8>, field_type: u16, value: &[u8]) {
+    out.extend_from_slice(&field_type.to_le_bytes());
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+// Writes the Creation/LastMod/LastAccess/Expire fields starting at `first_field_type`
+fn write_times(out: &mut Vec<u8>, first_field_type: u16, times: &items::Times) {
+    let now = Utc::now();
+    let time = |value: &Option<items::TimestampValue>| {
+        value.as_ref().and_then(|t| t.as_datetime()).unwrap_or(now)
+    };
+
+    let expiry = if times.expires == Some(true) {
+        times.expiry_time.as_ref().and_then(|t| t.as_datetime()).unwrap_or(now)
+    } else {
+        utils::never_expires()
+    };
+
+    let packed = [
+        time(&times.creation_time),
+        time(&times.last_modification_time),
+        time(&times.last_access_time),
+        expiry,
+    ];
+
+    for (offset, value) in packed.iter().enumerate() {
+        write_field(out, first_field_type + offset as u16, &utils::to_packed_time(value));
+    }
+}
+
+fn to_cstring(value: &str) -> Vec<u8> {
+    let mut res = value.as_bytes().to_vec();
+    res.push(0);
+    res
+}
+
+// Sorted to keep the output deterministic
+fn sorted<T>(map: &HashMap<String, T>) -> Vec<(&String, &T)> {
+    let mut items: Vec<_> = map.iter().collect();
+    items.sort_by(|a, b| a.0.cmp(b.0));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::structures::variant_dictionary::VariantDictionaryValue;
+    use spectral::prelude::*;
+
+    // 2021-06-15 13:45:30 and KeePass 1.x' "never expires"
+    const TIME: [u8; 5] = [0x1f, 0x95, 0x9e, 0xdb, 0x5e];
+    const NEVER: [u8; 5] = [0x2e, 0xdf, 0x39, 0x7e, 0xfb];
+
+    fn field(field_type: u16, value: &[u8]) -> Vec<u8> {
+        let mut res = field_type.to_le_bytes().to_vec();
+        res.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        res.extend_from_slice(value);
+        res
+    }
+
+    fn sample_group() -> Vec<u8> {
+        [
+            field(0x0001, &7u32.to_le_bytes()),
+            field(0x0002, b"Internet\0"),
+            field(0x0003, &TIME),
+            field(0x0004, &TIME),
+            field(0x0005, &TIME),
+            field(0x0006, &NEVER),
+            field(0x0007, &48u32.to_le_bytes()),
+            field(0x0008, &0u16.to_le_bytes()),
+            field(0x0009, &GROUP_FLAG_EXPANDED.to_le_bytes()),
+            field(0xffff, &[]),
+        ].concat()
+    }
+
+    fn sample_entry(binary_desc: &[u8], binary_data: &[u8]) -> Vec<u8> {
+        [
+            field(0x0001, &[0x11u8; 16]),
+            field(0x0002, &7u32.to_le_bytes()),
+            field(0x0003, &23u32.to_le_bytes()),
+            field(0x0004, b"mail\0"),
+            field(0x0005, b"https://mail.example.com\0"),
+            field(0x0006, b"alice\0"),
+            field(0x0007, b"hunter2\0"),
+            field(0x0008, b"some notes\0"),
+            field(0x0009, &TIME),
+            field(0x000a, &TIME),
+            field(0x000b, &TIME),
+            field(0x000c, &TIME),
+            field(0x000d, binary_desc),
+            field(0x000e, binary_data),
+            field(0xffff, &[]),
+        ].concat()
+    }
+
+    fn meta_stream(name: &str, data: &[u8]) -> Vec<u8> {
+        [
+            field(0x0001, &[0u8; 16]),
+            field(0x0002, &7u32.to_le_bytes()),
+            field(0x0003, &0u32.to_le_bytes()),
+            field(0x0004, b"Meta-Info\0"),
+            field(0x0005, b"$\0"),
+            field(0x0006, b"SYSTEM\0"),
+            field(0x0007, b"\0"),
+            field(0x0008, format!("{}\0", name).as_bytes()),
+            field(0x000d, b"bin-stream\0"),
+            field(0x000e, data),
+            field(0xffff, &[]),
+        ].concat()
+    }
+
+    fn parse_sample(entries: &[Vec<u8>]) -> (items::Meta, items::Group) {
+        let header = KDBHeader {
+            version: 0,
+            flags: 0,
+            subversion: 0,
+            master_seed: vec![],
+            encryption_iv: vec![],
+            num_groups: 1,
+            num_entries: entries.len() as u32,
+            contents_hash: vec![],
+            transform_seed: vec![],
+            transform_rounds: 0,
+        };
+
+        let data = [sample_group(), entries.concat()].concat();
+
+        parse_db(&header, &data).unwrap()
+    }
+
+    #[test]
+    fn test_parse_group_fields() {
+        let (_, root) = parse_sample(&[sample_entry(b"\0", &[])]);
+        let group = &root.child_groups["Internet"];
+        let time = items::TimestampValue::from(utils::from_packed_time(&TIME).unwrap());
+
+        assert_that(&group.icon_id).is_equal_to(48);
+        assert_that(&group.is_expanded).is_true();
+        assert_that(&group.times.creation_time).is_equal_to(Some(time.clone()));
+        assert_that(&group.times.last_modification_time).is_equal_to(Some(time.clone()));
+        assert_that(&group.times.last_access_time).is_equal_to(Some(time));
+        assert_that(&group.times.expires).is_equal_to(Some(false));
+    }
+
+    #[test]
+    fn test_parse_entry_fields() {
+        let (_, root) = parse_sample(&[sample_entry(b"\0", &[])]);
+        let entry = &root.child_groups["Internet"].entries["mail"];
+        let times = entry.times().unwrap();
+
+        assert_that(&entry.uuid()).is_equal_to(items::UuidValue::from([0x11u8; 16].as_ref()));
+        assert_that(&entry.icon_id()).is_equal_to(23);
+        assert_that(&entry.get("URL").as_deref()).is_equal_to(Some("https://mail.example.com"));
+        assert_that(&entry.username().as_deref()).is_equal_to(Some("alice"));
+        assert_that(&entry.password().as_deref()).is_equal_to(Some("hunter2"));
+        assert_that(&entry.get("Notes").as_deref()).is_equal_to(Some("some notes"));
+        assert_that(&times.expires).is_equal_to(Some(true));
+        assert_that(&times.expiry_time)
+            .is_equal_to(Some(items::TimestampValue::from(utils::from_packed_time(&TIME).unwrap())));
+        assert_that(&entry.binary()).is_none();
+    }
+
+    #[test]
+    fn test_parse_entry_attachment() {
+        let (_, root) = parse_sample(&[sample_entry(b"key.pem\0", b"-----BEGIN")]);
+        let entry = &root.child_groups["Internet"].entries["mail"];
+
+        let binary = assert_that(&entry.binary()).is_some().subject.clone();
+
+        assert_that(&binary.id()).is_equal_to("key.pem".to_owned());
+        assert_that(&binary.value()).is_equal_to(b"-----BEGIN".to_vec());
+    }
+
+    #[test]
+    fn test_meta_streams_are_hidden() {
+        let (meta, root) = parse_sample(&[
+            sample_entry(b"\0", &[]),
+            meta_stream("Simple UI State", &[1, 2, 3]),
+            meta_stream("Default User Name", b"alice\0"),
+        ]);
+        let group = &root.child_groups["Internet"];
+
+        assert_that(&group.entries.len()).is_equal_to(1);
+        assert_that(&group.entries.contains_key("Meta-Info")).is_false();
+        assert_that(&meta.default_user_name()).is_equal_to("alice".to_owned());
+    }
+
+    #[test]
+    fn test_meta_stream_custom_icons() {
+        let icons = [
+            3u32.to_le_bytes().to_vec(), // icons
+            1u32.to_le_bytes().to_vec(), // entries
+            1u32.to_le_bytes().to_vec(), // groups
+            2u32.to_le_bytes().to_vec(), b"i0".to_vec(),
+            2u32.to_le_bytes().to_vec(), b"i1".to_vec(),
+            2u32.to_le_bytes().to_vec(), b"i2".to_vec(),
+            [0x11u8; 16].to_vec(), 2u32.to_le_bytes().to_vec(),
+            7u32.to_le_bytes().to_vec(), 1u32.to_le_bytes().to_vec(),
+        ].concat();
+
+        let (meta, root) = parse_sample(&[
+            sample_entry(b"\0", &[]),
+            meta_stream("KPX_CUSTOM_ICONS_4", &icons),
+        ]);
+        let group = &root.child_groups["Internet"];
+        let custom_icons = meta.custom_icons();
+
+        assert_that(&custom_icons.len()).is_equal_to(3);
+        assert_that(&custom_icons[2].data()).is_equal_to(b"i2".to_vec());
+        assert_that(&group.entries["mail"].custom_icon_uuid())
+            .is_equal_to(Some(custom_icons[2].uuid_value()));
+        assert_that(&group.custom_icon_uuid).is_equal_to(Some(custom_icons[1].uuid_value()));
+    }
+
+    #[test]
+    fn test_meta_stream_group_tree_state() {
+        let state = [1u32.to_le_bytes().to_vec(), 7u32.to_le_bytes().to_vec(), vec![0u8]].concat();
+
+        let (_, root) = parse_sample(&[
+            sample_entry(b"\0", &[]),
+            meta_stream("KPX_GROUP_TREE_STATE", &state),
+        ]);
+
+        assert_that(&root.child_groups["Internet"].is_expanded).is_false();
+    }
+
+    #[test]
+    fn test_truncated_meta_stream_is_ignored() {
+        let (meta, root) = parse_sample(&[
+            sample_entry(b"\0", &[]),
+            meta_stream("KPX_CUSTOM_ICONS_4", &[5, 0, 0, 0, 0]),
+        ]);
+
+        assert_that(&meta.custom_icons().is_empty()).is_true();
+        assert_that(&root.child_groups["Internet"].entries.len()).is_equal_to(1);
+    }
+
+    fn sample_database(flags: u32) -> Database {
+        let mut entry = items::Entry::new(&items::UuidValue::from([0x22u8; 16].as_ref()));
+        entry
+            .set_icon_id(23)
+            .set_times(&items::Times {
+                expires: Some(true),
+                expiry_time: Some(items::TimestampValue::from(utils::from_packed_time(&TIME).unwrap())),
+                ..items::Times::default()
+            })
+            .set_binary(&items::Binary::new("key.pem", false, b"-----BEGIN"))
+            .set_tags("work")
+            .add("Title", &items::StringValue::UnprotectedString("mail".to_owned()))
+            .add("UserName", &items::StringValue::UnprotectedString("alice".to_owned()))
+            .add("Password", &items::StringValue::ProtectedString("hunter2".into()))
+            .add("PIN", &items::StringValue::ProtectedString("1234".into()));
+        entry.add_history(&entry.clone());
+
+        let mut group = items::Group::new("Internet", &HashMap::new(), &HashMap::new());
+        group.icon_id = 48;
+        group.is_expanded = true;
+        group.add_entry("mail", &entry);
+
+        let mut root = items::Group::root();
+        root.add_group("Internet", &group);
+
+        let mut meta = items::Meta::default();
+        meta.set_default_user_name("alice");
+
+        Database {
+            header: Header::KDB(KDBHeader {
+                version: KDB_MAGIC,
+                flags,
+                subversion: kdb::KDB_VERSION,
+                master_seed: vec![],
+                encryption_iv: vec![],
+                num_groups: 0,
+                num_entries: 0,
+                contents_hash: vec![],
+                transform_seed: vec![],
+                transform_rounds: 10,
+            }),
+            inner_header: InnerHeader::None,
+            meta,
+            root,
+            deleted_objects: Vec::new(),
+        }
+    }
+
+    fn password(value: &str) -> Vec<SecretBytes> {
+        vec![into_secret(cryptopraphy::sha256(&[value.as_bytes()]).unwrap())]
+    }
+
+    #[test]
+    fn test_dump_parse_round_trip() {
+        let db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+
+        let (data, _) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        let group = &parsed.root.child_groups["Internet"];
+        let entry = &group.entries["mail"];
+        let binary = entry.binary().unwrap();
+
+        assert_that(&group.icon_id).is_equal_to(48);
+        assert_that(&group.is_expanded).is_true();
+        assert_that(&group.entries.len()).is_equal_to(1);
+        assert_that(&entry.uuid()).is_equal_to(items::UuidValue::from([0x22u8; 16].as_ref()));
+        assert_that(&entry.icon_id()).is_equal_to(23);
+        assert_that(&entry.username().as_deref()).is_equal_to(Some("alice"));
+        assert_that(&entry.password().as_deref()).is_equal_to(Some("hunter2"));
+        assert_that(&entry.times().unwrap().expires).is_equal_to(Some(true));
+        assert_that(&binary.id()).is_equal_to("key.pem".to_owned());
+        assert_that(&binary.value()).is_equal_to(b"-----BEGIN".to_vec());
+        assert_that(&parsed.meta.default_user_name()).is_equal_to("alice".to_owned());
+    }
+
+    #[test]
+    fn test_dump_parse_twofish() {
+        let db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_TWOFISH);
+
+        let (data, _) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        let header = match &parsed.header {
+            Header::KDB(header) => header,
+            _ => panic!("expected a KDB header"),
+        };
+
+        assert_that(&(header.flags & kdb::FLAG_TWOFISH)).is_equal_to(kdb::FLAG_TWOFISH);
+        assert_that(&parsed.root.child_groups["Internet"].entries.contains_key("mail")).is_true();
+    }
+
+    #[test]
+    fn test_dump_reports_dropped_features() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.meta.set_database_name("vault");
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).is_equal_to(vec![
+            DroppedFeature::DatabaseName,
+            DroppedFeature::CustomField { entry: "Internet/mail".to_owned(), field: "PIN".to_owned() },
+            DroppedFeature::History { entry: "Internet/mail".to_owned(), items: 1 },
+            DroppedFeature::Tags { entry: "Internet/mail".to_owned() },
+        ]);
+    }
+
+    #[test]
+    fn test_dump_of_a_parsed_file_drops_nothing() {
+        let (data, _) = dump(&sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL), &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        let (_, dropped) = dump(&parsed, &password("secret")).unwrap();
+
+        assert_that(&dropped).is_empty();
+    }
+
+    #[test]
+    fn test_dump_reports_root_group_data() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.root.name = "Passwords".to_owned();
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::RootGroupFlattened { group: "Passwords".to_owned() });
+    }
+
+    #[test]
+    fn test_dump_reports_meta_streams_without_groups() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.root = items::Group::root();
+        db.meta
+            .set_color(&items::ColorValue::new(240, 160, 0))
+            .add_custom_icon(&items::IconValue::new(&items::UuidValue::default(), b"PNG"));
+
+        let (data, dropped) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::DefaultUserName);
+        assert_that(&dropped).contains(DroppedFeature::DatabaseColor);
+        assert_that(&dropped).contains(DroppedFeature::CustomIcons { items: 1 });
+        assert_that(&parsed.meta.default_user_name()).is_equal_to(String::new());
+    }
+
+    #[test]
+    fn test_dump_reports_group_notes() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        let group = db.root.child_groups.get_mut("Internet").unwrap();
+        group.set_notes(&items::Notes::new("shared logins"));
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::GroupNotes { group: "Internet".to_owned() });
+    }
+
+    #[test]
+    fn test_dump_reports_extra_attachments() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        let entry = db.root.child_groups.get_mut("Internet").unwrap().entries.get_mut("mail").unwrap();
+        entry
+            .set_binary(&items::Binary::new("key.pub", false, b"ssh-ed25519"))
+            .set_binary(&items::Binary::new("notes.txt", false, b"backup codes"));
+
+        let (data, dropped) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::Attachments { entry: "Internet/mail".to_owned(), items: 2 });
+        assert_that(&parsed.root.child_groups["Internet"].entries["mail"].binaries().len()).is_equal_to(1);
+    }
+
+    #[test]
+    fn test_dump_reports_unknown_meta_streams() {
+        let (meta, root) = parse_sample(&[
+            sample_entry(b"\0", &[]),
+            meta_stream("Simple UI State", &[1, 2, 3]),
+            meta_stream("Default User Name", b"alice\0"),
+        ]);
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.meta = meta;
+        db.root = root;
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&db.meta.unknown_meta_streams()).is_equal_to(vec!["Simple UI State".to_owned()]);
+        assert_that(&dropped).contains(DroppedFeature::MetaStream { name: "Simple UI State".to_owned() });
+    }
+
+    #[test]
+    fn test_dump_reports_wrapped_root_group() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.root.add_entry("bank", &items::Entry::default());
+
+        let (data, dropped) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::RootGroupWrapped { group: "root".to_owned() });
+        assert_that(&dropped).does_not_contain(DroppedFeature::RootGroupFlattened { group: "root".to_owned() });
+        assert_that(&parsed.root.child_groups.contains_key("root")).is_true();
+    }
+
+    #[test]
+    fn test_dump_reports_recycle_bin() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+        assert_that(&dropped).does_not_contain(DroppedFeature::RecycleBin);
+
+        db.meta.set_recycle_bin_enabled(true);
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::RecycleBin);
+    }
+
+    #[test]
+    fn test_dump_reports_deleted_objects() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        db.deleted_objects.push(items::DeletedObject::new(
+            &items::UuidValue::from([0x33u8; 16].as_ref()),
+            &items::TimestampValue::now(),
+        ));
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::DeletedObjects { items: 1 });
+    }
+
+    #[test]
+    fn test_dump_reports_custom_data() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+        super::super::kdbx4::convert(&mut db, None).unwrap();
+        if let Header::KDBX4(header) = &mut db.header {
+            header.public_custom_data.put("team", VariantDictionaryValue::String("platform".to_owned()));
+        }
+
+        let (_, dropped) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&dropped).contains(DroppedFeature::CustomData { items: 1 });
+    }
+
+    #[test]
+    fn test_convert_keeps_cipher_and_rounds() {
+        let mut db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_TWOFISH);
+
+        super::super::kdbx4::convert(&mut db, None).unwrap();
+
+        match &db.header {
+            Header::KDBX4(header) => {
+                assert_that(&matches!(header.outer_cipher, OuterCipherSuite::Twofish)).is_true();
+                assert_that(&matches!(header.kdf, KdfSettings::Aes { rounds: 10, .. })).is_true();
+            }
+            _ => panic!("expected a KDBX4 header"),
+        }
+
+        convert(&mut db).unwrap();
+
+        let (data, _) = dump(&db, &password("secret")).unwrap();
+        let parsed = parse(&data, &password("secret")).unwrap();
+
+        match &parsed.header {
+            Header::KDB(header) => {
+                assert_that(&header.flags).is_equal_to(kdb::FLAG_SHA2 | kdb::FLAG_TWOFISH);
+                assert_that(&header.transform_rounds).is_equal_to(10);
+            }
+            _ => panic!("expected a KDB header"),
+        }
+        assert_that(&parsed.root.child_groups["Internet"].entries.contains_key("mail")).is_true();
+    }
+
+    #[test]
+    fn test_parse_wrong_password() {
+        let db = sample_database(kdb::FLAG_SHA2 | kdb::FLAG_RIJNDAEL);
+
+        let (data, _) = dump(&db, &password("secret")).unwrap();
+
+        assert_that(&matches!(parse(&data, &password("guess")), Err(Error::IncorrectKey))).is_true();
+    }
+}
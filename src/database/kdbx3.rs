@@ -9,13 +9,19 @@ use crate::{
             Header,
             InnerHeader
         },
+        compression::Compression,
         kdbx3::KDBX3Header,
         settings::Settings,
+        suites::{
+            KdfSettings,
+            OuterCipherSuite,
+        },
         traits::Sizable
     },
     database::{
         Database,
-        items
+        items,
+        kdb,
     },
     errors::{
         DatabaseIntegrityError,
@@ -25,9 +31,16 @@ use crate::{
         database::{
             binary::{
                 constants,
-                header::kdbx3::read_header
+                header::kdbx3::{
+                    is_legacy_version,
+                    read_header,
+                    write_header,
+                },
+            },
+            xml::{
+                parser,
+                writer,
             },
-            xml::parser
         },
         random
     },
@@ -37,24 +50,33 @@ use crate::api::suites::InnerCipherSuite;
 use crate::internal::cryptopraphy::{
     self,
     kdf::Kdf,
+    wipe::{
+        into_secret,
+        SecretBytes,
+    },
 };
 
+use generic_array::GenericArray;
+use zeroize::Zeroizing;
+
+const COMPRESSION_LEVEL: u32 = 6;
+
 // create a new database
 pub(crate) fn create(
     settings: &Settings
 ) -> Result<Database> {
     let mut kdbx3_header = KDBX3Header {
         version: constants::KDBX_MAGIC,
-        file_major_version: 4,
-        file_minor_version: 0,
+        file_major_version: 3,
+        file_minor_version: 1,
         outer_cipher: settings.outer_cipher_suite(),
         compression: settings.compression(),
-        master_seed: random::generate_random_bytes(32),
-        transform_seed: random::generate_random_bytes(32),
+        master_seed: random::generate_secret_bytes(32),
+        transform_seed: random::generate_secret_bytes(32),
         transform_rounds: settings.transform_rounds(),
-        outer_iv: random::generate_random_bytes(32),
-        protected_stream_key: random::generate_random_bytes(32),
-        stream_start: random::generate_random_bytes(32),
+        outer_iv: random::generate_secret_bytes(16),
+        protected_stream_key: random::generate_secret_bytes(32),
+        stream_start: random::generate_secret_bytes(32),
         inner_cipher: InnerCipherSuite::Salsa20, // skip None and Arc4Variant
         body_start: 0,
     };
@@ -65,13 +87,76 @@ pub(crate) fn create(
         Database {
             header: Header::KDBX3(kdbx3_header),
             inner_header: InnerHeader::None,
+            meta: items::Meta::default(),
             root: items::Group::root(),
+            deleted_objects: Vec::new(),
         }
     )
 }
 
+// KeePass' default for AES-KDF, used when the source database derives its key differently
+const DEFAULT_TRANSFORM_ROUNDS: u64 = 60_000;
+
+/// Rebuilds the header of `db` for KDBX 3.1. KDBX 3 only knows AES-KDF and no ChaCha20 outer
+/// cipher, those fall back to the defaults. The attachments stay on the entries.
+/// A KDBX 3 database is only rebuilt if it predates 3.1 or uses the read-only ArcFour variant.
+pub(crate) fn convert(db: &mut Database) -> Result<()> {
+    let (outer_cipher, compression, transform_rounds) = match &db.header {
+        // pre-3.1 files and the read-only ArcFour variant are rebuilt as KDBX 3.1 with Salsa20
+        Header::KDBX3(header)
+            if !is_legacy_version(header.file_major_version, header.file_minor_version)
+                && !matches!(header.inner_cipher, InnerCipherSuite::ArcFourVariant) => {
+            return Ok(());
+        }
+        Header::KDBX3(header) => (
+            header.outer_cipher.clone(),
+            header.compression.clone(),
+            header.transform_rounds,
+        ),
+        Header::KDBX4(header) => (
+            match header.outer_cipher {
+                OuterCipherSuite::ChaCha20 => OuterCipherSuite::AES256,
+                ref cipher => cipher.clone(),
+            },
+            header.compression.clone(),
+            match header.kdf {
+                KdfSettings::Aes { rounds, .. } => rounds,
+                _ => DEFAULT_TRANSFORM_ROUNDS,
+            },
+        ),
+        Header::KDB(header) => (
+            kdb::cipher_suite(header.flags)?,
+            Compression::GZip,
+            u64::from(header.transform_rounds),
+        ),
+    };
+
+    let mut kdbx3_header = KDBX3Header {
+        version: constants::KDBX_MAGIC,
+        file_major_version: 3,
+        file_minor_version: 1,
+        outer_cipher,
+        compression,
+        master_seed: random::generate_secret_bytes(32),
+        transform_seed: random::generate_secret_bytes(32),
+        transform_rounds,
+        outer_iv: random::generate_secret_bytes(16),
+        protected_stream_key: random::generate_secret_bytes(32),
+        stream_start: random::generate_secret_bytes(32),
+        inner_cipher: InnerCipherSuite::Salsa20,
+        body_start: 0,
+    };
+
+    kdbx3_header.body_start = kdbx3_header.size();
+
+    db.header = Header::KDBX3(kdbx3_header);
+    db.inner_header = InnerHeader::None;
+
+    Ok(())
+}
+
 /// Open, decrypt and database a KeePass types from a source and a password
-pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
+pub(crate) fn parse(data: &[u8], key_elements: &[SecretBytes]) -> Result<Database> {
     // database header
     let header = read_header(data)?;
 
@@ -83,40 +168,40 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
     // Rest of file after header is payload
     let payload_encrypted = &data[pos..];
 
-    // derive master key from composite key, transform_seed, transform_rounds and master_seed
-    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
-    let composite_key = cryptopraphy::sha256(&key_elements)?;
-
-    // KDF is hard coded for KDBX 3
-    let transformed_key = cryptopraphy::kdf::AesKdf::new(
-        header.transform_seed.as_ref(),
-        header.transform_rounds,
-    ).transform_key(&composite_key)?;
-
-    let master_key = cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?;
+    let master_key = derive_master_key(&header, key_elements)?;
 
-    // Decrypt payload
+    // Decrypt payload, a wrong key almost always shows as broken padding
     let payload = header
         .outer_cipher
         .get_cipher(&master_key, header.outer_iv.as_ref())?
-        .decrypt(payload_encrypted)?;
+        .decrypt(payload_encrypted)
+        .map_err(|_| Error::IncorrectKey)?;
 
     // Check if we decrypted correctly
-    if &payload[0..header.stream_start.len()] != header.stream_start.as_slice() {
+    if !payload.starts_with(&header.stream_start) {
         return Err(Error::IncorrectKey);
     }
 
-    // Derive stream key for decrypting inner protected values and set up decryption context
-    let stream_key = cryptopraphy::sha256(&[header.protected_stream_key.as_ref()])?;
+    // without stream start bytes (pre-3.1 files) a wrong key first shows in the block hashes
+    let has_stream_start = !header.stream_start.is_empty();
+    pos = header.stream_start.len();
+
+    // Derive stream key for decrypting inner protected values and set up decryption context,
+    // the ArcFour variant takes the key as is
+    let stream_key = match header.inner_cipher {
+        InnerCipherSuite::ArcFourVariant => Zeroizing::new(header.protected_stream_key.clone()),
+        _ => into_secret(cryptopraphy::sha256(&[header.protected_stream_key.as_ref()])?),
+    };
     let mut inner_decryptor = header.inner_cipher.get_cipher(&stream_key)?;
 
     let mut db = Database {
         header: Header::KDBX3(header),
         inner_header: InnerHeader::None,
+        meta: items::Meta::default(),
         root: items::Group::root(),
+        deleted_objects: Vec::new(),
     };
 
-    pos = 32;
     let mut block_index = 0;
     loop {
         // Parse blocks in payload.
@@ -139,11 +224,13 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
             break;
         }
 
-        let block_buffer_compressed = &payload[(pos + 40)..(pos + 40 + block_size)];
-
-        // Test block hash
+        // Test block hash, a block reaching past the payload cannot match it either
+        let block_buffer_compressed = payload.get((pos + 40)..(pos + 40 + block_size)).unwrap_or_default();
         let block_hash_check = cryptopraphy::sha256(&[&block_buffer_compressed])?;
-        if block_hash != block_hash_check.as_slice() {
+        if block_buffer_compressed.len() != block_size || block_hash != block_hash_check.as_slice() {
+            if block_index == 0 && !has_stream_start {
+                return Err(Error::IncorrectKey);
+            }
             return Err(DatabaseIntegrityError::BlockHashMismatch { block_index }.into());
         }
 
@@ -151,7 +238,12 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
         let block_buffer = compression.decompress(block_buffer_compressed)?;
 
         // Parse XML data
-        let block_group = parser::parse(&block_buffer, &mut *inner_decryptor)?;
+        let (block_meta, block_group, block_deleted_objects) =
+            parser::parse(&block_buffer, &mut *inner_decryptor, &[])?;
+        if block_index == 0 {
+            db.meta = block_meta;
+        }
+        db.deleted_objects.extend(block_deleted_objects);
         db.root
             .child_groups
             .insert(block_group.name.clone(), block_group);
@@ -171,3 +263,111 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
 
     Ok(db)
 }
+
+/// Serializes and encrypts `db` as a KDBX 3.1 file with Salsa20 as inner stream, also for
+/// databases read from 2.x/3.0 files. Seeds, IV, stream start and stream key are fresh for
+/// every save. The attachments go into `<Meta><Binaries>`, KDBX 3 has no inner header.
+pub(crate) fn dump(db: &Database, key_elements: &[SecretBytes]) -> Result<Vec<u8>> {
+    let header = match &db.header {
+        Header::KDBX3(header) => header,
+        header => return Err(Error::UnsupportedFormat { format: header.format_version() }),
+    };
+
+    let header = KDBX3Header {
+        version: header.version,
+        file_major_version: 3,
+        file_minor_version: 1,
+        outer_cipher: header.outer_cipher.clone(),
+        compression: header.compression.clone(),
+        master_seed: random::generate_secret_bytes(32),
+        transform_seed: random::generate_secret_bytes(32),
+        transform_rounds: header.transform_rounds,
+        outer_iv: random::generate_secret_bytes(16),
+        protected_stream_key: random::generate_secret_bytes(32),
+        stream_start: random::generate_secret_bytes(32),
+        inner_cipher: InnerCipherSuite::Salsa20,
+        body_start: 0,
+    };
+
+    let stream_key = into_secret(cryptopraphy::sha256(&[header.protected_stream_key.as_ref()])?);
+    let mut inner_encryptor = header.inner_cipher.get_cipher(&stream_key)?;
+    let (xml, _) = writer::write(
+        &db.meta,
+        &db.root,
+        &db.deleted_objects,
+        &mut *inner_encryptor,
+        writer::AttachmentPool::Meta,
+    )?;
+    let xml = Zeroizing::new(xml);
+
+    // `parse` decompresses every block on its own, so the whole document goes into one block
+    let block = Zeroizing::new(
+        header.compression.get_compression().compress(&xml, COMPRESSION_LEVEL)?
+    );
+    let block_hash = cryptopraphy::sha256(&[&block])?;
+
+    let mut payload = Zeroizing::new(header.stream_start.clone());
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload.extend_from_slice(&block_hash);
+    payload.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&block);
+    // a block of size 0 with an all zero hash ends the stream
+    payload.extend_from_slice(&1u32.to_le_bytes());
+    payload.extend_from_slice(&[0u8; 32]);
+    payload.extend_from_slice(&0u32.to_le_bytes());
+
+    let master_key = derive_master_key(&header, key_elements)?;
+    let payload_encrypted = header
+        .outer_cipher
+        .get_cipher(&master_key, header.outer_iv.as_ref())?
+        .encrypt(&payload)?;
+
+    let mut data = write_header(&header);
+    data.extend_from_slice(&payload_encrypted);
+
+    Ok(data)
+}
+
+// derive master key from composite key, transform_seed, transform_rounds and master_seed
+fn derive_master_key(header: &KDBX3Header, key_elements: &[SecretBytes]) -> Result<SecretBytes> {
+    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
+    let composite_key = into_secret(cryptopraphy::sha256(&key_elements)?);
+
+    // KDF is hard coded for KDBX 3
+    let transformed_key = into_secret(
+        cryptopraphy::kdf::AesKdf::new(
+            header.transform_seed.as_ref(),
+            header.transform_rounds,
+        ).transform_key(GenericArray::from_slice(&composite_key))?
+    );
+
+    Ok(into_secret(
+        cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_convert_replaces_arcfour_variant() {
+        let mut db = create(&Settings::for_kdbx3(Compression::None, OuterCipherSuite::Twofish, 20)).unwrap();
+        if let Header::KDBX3(header) = &mut db.header {
+            header.inner_cipher = InnerCipherSuite::ArcFourVariant;
+        }
+
+        convert(&mut db).unwrap();
+
+        let header = match &db.header {
+            Header::KDBX3(header) => header,
+            _ => panic!("expected a KDBX3 header"),
+        };
+
+        assert_that(&matches!(header.inner_cipher, InnerCipherSuite::Salsa20)).is_true();
+        assert_that(&matches!(header.outer_cipher, OuterCipherSuite::Twofish)).is_true();
+        assert_that(&matches!(header.compression, Compression::None)).is_true();
+        assert_that(&header.transform_rounds).is_equal_to(20);
+    }
+}
@@ -4,17 +4,27 @@ use crate::{
             Header,
             InnerHeader,
         },
+        integrity::IntegrityReport,
+        compression::Compression,
         kdbx4::{
+            BinaryAttachment,
             KDBX4Header,
             KDBX4InnerHeader
         },
         settings::Settings,
-        suites::InnerCipherSuite,
-        traits::Sizable
+        suites::{
+            InnerCipherSuite,
+            KdfLimits,
+            KdfSettings,
+            OuterCipherSuite,
+        },
+        traits::Sizable,
+        variant_dictionary::VariantDictionary,
     },
     database::{
         Database,
-        items
+        items,
+        kdb,
     },
     errors::{
         DatabaseIntegrityError,
@@ -26,14 +36,33 @@ use crate::{
                 constants,
                 header::kdbx4
             },
-            xml::parser
+            xml::{
+                parser,
+                writer,
+            },
         },
         random,
         suites::hmac_block_stream,
     },
     results::Result,
 };
-use crate::internal::cryptopraphy;
+use crate::internal::cryptopraphy::{
+    self,
+    wipe::{
+        into_secret,
+        SecretBytes,
+    },
+};
+
+use generic_array::{
+    GenericArray,
+    typenum,
+};
+use zeroize::Zeroizing;
+
+// KeePass writes the payload in blocks of 1 MiB
+const BLOCK_SIZE: usize = 1024 * 1024;
+const COMPRESSION_LEVEL: u32 = 6;
 
 // create a new database
 pub(crate) fn create(
@@ -45,36 +74,111 @@ pub(crate) fn create(
         file_minor_version: 0,
         outer_cipher: settings.outer_cipher_suite(),
         compression: settings.compression(),
-        master_seed: random::generate_random_bytes(32),
-        outer_iv: random::generate_random_bytes(32),
+        master_seed: random::generate_secret_bytes(32),
+        outer_iv: random::generate_secret_bytes(32),
         kdf: settings.kdf_settings(),
+        public_custom_data: VariantDictionary::default(),
         body_start: 0,
     };
     let mut kdbx4_inner_header = KDBX4InnerHeader {
         inner_random_stream: InnerCipherSuite::ChaCha20,
-        inner_random_stream_key: random::generate_random_bytes(32),
+        inner_random_stream_key: random::generate_secret_bytes(32),
         binaries: vec![],
         body_start: 0
     };
 
+    // the inner header is the start of the decrypted payload, its body follows right after it
     kdbx4_header.body_start = kdbx4_header.size();
-    kdbx4_inner_header.body_start = kdbx4_header.size() + kdbx4_inner_header.size();
+    kdbx4_inner_header.body_start = kdbx4_inner_header.size();
 
     Ok(
         Database {
             header: Header::KDBX4(kdbx4_header),
             inner_header: InnerHeader::KDBX4(kdbx4_inner_header),
+            meta: items::Meta::default(),
             root: items::Group::root(),
+            deleted_objects: Vec::new(),
         }
     )
 }
 
+/// Rebuilds the header of `db` for KDBX 4, keeping the cipher and compression. The key is
+/// derived with a reseeded `kdf`, or AES-KDF with the rounds of `db` without one.
+/// The attachments stay on the entries, the inner header pool is rebuilt on save.
+pub(crate) fn convert(db: &mut Database, kdf: Option<&KdfSettings>) -> Result<()> {
+    let (outer_cipher, compression, rounds) = match &mut db.header {
+        Header::KDBX4(header) => {
+            if let Some(kdf) = kdf {
+                header.kdf = reseeded(kdf);
+                header.body_start = header.size();
+            }
+            return Ok(());
+        }
+        Header::KDBX3(header) => (
+            header.outer_cipher.clone(),
+            header.compression.clone(),
+            header.transform_rounds,
+        ),
+        Header::KDB(header) => (
+            kdb::cipher_suite(header.flags)?,
+            Compression::GZip,
+            u64::from(header.transform_rounds),
+        ),
+    };
+
+    let outer_iv_length = match outer_cipher {
+        OuterCipherSuite::ChaCha20 => 12,
+        _ => 16,
+    };
+
+    let mut kdbx4_header = KDBX4Header {
+        version: constants::KDBX_MAGIC,
+        file_major_version: 4,
+        file_minor_version: 0,
+        outer_cipher,
+        compression,
+        master_seed: random::generate_secret_bytes(32),
+        outer_iv: random::generate_secret_bytes(outer_iv_length),
+        kdf: match kdf {
+            Some(kdf) => reseeded(kdf),
+            None => KdfSettings::Aes {
+                seed: random::generate_secret_bytes(32),
+                rounds,
+            },
+        },
+        public_custom_data: VariantDictionary::default(),
+        body_start: 0,
+    };
+    let mut kdbx4_inner_header = KDBX4InnerHeader {
+        inner_random_stream: InnerCipherSuite::ChaCha20,
+        inner_random_stream_key: random::generate_secret_bytes(64),
+        binaries: vec![],
+        body_start: 0,
+    };
+
+    kdbx4_header.body_start = kdbx4_header.size();
+    kdbx4_inner_header.body_start = kdbx4_inner_header.size();
+
+    db.header = Header::KDBX4(kdbx4_header);
+    db.inner_header = InnerHeader::KDBX4(kdbx4_inner_header);
+    db.meta.clear_binaries();
+
+    Ok(())
+}
+
 /// Open, decrypt and database a KeePass types from a source and key elements
-pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
+pub(crate) fn parse(
+    data: &[u8],
+    key_elements: &[SecretBytes],
+    limits: &KdfLimits,
+) -> Result<Database> {
     // database header
     let header = kdbx4::read_outer_header(data)?;
     let pos = header.body_start;
 
+    // refuse to run a key derivation that is too expensive before doing any work
+    limits.check(&header.kdf)?;
+
     // split file into segments:
     //      header_data         - The outer header data
     //      header_sha256       - A Sha256 hash of header_data (for verification of header integrity)
@@ -85,11 +189,7 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
     let header_hmac = &data[(pos + 32)..(pos + 64)];
     let hmac_block_stream = &data[(pos + 64)..];
 
-    // derive master key from composite key, transform_seed, transform_rounds and master_seed
-    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
-    let composite_key = cryptopraphy::sha256(&key_elements)?;
-    let transformed_key = header.kdf.get_kdf().transform_key(&composite_key)?;
-    let master_key = cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?;
+    let (master_key, hmac_key) = derive_keys(&header, key_elements)?;
 
     // verify header
     if header_sha256 != cryptopraphy::sha256(&[&data[0..pos]])?.as_slice() {
@@ -97,15 +197,14 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
     }
 
     // verify credentials
-    let hmac_key = cryptopraphy::sha512(&[&header.master_seed, &transformed_key, b"\x01"])?;
-    let header_hmac_key = hmac_block_stream::get_hmac_block_key(usize::max_value(), &hmac_key)?;
-    if header_hmac != cryptopraphy::hmac(&[header_data], &header_hmac_key)?.as_slice() {
+    let hmac_key = GenericArray::from_slice(&hmac_key);
+    if !header_hmac_matches(header_data, header_hmac, hmac_key)? {
         return Err(Error::IncorrectKey);
     }
 
     // read encrypted payload from hmac-verified block stream
     let payload_encrypted =
-        hmac_block_stream::read_hmac_block_stream(&hmac_block_stream, &hmac_key)?;
+        hmac_block_stream::read_hmac_block_stream(hmac_block_stream, hmac_key)?;
 
     // Decrypt and decompress encrypted payload
     let payload_compressed = header
@@ -127,13 +226,213 @@ pub(crate) fn parse(data: &[u8], key_elements: &[Vec<u8>]) -> Result<Database> {
 
     // after inner header is one XML document
     let xml = &payload[inner_header.body_start..];
-    let root = parser::parse(&xml, &mut *inner_decryptor)?;
+    let attachments: Vec<&[u8]> = inner_header.binaries.iter().map(BinaryAttachment::content).collect();
+    let (meta, root, deleted_objects) = parser::parse(xml, &mut *inner_decryptor, &attachments)?;
 
     let db = Database {
         header: Header::KDBX4(header),
         inner_header: InnerHeader::KDBX4(inner_header),
+        meta,
         root,
+        deleted_objects,
     };
 
     Ok(db)
 }
+
+/// Serializes and encrypts `db` as a KDBX 4 file. The master seed, the IV, the KDF seed and the
+/// inner stream key are fresh for every save, like KeePass does.
+pub(crate) fn dump(db: &Database, key_elements: &[SecretBytes]) -> Result<Vec<u8>> {
+    let header = match &db.header {
+        Header::KDBX4(header) => header,
+        header => return Err(Error::UnsupportedFormat { format: header.format_version() }),
+    };
+
+    let header = KDBX4Header {
+        version: header.version,
+        file_major_version: header.file_major_version,
+        file_minor_version: header.file_minor_version,
+        outer_cipher: header.outer_cipher.clone(),
+        compression: header.compression.clone(),
+        master_seed: random::generate_secret_bytes(32),
+        outer_iv: random::generate_secret_bytes(header.outer_iv.len()),
+        kdf: reseeded(&header.kdf),
+        public_custom_data: header.public_custom_data.clone(),
+        body_start: 0,
+    };
+    let mut inner_header = KDBX4InnerHeader {
+        inner_random_stream: InnerCipherSuite::ChaCha20,
+        inner_random_stream_key: random::generate_secret_bytes(64),
+        binaries: vec![],
+        body_start: 0,
+    };
+
+    let mut inner_encryptor = inner_header
+        .inner_random_stream
+        .get_cipher(&inner_header.inner_random_stream_key)?;
+    let (xml, attachments) = writer::write(
+        &db.meta,
+        &db.root,
+        &db.deleted_objects,
+        &mut *inner_encryptor,
+        writer::AttachmentPool::InnerHeader,
+    )?;
+
+    // the pool is rebuilt from the entries, so attachments no entry uses any more are dropped
+    inner_header.binaries = attachments
+        .iter()
+        .map(|content| BinaryAttachment::new(0, content))
+        .collect();
+
+    let payload = Zeroizing::new([kdbx4::write_inner_header(&inner_header), xml].concat());
+    let payload_compressed = Zeroizing::new(
+        header.compression.get_compression().compress(&payload, COMPRESSION_LEVEL)?
+    );
+
+    let (master_key, hmac_key) = derive_keys(&header, key_elements)?;
+    let hmac_key = GenericArray::from_slice(&hmac_key);
+
+    let payload_encrypted = header
+        .outer_cipher
+        .get_cipher(&master_key, header.outer_iv.as_ref())?
+        .encrypt(&payload_compressed)?;
+
+    let mut data = kdbx4::write_outer_header(&header);
+    let header_sha256 = cryptopraphy::sha256(&[&data])?;
+    let header_hmac = header_hmac(&data, hmac_key)?;
+
+    data.extend_from_slice(&header_sha256);
+    data.extend_from_slice(&header_hmac);
+    data.extend_from_slice(
+        &hmac_block_stream::write_hmac_block_stream(&payload_encrypted, BLOCK_SIZE, hmac_key)?
+    );
+
+    Ok(data)
+}
+
+// The same key derivation with a fresh seed or salt
+fn reseeded(kdf: &KdfSettings) -> KdfSettings {
+    match kdf.clone() {
+        KdfSettings::Aes { seed, rounds } => KdfSettings::Aes {
+            seed: random::generate_secret_bytes(seed.len()),
+            rounds,
+        },
+        KdfSettings::Argon2 { memory, salt, iterations, lanes, version } => KdfSettings::Argon2 {
+            memory,
+            salt: random::generate_secret_bytes(salt.len()),
+            iterations,
+            lanes,
+            version,
+        },
+    }
+}
+
+/// Checks every part of a KDBX 4 file instead of stopping at the first damage like `parse`,
+/// and recovers the XML in front of the first corrupted block
+pub(crate) fn verify(
+    data: &[u8],
+    key_elements: &[SecretBytes],
+    limits: &KdfLimits,
+) -> Result<IntegrityReport> {
+    let header = kdbx4::read_outer_header(data)?;
+    let pos = header.body_start;
+
+    limits.check(&header.kdf)?;
+
+    // without the header hash and HMAC there is nothing to check the key against
+    if data.len() < pos + 64 {
+        return Ok(IntegrityReport {
+            truncated: true,
+            ..IntegrityReport::default()
+        });
+    }
+
+    let header_data = &data[0..pos];
+    let header_sha256 = &data[pos..(pos + 32)];
+    let header_hmac = &data[(pos + 32)..(pos + 64)];
+    let hmac_block_stream = &data[(pos + 64)..];
+
+    let (master_key, hmac_key) = derive_keys(&header, key_elements)?;
+    let hmac_key = GenericArray::from_slice(&hmac_key);
+
+    let header_hash_valid = header_sha256 == cryptopraphy::sha256(&[header_data])?.as_slice();
+    let header_hmac_valid = header_hmac_matches(header_data, header_hmac, hmac_key)?;
+
+    // the header itself is fine, so it is the key that does not match
+    if header_hash_valid && !header_hmac_valid {
+        return Err(Error::IncorrectKey);
+    }
+
+    let check = hmac_block_stream::check_hmac_block_stream(hmac_block_stream, hmac_key)?;
+
+    // with a damaged header only an intact first block shows that the key is right, a wrong
+    // key or damaged seeds would make every block look corrupted
+    let key_confirmed = check.block_count > 0 && check.corrupted_blocks.first() != Some(&0);
+    if !header_hash_valid && !key_confirmed {
+        return Err(DatabaseIntegrityError::HeaderHashMismatch.into());
+    }
+    let complete = check.corrupted_blocks.is_empty() && !check.truncated;
+
+    let mut outer_cipher = header
+        .outer_cipher
+        .get_cipher(&master_key, header.outer_iv.as_ref())?;
+    let payload_compressed = if complete {
+        outer_cipher.decrypt(&check.intact_prefix)
+    } else {
+        outer_cipher.decrypt_prefix(&check.intact_prefix)
+    }.unwrap_or_default();
+    let payload = header
+        .compression
+        .get_decompression()
+        .decompress_prefix(&payload_compressed);
+
+    let recovered_xml = kdbx4::inner_header_length(&payload)
+        .map(|length| payload[length..].to_vec())
+        .unwrap_or_default();
+
+    Ok(IntegrityReport {
+        header_hash_valid,
+        header_hmac_valid,
+        block_count: check.block_count,
+        corrupted_blocks: check.corrupted_blocks,
+        truncated: check.truncated,
+        recovered_xml,
+    })
+}
+
+// The master key for the outer cipher and the base key of the header and block HMACs
+fn derive_keys(header: &KDBX4Header, key_elements: &[SecretBytes]) -> Result<(SecretBytes, SecretBytes)> {
+    // derive master key from composite key, transform_seed, transform_rounds and master_seed
+    let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
+    let composite_key = into_secret(cryptopraphy::sha256(&key_elements)?);
+    let transformed_key = into_secret(
+        header.kdf.get_kdf().transform_key(GenericArray::from_slice(&composite_key))?
+    );
+    let master_key = into_secret(
+        cryptopraphy::sha256(&[header.master_seed.as_ref(), &transformed_key])?
+    );
+    let hmac_key = into_secret(
+        cryptopraphy::sha512(&[&header.master_seed, &transformed_key, b"\x01"])?
+    );
+
+    Ok((master_key, hmac_key))
+}
+
+fn header_hmac_matches(
+    header_data: &[u8],
+    header_hmac: &[u8],
+    hmac_key: &GenericArray<u8, typenum::U64>,
+) -> Result<bool> {
+    Ok(header_hmac == self::header_hmac(header_data, hmac_key)?.as_slice())
+}
+
+fn header_hmac(
+    header_data: &[u8],
+    hmac_key: &GenericArray<u8, typenum::U64>,
+) -> Result<GenericArray<u8, typenum::U32>> {
+    let header_hmac_key = into_secret(
+        hmac_block_stream::get_hmac_block_key(usize::MAX, hmac_key)?
+    );
+
+    cryptopraphy::hmac(&[header_data], &header_hmac_key)
+}
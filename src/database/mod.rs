@@ -1,7 +1,16 @@
 use crate::{
-    api::header::{
-        Header,
-        InnerHeader
+    api::{
+        header::{
+            FormatVersion,
+            Header,
+            InnerHeader
+        },
+        integrity::IntegrityReport,
+        kdb::DroppedFeature,
+        suites::{
+            KdfLimits,
+            KdfSettings,
+        },
     },
     errors::DatabaseIntegrityError,
     internal::{
@@ -16,12 +25,32 @@ use crate::{
     },
     results::Result,
 };
-use crate::internal::cryptopraphy;
+use crate::internal::cryptopraphy::{
+    self,
+    wipe::{
+        into_secret,
+        SecretBytes,
+    },
+};
+
+use chrono::{
+    Duration,
+    Utc,
+};
 
+pub mod breaches;
+pub mod generator;
+pub mod health;
 pub mod items;
 pub mod kdb;
 pub mod kdbx3;
 pub mod kdbx4;
+pub mod quality;
+pub(crate) mod recycle_bin;
+pub mod references;
+pub mod search;
+#[cfg(test)]
+pub(crate) mod test_helpers;
 pub(crate) mod utils;
 
 /// A decrypted KeePass types
@@ -33,8 +62,15 @@ pub struct Database {
     /// Optional inner header information
     pub inner_header: InnerHeader,
 
+    /// Database settings and metadata
+    pub meta: items::Meta,
+
     /// Root node of the KeePass types
     pub root: items::Group,
+
+    /// Tombstones of the groups and entries deleted for good, so synchronizing clients do
+    /// not bring them back
+    pub deleted_objects: Vec<items::DeletedObject>,
 }
 
 impl Database {
@@ -45,19 +81,24 @@ impl Database {
         keyfile: Option<&mut dyn std::io::Read>,
     ) -> Result<Database>
     {
-        let mut key_elements: Vec<Vec<u8>> = Vec::new();
-
-        if let Some(p) = password {
-            key_elements.push(
-                cryptopraphy::sha256(&[p.as_bytes()])?
-                    .as_slice()
-                    .to_vec(),
-            );
-        }
+        Database::open_with_limits(
+            source,
+            password,
+            keyfile,
+            &KdfLimits::default(),
+        )
+    }
 
-        if let Some(f) = keyfile {
-            key_elements.push(keyfile::parse(f)?);
-        }
+    /// Like `open`, but rejects databases whose key derivation exceeds `limits`
+    /// with `Error::KdfResourceLimit` instead of running it
+    pub fn open_with_limits(
+        source: &mut dyn std::io::Read,
+        password: Option<&str>,
+        keyfile: Option<&mut dyn std::io::Read>,
+        limits: &KdfLimits,
+    ) -> Result<Database>
+    {
+        let key_elements = key_elements(password, keyfile)?;
 
         let mut data = Vec::new();
         source.read_to_end(&mut data)?;
@@ -74,11 +115,12 @@ impl Database {
             KDB_MAGIC => {
                 kdb::parse(data.as_ref(), &key_elements)
             }
-            KDBX_MAGIC if file_major_version == 3 => {
+            // 2.x and 3.0 are the pre-3.1 variants of the same format
+            KDBX_MAGIC if file_major_version == 2 || file_major_version == 3 => {
                 kdbx3::parse(data.as_ref(), &key_elements)
             }
             KDBX_MAGIC if file_major_version == 4 => {
-                kdbx4::parse(data.as_ref(), &key_elements)
+                kdbx4::parse(data.as_ref(), &key_elements, limits)
             }
             _ => Err(
                 DatabaseIntegrityError::InvalidKDBXVersion {
@@ -90,7 +132,189 @@ impl Database {
         }
     }
 
+    /// Checks a KDBX 4 file from end to end and reports every damaged part instead of failing
+    /// at the first one like `open` does. Fails with `Error::IncorrectKey` if the header is
+    /// intact but its HMAC does not match the key. If the header is damaged and the first block
+    /// does not match the key either, it fails with a header hash mismatch, as the key cannot
+    /// be told apart from the damage.
+    pub fn verify(
+        source: &mut dyn std::io::Read,
+        password: Option<&str>,
+        keyfile: Option<&mut dyn std::io::Read>,
+    ) -> Result<IntegrityReport>
+    {
+        Database::verify_with_limits(
+            source,
+            password,
+            keyfile,
+            &KdfLimits::default(),
+        )
+    }
+
+    /// Like `verify`, but rejects databases whose key derivation exceeds `limits`
+    /// with `Error::KdfResourceLimit` instead of running it
+    pub fn verify_with_limits(
+        source: &mut dyn std::io::Read,
+        password: Option<&str>,
+        keyfile: Option<&mut dyn std::io::Read>,
+        limits: &KdfLimits,
+    ) -> Result<IntegrityReport>
+    {
+        let key_elements = key_elements(password, keyfile)?;
+
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        kdbx4::verify(data.as_ref(), &key_elements, limits)
+    }
+
+    /// Changes which standard fields are protected and re-protects all entries accordingly
+    pub fn set_memory_protection(&mut self, protection: &items::MemoryProtection) {
+        self.meta.set_memory_protection(protection);
+        self.root.apply_memory_protection(protection);
+    }
+
+    /// Writes the database in the KDBX version of its header with fresh seeds, IVs and inner
+    /// stream key. KDBX 2.x/3.0 databases are written as 3.1. KDB databases fail with
+    /// `Error::UnsupportedFormat`, they are written with `save_kdb` or converted first.
+    pub fn save(
+        &self,
+        destination: &mut dyn std::io::Write,
+        password: Option<&str>,
+        keyfile: Option<&mut dyn std::io::Read>,
+    ) -> Result<()>
+    {
+        let key_elements = key_elements(password, keyfile)?;
+
+        let data = match self.header {
+            Header::KDBX3(_) => kdbx3::dump(self, &key_elements)?,
+            _ => kdbx4::dump(self, &key_elements)?,
+        };
+        destination.write_all(&data)?;
+
+        Ok(())
+    }
+
+    /// Writes the database as a KeePass 1.x file, encrypted with a fresh seed and IV.
+    /// KDB cannot store everything KDBX can, the returned list says what was left out.
+    pub fn save_kdb(
+        &self,
+        destination: &mut dyn std::io::Write,
+        password: Option<&str>,
+        keyfile: Option<&mut dyn std::io::Read>,
+    ) -> Result<Vec<DroppedFeature>>
+    {
+        let key_elements = key_elements(password, keyfile)?;
+
+        let (data, dropped) = kdb::dump(self, &key_elements)?;
+        destination.write_all(&data)?;
+
+        Ok(dropped)
+    }
+
+    /// Rebuilds the headers for another file format with fresh seeds, keys and IVs.
+    /// Ciphers and key derivation settings are carried over where the target supports them.
+    /// Converting to KDB drops the attachment pools, use `save_kdb` to learn what else is lost.
+    /// KDBX 2.x/3.0 files are rebuilt as 3.1 with Salsa20 even when staying on KDBX 3.
+    pub fn convert_to(&mut self, version: FormatVersion) -> Result<()> {
+        match version {
+            FormatVersion::KDB => kdb::convert(self),
+            FormatVersion::KDBX3 => kdbx3::convert(self),
+            FormatVersion::KDBX4 => kdbx4::convert(self, None),
+        }
+    }
+
+    /// Like `convert_to(FormatVersion::KDBX4)`, but derives the key with `kdf` instead of
+    /// AES-KDF. The seed or salt of `kdf` is replaced by a fresh one. A KDBX 4 database
+    /// keeps its headers and only switches to `kdf`.
+    pub fn convert_to_kdbx4(&mut self, kdf: &KdfSettings) -> Result<()> {
+        kdbx4::convert(self, Some(kdf))
+    }
+
+    /// Finds the entries matching `query`, see `search::Query` for the syntax. Groups with
+    /// searching disabled and the recycle bin are skipped.
+    pub fn search(&self, query: &str) -> Result<Vec<search::SearchResult<'_>>> {
+        let query = search::Query::parse(query)?;
+
+        Ok(search::search(&self.root, &self.meta, &query, Utc::now()))
+    }
+
+    /// Rates the password of every entry outside the recycle bin, weakest first, for sweeps
+    /// over a whole database. Entries without a password are left out.
+    pub fn password_quality_report(&self) -> Vec<quality::QualityResult<'_>> {
+        quality::report(&self.root, &self.meta)
+    }
+
+    /// Finds reused passwords, stale and expired entries and entries without a username or
+    /// URL, leaving out the recycle bin
+    pub fn health_report(&self, policy: &health::HealthPolicy) -> Result<health::HealthReport<'_>> {
+        health::report(&self.root, &self.meta, policy, Utc::now())
+    }
+
+    /// Looks up every entry password in a local Have I Been Pwned list, without any network
+    /// access. Returns the breached entries outside the recycle bin, the most breached first.
+    pub fn breached_passwords<R: std::io::Read + std::io::Seek>(
+        &self,
+        list: &mut breaches::BreachList<R>,
+    ) -> Result<Vec<breaches::BreachedEntry<'_>>> {
+        breaches::check(&self.root, &self.meta, list)
+    }
+
+    /// Returns the field `key` of `entry` with its `{REF:...}` placeholders replaced by the
+    /// fields of the entries they point to, see `references::reference_to` to create one.
+    /// Entries in the recycle bin are no reference targets.
+    pub fn resolve_field<'a>(
+        &self,
+        entry: &'a items::Entry,
+        key: &str,
+    ) -> Result<Option<items::ValueGuard<'a>>> {
+        references::resolve_field(&self.root, &self.meta, entry, key)
+    }
+
+    /// Moves the entry at `path`, its group names followed by its own, to the recycle bin,
+    /// which is created on demand. Entries already in the recycle bin, or all entries when it is
+    /// disabled, are deleted for good and leave a tombstone. `false` if there is no such entry.
+    pub fn delete_entry(&mut self, path: &[&str]) -> bool {
+        recycle_bin::delete_entry(self, path)
+    }
+
+    /// Moves the group at `path` to the recycle bin like `delete_entry`. Deleting the recycle
+    /// bin itself, or a group holding it, deletes for good with tombstones for everything inside.
+    pub fn delete_group(&mut self, path: &[&str]) -> bool {
+        recycle_bin::delete_group(self, path)
+    }
+
+    /// Deletes everything in the recycle bin for good, leaving tombstones
+    pub fn empty_recycle_bin(&mut self) {
+        recycle_bin::empty(self)
+    }
+
+    /// Drops the tombstones in `deleted_objects` older than `max_age`. Clients synchronizing
+    /// less often than that may bring the items back.
+    pub fn prune_deleted_objects(&mut self, max_age: Duration) {
+        recycle_bin::prune(&mut self.deleted_objects, Utc::now() - max_age)
+    }
+
     pub fn close(&self) {
         // nothing yet
     }
 }
+
+fn key_elements(
+    password: Option<&str>,
+    keyfile: Option<&mut dyn std::io::Read>,
+) -> Result<Vec<SecretBytes>> {
+    let mut key_elements: Vec<SecretBytes> = Vec::new();
+
+    if let Some(p) = password {
+        key_elements.push(
+            into_secret(cryptopraphy::sha256(&[p.as_bytes()])?),
+        );
+    }
+
+    if let Some(f) = keyfile {
+        key_elements.push(keyfile::parse(f)?);
+    }
+
+    Ok(key_elements)
+}
@@ -0,0 +1,426 @@
+use super::{
+    cheapest_split,
+    PatternKind,
+};
+use crate::wordlists::BIP39_ENGLISH;
+
+use chrono::{
+    Datelike,
+    Utc,
+};
+use zeroize::Zeroizing;
+
+use std::{
+    collections::HashMap,
+    sync::OnceLock,
+};
+
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+const MIN_WORD_LENGTH: usize = 3;
+const MAX_WORD_LENGTH: usize = 16;
+// l33t readings tried per substring, each ambiguous character doubles them
+const MAX_SUBSTITUTIONS: usize = 16;
+const MAX_REPEATED_BLOCK: usize = 16;
+
+// Characters standing in for letters, `1` and `|` can be an `i` or an `l`
+const L33T: &[(char, &str)] = &[
+    ('4', "a"), ('@', "a"), ('8', "b"), ('(', "c"), ('{', "c"), ('[', "c"), ('<', "c"),
+    ('3', "e"), ('6', "g"), ('9', "g"), ('1', "il"), ('!', "i"), ('|', "il"), ('0', "o"),
+    ('$', "s"), ('5', "s"), ('7', "t"), ('+', "t"), ('%', "x"), ('2', "z"),
+];
+
+// The rows of a US QWERTY keyboard, unshifted and shifted, and the column of their first key.
+// The rows are staggered so the keys above a key are in its column and the next one.
+const KEYBOARD_ROWS: &[(&str, &str, i32)] = &[
+    ("`1234567890-=", "~!@#$%^&*()_+", 0),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|", 1),
+    ("asdfghjkl;'", "ASDFGHJKL:\"", 1),
+    ("zxcvbnm,./", "ZXCVBNM<>?", 1),
+];
+const KEYBOARD_KEYS: f64 = 47.0;
+const KEYBOARD_DIRECTIONS: &[(i32, i32)] = &[(0, -1), (0, 1), (-1, 0), (-1, 1), (1, -1), (1, 0)];
+
+const DATE_SEPARATORS: &str = " -/._";
+const DAYS_PER_YEAR: f64 = 365.0;
+const MIN_YEAR_SPACE: i32 = 20;
+
+/// A guessable run of characters `start..end` and the bits it takes to guess it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub kind: PatternKind,
+    pub bits: f64,
+}
+
+/// All the patterns in `chars`, overlapping ones included
+pub(super) fn find(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    dictionary(chars, &mut matches);
+    keyboard_walks(chars, &mut matches);
+    sequences(chars, &mut matches);
+    repeats(chars, &mut matches);
+    dates(chars, &mut matches);
+
+    matches
+}
+
+struct Dictionaries {
+    common: HashMap<&'static str, usize>,
+    words: HashMap<&'static str, usize>,
+}
+
+fn dictionaries() -> &'static Dictionaries {
+    static DICTIONARIES: OnceLock<Dictionaries> = OnceLock::new();
+
+    DICTIONARIES.get_or_init(|| {
+        let ranked = |text: &'static str| text.lines()
+            .enumerate()
+            .map(|(rank, word)| (word, rank + 1))
+            .collect();
+
+        Dictionaries {
+            common: ranked(COMMON_PASSWORDS),
+            words: ranked(BIP39_ENGLISH),
+        }
+    })
+}
+
+// The common passwords are ordered by use, the words are not
+fn lookup(word: &str) -> Option<(PatternKind, f64)> {
+    let dictionaries = dictionaries();
+
+    let common = dictionaries.common.get(word)
+        .map(|rank| (PatternKind::CommonPassword, (*rank as f64 + 1.0).log2()));
+    let english = dictionaries.words.get(word)
+        .map(|_| (PatternKind::DictionaryWord, (dictionaries.words.len() as f64).log2()));
+
+    common.into_iter().chain(english).min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+fn dictionary(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for end in start + MIN_WORD_LENGTH..=chars.len().min(start + MAX_WORD_LENGTH) {
+            let slice = &chars[start..end];
+            let lower = Zeroizing::new(slice.iter().flat_map(|c| c.to_lowercase()).collect::<String>());
+            let case = case_bits(slice);
+
+            let mut found = lookup(&lower).map(|(kind, bits)| (kind, bits + case));
+
+            let reversed = Zeroizing::new(lower.chars().rev().collect::<String>());
+            if let Some((kind, bits)) = lookup(&reversed) {
+                found = cheaper(found, (kind, bits + case + 1.0));
+            }
+
+            for (reading, substitutions) in l33t_readings(&lower) {
+                if let Some((kind, bits)) = lookup(&reading) {
+                    found = cheaper(found, (kind, bits + case + substitutions as f64));
+                }
+            }
+
+            if let Some((kind, bits)) = found {
+                matches.push(Match { start, end, kind, bits: bits.max(1.0) });
+            }
+        }
+    }
+}
+
+fn cheaper(found: Option<(PatternKind, f64)>, other: (PatternKind, f64)) -> Option<(PatternKind, f64)> {
+    match found {
+        Some(found) if found.1 <= other.1 => Some(found),
+        _ => Some(other),
+    }
+}
+
+// Upper case letters cost a bit when only the first or all letters are, otherwise every
+// way to pick that many of the letters
+fn case_bits(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 0.0;
+    }
+    if lower == 0 || (upper == 1 && word[0].is_uppercase()) {
+        return 1.0;
+    }
+
+    let letters = upper + lower;
+    let variations: f64 = (1..=upper.min(lower)).map(|i| binomial(letters, i)).sum();
+    variations.log2()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |result, i| result * (n - i) as f64 / (i + 1) as f64)
+}
+
+// The words `text` reads as when its l33t characters are taken as letters, with the number
+// of characters replaced. Text made of l33t characters only is not a word.
+fn l33t_readings(text: &str) -> Vec<(Zeroizing<String>, usize)> {
+    let substitution = |c: char| L33T.iter().find(|(l33t, _)| *l33t == c).map(|(_, letters)| *letters);
+
+    let substitutions = text.chars().filter(|c| substitution(*c).is_some()).count();
+    if substitutions == 0 || substitutions == text.chars().count() {
+        return Vec::new();
+    }
+
+    let mut readings = vec![Zeroizing::new(String::new())];
+    for c in text.chars() {
+        match substitution(c) {
+            Some(letters) => {
+                readings = readings.iter()
+                    .flat_map(|reading| letters.chars().map(move |letter| {
+                        let mut next = Zeroizing::new(reading.to_string());
+                        next.push(letter);
+                        next
+                    }))
+                    .take(MAX_SUBSTITUTIONS)
+                    .collect();
+            }
+            None => readings.iter_mut().for_each(|reading| reading.push(c)),
+        }
+    }
+
+    readings.into_iter().map(|reading| (reading, substitutions)).collect()
+}
+
+fn key_position(c: char) -> Option<(i32, i32, bool)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, (plain, shifted, first))| {
+        plain.chars().position(|key| key == c).map(|column| (row as i32, first + column as i32, false))
+            .or_else(|| shifted.chars().position(|key| key == c).map(|column| (row as i32, first + column as i32, true)))
+    })
+}
+
+// The direction from `a` to `b` if they are neighbouring keys
+fn key_direction(a: char, b: char) -> Option<usize> {
+    let (row_a, column_a, _) = key_position(a)?;
+    let (row_b, column_b, _) = key_position(b)?;
+
+    KEYBOARD_DIRECTIONS.iter().position(|(rows, columns)| row_a + rows == row_b && column_a + columns == column_b)
+}
+
+fn keyboard_walks(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        let mut turns = 0;
+        let mut direction = None;
+
+        for end in start + 1..chars.len() {
+            let next = match key_direction(chars[end - 1], chars[end]) {
+                Some(next) => next,
+                None => break,
+            };
+            if direction.is_some_and(|direction| direction != next) {
+                turns += 1;
+            }
+            direction = Some(next);
+
+            let length = end + 1 - start;
+            if length >= MIN_WORD_LENGTH {
+                let shifted = chars[start..=end].iter().any(|c| key_position(*c).is_some_and(|key| key.2));
+                let bits = (KEYBOARD_KEYS * length as f64).log2()
+                    + f64::from(turns) * (KEYBOARD_DIRECTIONS.len() as f64).log2()
+                    + if shifted { 1.0 } else { 0.0 };
+
+                matches.push(Match { start, end: end + 1, kind: PatternKind::KeyboardWalk, bits });
+            }
+        }
+    }
+}
+
+// Runs of letters or digits that go up or down by one
+fn sequences(chars: &[char], matches: &mut Vec<Match>) {
+    let step = |a: char, b: char| -> Option<i32> {
+        let same_class = (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+            || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+            || (a.is_ascii_digit() && b.is_ascii_digit());
+        let delta = b as i32 - a as i32;
+
+        Some(delta).filter(|delta| same_class && delta.abs() == 1)
+    };
+
+    for start in 0..chars.len() {
+        let mut delta = None;
+
+        for end in start + 1..chars.len() {
+            match step(chars[end - 1], chars[end]) {
+                Some(next) if delta.is_none() || delta == Some(next) => delta = Some(next),
+                _ => break,
+            }
+
+            let length = end + 1 - start;
+            if length >= MIN_WORD_LENGTH {
+                let first = chars[start];
+                let starts = if "aAzZ019".contains(first) {
+                    4.0
+                } else if first.is_ascii_digit() {
+                    10.0
+                } else {
+                    26.0
+                };
+                let descending = if delta == Some(-1) { 1.0 } else { 0.0 };
+                let bits = (starts * length as f64).log2() + descending;
+
+                matches.push(Match { start, end: end + 1, kind: PatternKind::Sequence, bits });
+            }
+        }
+    }
+}
+
+// A block repeated costs the block and the number of repeats
+fn repeats(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for block in 1..=MAX_REPEATED_BLOCK.min((chars.len() - start) / 2) {
+            let pattern = &chars[start..start + block];
+            let count = chars[start..].chunks_exact(block).take_while(|chunk| *chunk == pattern).count();
+
+            if count >= 2 && count * block >= MIN_WORD_LENGTH {
+                let bits = cheapest_split(pattern).0 + (count as f64).log2();
+
+                matches.push(Match { start, end: start + count * block, kind: PatternKind::Repeat, bits });
+                // longer blocks made of the same one only repeat it fewer times
+                break;
+            }
+        }
+    }
+}
+
+fn dates(chars: &[char], matches: &mut Vec<Match>) {
+    let this_year = Utc::now().year();
+
+    for start in 0..chars.len() {
+        for end in start + 4..=chars.len().min(start + 10) {
+            let text = Zeroizing::new(chars[start..end].iter().collect::<String>());
+            let separated = text.chars().any(|c| DATE_SEPARATORS.contains(c));
+
+            if let Some(year) = parse_date(&text) {
+                let year_space = f64::from((year - this_year).abs().max(MIN_YEAR_SPACE));
+                let days = if end - start == 4 && !separated { 1.0 } else { DAYS_PER_YEAR };
+                let bits = (days * year_space).log2() + if separated { 2.0 } else { 0.0 };
+
+                matches.push(Match { start, end, kind: PatternKind::Date, bits });
+            }
+        }
+    }
+}
+
+// The year of a year on its own, or of a day, month and year in any common order
+fn parse_date(text: &str) -> Option<i32> {
+    let groups: Vec<&str> = match text.chars().find(|c| DATE_SEPARATORS.contains(*c)) {
+        Some(separator) => text.split(separator).collect(),
+        None => Vec::new(),
+    };
+
+    if !text.chars().all(|c| c.is_ascii_digit()) && groups.len() != 3 {
+        return None;
+    }
+
+    if groups.len() == 3 {
+        if groups.iter().any(|group| group.is_empty() || !group.chars().all(|c| c.is_ascii_digit())) {
+            return None;
+        }
+        return valid_date(groups[0], groups[1], groups[2]);
+    }
+
+    if text.len() == 4 {
+        let year: i32 = text.parse().ok()?;
+        if (1900..=2050).contains(&year) {
+            return Some(year);
+        }
+    }
+
+    // digits only, two for the day and month and two or four for the year
+    match text.len() {
+        6 => valid_date(&text[..2], &text[2..4], &text[4..]),
+        8 => valid_date(&text[..2], &text[2..4], &text[4..])
+            .or_else(|| valid_date(&text[..4], &text[4..6], &text[6..])),
+        _ => None,
+    }
+}
+
+fn valid_date(a: &str, b: &str, c: &str) -> Option<i32> {
+    let year = |text: &str| -> Option<i32> {
+        let value: i32 = text.parse().ok()?;
+        match text.len() {
+            2 if value < 50 => Some(2000 + value),
+            2 => Some(1900 + value),
+            4 if (1900..=2050).contains(&value) => Some(value),
+            _ => None,
+        }
+    };
+    let day_month = |day: &str, month: &str| -> bool {
+        let short = day.len() <= 2 && month.len() <= 2;
+        let day: u32 = day.parse().unwrap_or(0);
+        let month: u32 = month.parse().unwrap_or(0);
+        short && (1..=31).contains(&day) && (1..=12).contains(&month)
+    };
+
+    // day month year, month day year, year month day
+    if day_month(a, b) || day_month(b, a) {
+        if let Some(year) = year(c) {
+            return Some(year);
+        }
+    }
+    if day_month(c, b) {
+        return year(a);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn found(password: &str, kind: PatternKind) -> Vec<(usize, usize)> {
+        let chars: Vec<char> = password.chars().collect();
+
+        find(&chars).iter()
+            .filter(|m| m.kind == kind)
+            .map(|m| (m.start, m.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_dictionary_reversed_and_l33t() {
+        assert_that(&found("drowssap", PatternKind::CommonPassword)).contains((0, 8));
+        assert_that(&found("x0xyg3n!", PatternKind::DictionaryWord)).contains((1, 7));
+        assert_that(&l33t_readings("l1ke").len()).is_equal_to(2);
+    }
+
+    #[test]
+    fn test_case_bits() {
+        assert_that(&case_bits(&['w', 'o', 'r', 'd'])).is_equal_to(0.0);
+        assert_that(&case_bits(&['W', 'o', 'r', 'd'])).is_equal_to(1.0);
+        assert_that(&case_bits(&['W', 'O', 'R', 'D'])).is_equal_to(1.0);
+        assert_that(&case_bits(&['w', 'O', 'r', 'd'])).is_equal_to(2.0);
+    }
+
+    #[test]
+    fn test_keyboard_walks() {
+        assert_that(&found("zaq1", PatternKind::KeyboardWalk)).contains((0, 4));
+        assert_that(&found("!QAZ", PatternKind::KeyboardWalk)).contains((0, 4));
+        assert_that(&found("qdz", PatternKind::KeyboardWalk)).is_empty();
+    }
+
+    #[test]
+    fn test_sequences_and_repeats() {
+        assert_that(&found("x9876", PatternKind::Sequence)).contains((1, 5));
+        assert_that(&found("acegi", PatternKind::Sequence)).is_empty();
+        assert_that(&found("ab!ab!ab!", PatternKind::Repeat)).contains((0, 9));
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_that(&parse_date("1987")).is_equal_to(Some(1987));
+        assert_that(&parse_date("241299")).is_equal_to(Some(1999));
+        assert_that(&parse_date("2020-02-29")).is_equal_to(Some(2020));
+        assert_that(&parse_date("12/31/08")).is_equal_to(Some(2008));
+        assert_that(&parse_date("1234")).is_none();
+        assert_that(&parse_date("19871224")).is_equal_to(Some(1987));
+        assert_that(&parse_date("99.99.99")).is_none();
+        assert_that(&parse_date("24-12/99")).is_none();
+    }
+}
@@ -0,0 +1,237 @@
+use super::{
+    items::{
+        Entry,
+        Group,
+        Meta,
+    },
+    recycle_bin,
+};
+
+use zeroize::Zeroizing;
+
+mod matchers;
+
+use matchers::Match;
+
+// KeePassXC's health check thresholds, in bits
+const POOR_BITS: f64 = 40.0;
+const WEAK_BITS: f64 = 75.0;
+const GOOD_BITS: f64 = 100.0;
+
+/// How KeePassXC would rate a password, from worst to best
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum QualityRating {
+    /// Empty, or guessed at once
+    Bad,
+    Poor,
+    Weak,
+    Good,
+    Excellent,
+}
+
+impl QualityRating {
+    fn from_bits(bits: f64) -> Self {
+        if bits <= 0.0 {
+            QualityRating::Bad
+        } else if bits < POOR_BITS {
+            QualityRating::Poor
+        } else if bits < WEAK_BITS {
+            QualityRating::Weak
+        } else if bits < GOOD_BITS {
+            QualityRating::Good
+        } else {
+            QualityRating::Excellent
+        }
+    }
+}
+
+/// The kinds of guessable structure the estimator looks for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PatternKind {
+    /// One of the most used passwords
+    CommonPassword,
+    /// An English word, possibly reversed or with l33t substitutions
+    DictionaryWord,
+    /// Neighbouring keys on a QWERTY keyboard, like `qwerty` or `zaq1`
+    KeyboardWalk,
+    /// Evenly spaced characters, like `abcd` or `9876`
+    Sequence,
+    /// A character or a block repeated, like `aaa` or `abcabc`
+    Repeat,
+    /// A year or a day, month and year, like `1987` or `24.12.99`
+    Date,
+}
+
+/// A guessable part of a password, in characters so it does not reveal the password
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Weakness {
+    pub kind: PatternKind,
+    pub start: usize,
+    pub length: usize,
+}
+
+/// The strength estimate of a password
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordQuality {
+    /// The estimated entropy, the base 2 logarithm of the guesses an attacker needs
+    pub bits: f64,
+    pub rating: QualityRating,
+    /// The patterns the estimate is based on, in the order they appear
+    pub weaknesses: Vec<Weakness>,
+}
+
+/// An entry in `Database::password_quality_report`
+#[derive(Debug)]
+pub struct QualityResult<'a> {
+    /// Names of the groups between the root group and the entry
+    pub path: Vec<&'a str>,
+    pub entry: &'a Entry,
+    pub quality: PasswordQuality,
+}
+
+/// Estimates how hard `password` is to guess. The password is split into the dictionary words,
+/// keyboard walks, sequences, repeats and dates it is made of, and into characters that fit no
+/// pattern. The bits are those of the cheapest split, where a pattern costs the guesses needed
+/// to find it among its kind and any other character costs a guess over its character classes.
+pub fn estimate(password: &str) -> PasswordQuality {
+    let chars = Zeroizing::new(password.chars().collect::<Vec<_>>());
+    let (bits, matches) = cheapest_split(&chars);
+
+    PasswordQuality {
+        bits,
+        rating: QualityRating::from_bits(bits),
+        weaknesses: matches.iter()
+            .map(|m| Weakness { kind: m.kind, start: m.start, length: m.end - m.start })
+            .collect(),
+    }
+}
+
+// Dynamic programming over the prefixes, `best[i]` is the cheapest way to guess the first i
+// characters and the match that ends it, if any
+fn cheapest_split(chars: &[char]) -> (f64, Vec<Match>) {
+    let guess_bits = (character_pool(chars) as f64).log2();
+    let mut matches = matchers::find(chars);
+    matches.sort_by_key(|m| m.end);
+
+    let mut best: Vec<(f64, Option<usize>)> = vec![(0.0, None); chars.len() + 1];
+    let mut next = 0;
+    for end in 1..=chars.len() {
+        best[end] = (best[end - 1].0 + guess_bits, None);
+
+        while next < matches.len() && matches[next].end == end {
+            let bits = best[matches[next].start].0 + matches[next].bits;
+            if bits < best[end].0 {
+                best[end] = (bits, Some(next));
+            }
+            next += 1;
+        }
+    }
+
+    let mut used = Vec::new();
+    let mut end = chars.len();
+    while end > 0 {
+        match best[end].1 {
+            Some(index) => {
+                end = matches[index].start;
+                used.push(matches[index]);
+            }
+            None => end -= 1,
+        }
+    }
+    used.reverse();
+
+    (best[chars.len()].0, used)
+}
+
+// The number of characters to pick from when guessing one, from the classes in use
+fn character_pool(chars: &[char]) -> u32 {
+    let mut pool = 0;
+
+    if chars.iter().any(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if chars.iter().any(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if chars.iter().any(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if chars.iter().any(|c| c.is_ascii() && !c.is_ascii_alphanumeric()) {
+        pool += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+
+    pool.max(1)
+}
+
+/// The quality of every entry password outside the recycle bin, weakest first
+pub(crate) fn report<'a>(root: &'a Group, meta: &Meta) -> Vec<QualityResult<'a>> {
+    let mut results: Vec<_> = recycle_bin::entries_outside_recycle_bin(root, meta)
+        .filter_map(|found| {
+            let quality = found.entry.password_quality()?;
+            Some(QualityResult { path: found.path, entry: found.entry, quality })
+        })
+        .collect();
+
+    results.sort_by(|a, b| a.quality.bits.total_cmp(&b.quality.bits));
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn kinds(password: &str) -> Vec<PatternKind> {
+        estimate(password).weaknesses.iter().map(|weakness| weakness.kind).collect()
+    }
+
+    #[test]
+    fn test_empty_password_is_bad() {
+        let quality = estimate("");
+
+        assert_that(&quality.bits).is_equal_to(0.0);
+        assert_that(&quality.rating).is_equal_to(QualityRating::Bad);
+    }
+
+    #[test]
+    fn test_common_passwords_are_poor() {
+        for password in &["password", "123456", "qwerty", "P@ssw0rd", "iloveyou1", "hunter2"] {
+            assert_that(&estimate(password).rating).is_equal_to(QualityRating::Poor);
+        }
+        assert_that(&kinds("P@ssw0rd")).is_equal_to(vec![PatternKind::CommonPassword]);
+    }
+
+    #[test]
+    fn test_patterns_lower_the_estimate() {
+        assert_that(&kinds("Summer1987")).is_equal_to(vec![PatternKind::CommonPassword, PatternKind::Date]);
+        assert_that(&kinds("qwertasdfg")).is_equal_to(vec![PatternKind::KeyboardWalk, PatternKind::KeyboardWalk]);
+        assert_that(&kinds("lmnopq")).is_equal_to(vec![PatternKind::Sequence]);
+        assert_that(&kinds("zzzzzzzz")).is_equal_to(vec![PatternKind::Repeat]);
+        assert_that(&kinds("24.12.1999")).is_equal_to(vec![PatternKind::Date]);
+
+        for password in &["Summer1987", "qwertasdfg", "lmnopq", "zzzzzzzz", "24.12.1999"] {
+            assert_that(&(estimate(password).bits < 30.0)).is_true();
+        }
+    }
+
+    #[test]
+    fn test_random_passwords_rate_by_length() {
+        assert_that(&estimate("x7#Kq9").rating).is_equal_to(QualityRating::Poor);
+        assert_that(&estimate("7Gq!x9#Lr2$").rating).is_equal_to(QualityRating::Weak);
+        assert_that(&estimate("7Gq!x9#Lr2$wZp").rating).is_equal_to(QualityRating::Good);
+        assert_that(&estimate("7Gq!x9#Lr2$wZp4&vB8^").rating).is_equal_to(QualityRating::Excellent);
+    }
+
+    #[test]
+    fn test_passphrases_count_words() {
+        let quality = estimate("canyon-oxygen-rubber-ticket-wisdom-garlic-yellow");
+
+        assert_that(&quality.weaknesses.iter().all(|w| w.kind == PatternKind::DictionaryWord)).is_true();
+        assert_that(&quality.weaknesses).has_length(7);
+        assert_that(&quality.rating).is_equal_to(QualityRating::Excellent);
+    }
+}
@@ -0,0 +1,392 @@
+use super::{
+    items::{
+        DeletedObject,
+        Entry,
+        Group,
+        Meta,
+        TimestampValue,
+        UuidValue,
+    },
+    Database,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+use std::collections::HashMap;
+
+const RECYCLE_BIN_NAME: &str = "Recycle Bin";
+// the trash can of the KeePass standard icons
+const RECYCLE_BIN_ICON: u32 = 43;
+
+pub(crate) fn delete_entry(db: &mut Database, path: &[&str]) -> bool {
+    let (name, parent) = match path.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let bin = bin_path(db);
+    let entry = match group_mut(&mut db.root, parent).and_then(|group| group.entries.remove(*name)) {
+        Some(entry) => entry,
+        None => return false,
+    };
+
+    let permanent = !db.meta.recycle_bin_enabled()
+        || bin.as_deref().is_some_and(|bin| parent.starts_with(&as_strs(bin)));
+    if permanent {
+        db.deleted_objects.push(tombstone(&entry.uuid()));
+        return true;
+    }
+
+    let mut entry = entry;
+    let mut times = entry.times().unwrap_or_default();
+    times.location_changed = Some(TimestampValue::now());
+    entry.set_times(&times);
+
+    let bin = recycle_bin(db);
+    let name = unique_name(&bin.entries, name);
+    bin.entries.insert(name, entry);
+
+    true
+}
+
+pub(crate) fn delete_group(db: &mut Database, path: &[&str]) -> bool {
+    let (name, parent) = match path.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let bin = bin_path(db);
+    let mut group = match group_mut(&mut db.root, parent).and_then(|group| group.child_groups.remove(*name)) {
+        Some(group) => group,
+        None => return false,
+    };
+
+    // covers the recycle bin itself and any group holding it
+    let permanent = !db.meta.recycle_bin_enabled()
+        || bin.as_deref().is_some_and(|bin| parent.starts_with(&as_strs(bin)) || as_strs(bin).starts_with(path));
+    if permanent {
+        bury(&group, &mut db.deleted_objects);
+        return true;
+    }
+
+    group.times.location_changed = Some(TimestampValue::now());
+
+    let bin = recycle_bin(db);
+    let name = unique_name(&bin.child_groups, name);
+    bin.child_groups.insert(name, group);
+
+    true
+}
+
+pub(crate) fn empty(db: &mut Database) {
+    let bin = match bin_path(db) {
+        Some(bin) => bin,
+        None => return,
+    };
+
+    if let Some(group) = group_mut(&mut db.root, &as_strs(&bin)) {
+        let entries = std::mem::take(&mut group.entries);
+        let child_groups = std::mem::take(&mut group.child_groups);
+
+        for entry in entries.values() {
+            db.deleted_objects.push(tombstone(&entry.uuid()));
+        }
+        for child_group in child_groups.values() {
+            bury(child_group, &mut db.deleted_objects);
+        }
+    }
+}
+
+pub(crate) fn prune(deleted_objects: &mut Vec<DeletedObject>, deleted_before: DateTime<Utc>) {
+    deleted_objects.retain(|deleted_object| {
+        deleted_object.deletion_time()
+            .as_datetime()
+            .map_or(true, |time| time >= deleted_before)
+    });
+}
+
+/// An entry found by `entries_outside_recycle_bin`
+pub(crate) struct TreeEntry<'a> {
+    /// The names of the groups from the root down to the entry
+    pub path: Vec<&'a str>,
+    /// The groups from the root down to the entry's own one, the root included
+    pub groups: Vec<&'a Group>,
+    pub entry: &'a Entry,
+}
+
+/// The entries under `root` in tree order, groups and entries by name, leaving out the
+/// recycle bin `meta` names if it is enabled
+pub(crate) fn entries_outside_recycle_bin<'a>(
+    root: &'a Group,
+    meta: &Meta,
+) -> impl Iterator<Item = TreeEntry<'a>> {
+    let recycle_bin = if meta.recycle_bin_enabled() { meta.recycle_bin_uuid() } else { None };
+
+    let mut entries = Vec::new();
+    collect(root, recycle_bin.as_ref(), &mut Vec::new(), &mut Vec::new(), &mut entries);
+
+    entries.into_iter()
+}
+
+fn collect<'a>(
+    group: &'a Group,
+    recycle_bin: Option<&UuidValue>,
+    path: &mut Vec<&'a str>,
+    groups: &mut Vec<&'a Group>,
+    entries: &mut Vec<TreeEntry<'a>>,
+) {
+    if recycle_bin == Some(&group.uuid) {
+        return;
+    }
+
+    groups.push(group);
+
+    let mut own: Vec<_> = group.entries.iter().collect();
+    own.sort_by(|a, b| a.0.cmp(b.0));
+    entries.extend(
+        own.into_iter()
+            .map(|(_, entry)| TreeEntry { path: path.clone(), groups: groups.clone(), entry })
+    );
+
+    let mut child_groups: Vec<_> = group.child_groups.iter().collect();
+    child_groups.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, child_group) in child_groups {
+        path.push(name);
+        collect(child_group, recycle_bin, path, groups, entries);
+        path.pop();
+    }
+
+    groups.pop();
+}
+
+fn tombstone(uuid: &UuidValue) -> DeletedObject {
+    DeletedObject::new(uuid, &TimestampValue::now())
+}
+
+// Tombstones for `group` and everything in it
+fn bury(group: &Group, deleted_objects: &mut Vec<DeletedObject>) {
+    deleted_objects.push(tombstone(&group.uuid));
+    deleted_objects.extend(group.entries.values().map(|entry: &Entry| tombstone(&entry.uuid())));
+
+    for child_group in group.child_groups.values() {
+        bury(child_group, deleted_objects);
+    }
+}
+
+fn as_strs(path: &[String]) -> Vec<&str> {
+    path.iter().map(String::as_str).collect()
+}
+
+fn group_mut<'g>(group: &'g mut Group, path: &[&str]) -> Option<&'g mut Group> {
+    match path.split_first() {
+        Some((name, rest)) => group_mut(group.child_groups.get_mut(*name)?, rest),
+        None => Some(group),
+    }
+}
+
+// The names leading to the group `Meta::recycle_bin_uuid` points to, if it exists
+fn bin_path(db: &Database) -> Option<Vec<String>> {
+    let uuid = db.meta.recycle_bin_uuid()?;
+
+    find_group(&db.root, &uuid)
+}
+
+fn find_group(group: &Group, uuid: &UuidValue) -> Option<Vec<String>> {
+    if group.uuid == *uuid {
+        return Some(Vec::new());
+    }
+
+    group.child_groups.iter().find_map(|(name, child_group)| {
+        find_group(child_group, uuid).map(|mut path| {
+            path.insert(0, name.to_owned());
+            path
+        })
+    })
+}
+
+// The recycle bin, created in the root group like KeePass does if it is missing
+fn recycle_bin(db: &mut Database) -> &mut Group {
+    let path = match bin_path(db) {
+        Some(path) => path,
+        None => {
+            let mut bin = Group::new(RECYCLE_BIN_NAME, &HashMap::new(), &HashMap::new());
+            bin.icon_id = RECYCLE_BIN_ICON;
+            bin.enable_auto_type = false;
+            bin.enable_searching = Some(false);
+
+            db.meta.set_recycle_bin_uuid(&bin.uuid);
+
+            let name = unique_name(&db.root.child_groups, RECYCLE_BIN_NAME);
+            db.root.child_groups.insert(name.clone(), bin);
+            vec![name]
+        }
+    };
+
+    group_mut(&mut db.root, &as_strs(&path)).expect("the recycle bin was just found or created")
+}
+
+// `name`, or `name (2)`, `name (3)`... if a sibling already has it
+fn unique_name<T>(siblings: &HashMap<String, T>, name: &str) -> String {
+    if !siblings.contains_key(name) {
+        return name.to_owned();
+    }
+
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !siblings.contains_key(candidate))
+        .expect("a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{
+            compression::Compression,
+            settings::Settings,
+            suites::OuterCipherSuite,
+        },
+        database::{
+            kdbx3,
+            test_helpers::entry,
+        },
+    };
+    use chrono::Duration;
+    use spectral::prelude::*;
+
+    // root: mail / Work: vpn, Servers: ssh
+    fn sample() -> Database {
+        let mut db = kdbx3::create(&Settings::for_kdbx3(Compression::None, OuterCipherSuite::AES256, 20)).unwrap();
+        db.meta.set_recycle_bin_enabled(true);
+
+        let mut servers = Group::new("Servers", &HashMap::new(), &HashMap::new());
+        servers.add_entry("ssh", &entry("ssh", "", "", ""));
+        let mut work = Group::new("Work", &HashMap::new(), &HashMap::new());
+        work.add_entry("vpn", &entry("vpn", "", "", ""));
+        work.add_group("Servers", &servers);
+
+        db.root.add_entry("mail", &entry("mail", "", "", ""));
+        db.root.add_group("Work", &work);
+        db
+    }
+
+    fn bin(db: &Database) -> &Group {
+        &db.root.child_groups[RECYCLE_BIN_NAME]
+    }
+
+    #[test]
+    fn test_delete_entry_creates_and_uses_recycle_bin() {
+        let mut db = sample();
+
+        assert_that(&db.delete_entry(&["mail"])).is_true();
+        assert_that(&db.delete_entry(&["Work", "vpn"])).is_true();
+
+        let bin = bin(&db);
+        assert_that(&db.meta.recycle_bin_uuid()).is_equal_to(Some(bin.uuid.clone()));
+        assert_that(&bin.icon_id).is_equal_to(RECYCLE_BIN_ICON);
+        assert_that(&bin.searching_enabled(true)).is_false();
+        assert_that(&bin.entries.contains_key("mail")).is_true();
+        assert_that(&bin.entries["vpn"].times().unwrap().location_changed.is_some()).is_true();
+        assert_that(&db.root.entries.contains_key("mail")).is_false();
+        assert_that(&db.deleted_objects).is_empty();
+    }
+
+    #[test]
+    fn test_delete_keeps_entries_with_the_same_name() {
+        let mut db = sample();
+        db.root.child_groups.get_mut("Work").unwrap().add_entry("mail", &entry("work mail", "", "", ""));
+
+        db.delete_entry(&["mail"]);
+        db.delete_entry(&["Work", "mail"]);
+
+        let mut names: Vec<&String> = bin(&db).entries.keys().collect();
+        names.sort();
+        assert_that(&names).is_equal_to(vec![&"mail".to_owned(), &"mail (2)".to_owned()]);
+    }
+
+    #[test]
+    fn test_delete_in_recycle_bin_is_permanent() {
+        let mut db = sample();
+        let uuid = db.root.entries["mail"].uuid();
+
+        db.delete_entry(&["mail"]);
+        assert_that(&db.delete_entry(&[RECYCLE_BIN_NAME, "mail"])).is_true();
+
+        assert_that(&bin(&db).entries).is_empty();
+        assert_that(&db.deleted_objects).has_length(1);
+        assert_that(&db.deleted_objects[0].uuid()).is_equal_to(uuid);
+    }
+
+    #[test]
+    fn test_delete_without_recycle_bin_is_permanent() {
+        let mut db = sample();
+        db.meta.set_recycle_bin_enabled(false);
+
+        assert_that(&db.delete_group(&["Work"])).is_true();
+
+        // Work, Servers, vpn and ssh
+        assert_that(&db.deleted_objects).has_length(4);
+        assert_that(&db.root.child_groups).is_empty();
+    }
+
+    #[test]
+    fn test_delete_group_and_empty_recycle_bin() {
+        let mut db = sample();
+
+        db.delete_group(&["Work", "Servers"]);
+        db.delete_entry(&["mail"]);
+        assert_that(&bin(&db).child_groups.contains_key("Servers")).is_true();
+
+        db.empty_recycle_bin();
+
+        assert_that(&bin(&db).child_groups).is_empty();
+        assert_that(&bin(&db).entries).is_empty();
+        // Servers, ssh and mail
+        assert_that(&db.deleted_objects).has_length(3);
+    }
+
+    #[test]
+    fn test_delete_recycle_bin_itself_is_permanent() {
+        let mut db = sample();
+        db.delete_entry(&["mail"]);
+
+        assert_that(&db.delete_group(&[RECYCLE_BIN_NAME])).is_true();
+        assert_that(&db.deleted_objects).has_length(2);
+
+        // the next deletion makes a new one
+        db.delete_entry(&["Work", "vpn"]);
+        assert_that(&bin(&db).entries.contains_key("vpn")).is_true();
+    }
+
+    #[test]
+    fn test_prune_drops_old_tombstones() {
+        let now = Utc::now();
+        let deleted = |uuid: &str, time: TimestampValue| DeletedObject::new(&UuidValue::from(uuid), &time);
+        let mut deleted_objects = vec![
+            deleted("2b5d0a1e-58bb-4c33-9c1b-7a0e3f4f61d2", TimestampValue::from(now - Duration::days(200))),
+            deleted("9d7c51ab-3f0e-4f5b-8a43-5e2a0c6d7b19", TimestampValue::from(now - Duration::days(2))),
+            deleted("c4e8f2a0-1b7d-4e69-b3a5-0f9d8e7c6b54", TimestampValue::now()),
+        ];
+
+        prune(&mut deleted_objects, now - Duration::days(180));
+
+        let uuids: Vec<UuidValue> = deleted_objects.iter().map(DeletedObject::uuid).collect();
+        assert_that(&uuids).is_equal_to(vec![
+            UuidValue::from("9d7c51ab-3f0e-4f5b-8a43-5e2a0c6d7b19"),
+            UuidValue::from("c4e8f2a0-1b7d-4e69-b3a5-0f9d8e7c6b54"),
+        ]);
+    }
+
+    #[test]
+    fn test_delete_missing_items() {
+        let mut db = sample();
+
+        assert_that(&db.delete_entry(&[])).is_false();
+        assert_that(&db.delete_entry(&["nothing"])).is_false();
+        assert_that(&db.delete_group(&["Work", "vpn"])).is_false();
+        assert_that(&db.delete_group(&[])).is_false();
+    }
+}
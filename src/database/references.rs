@@ -0,0 +1,412 @@
+use super::{
+    items::{
+        Entry,
+        Group,
+        MemoryProtection,
+        Meta,
+        UuidValue,
+        ValueGuard,
+    },
+    recycle_bin,
+};
+use crate::{
+    errors::Error,
+    results::Result,
+};
+
+use secstr::SecStr;
+use zeroize::Zeroizing;
+
+const REFERENCE_START: &str = "{REF:";
+
+// fields holding references or placeholders are expanded again, KeePass stops at the same depth
+pub(crate) const MAX_DEPTH: usize = 12;
+
+/// The entry fields a `{REF:<field>@<field>:<text>}` placeholder reads or searches in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReferenceField {
+    Title,
+    UserName,
+    Password,
+    Url,
+    Notes,
+    Uuid,
+}
+
+impl ReferenceField {
+    fn code(self) -> char {
+        match self {
+            ReferenceField::Title => 'T',
+            ReferenceField::UserName => 'U',
+            ReferenceField::Password => 'P',
+            ReferenceField::Url => 'A',
+            ReferenceField::Notes => 'N',
+            ReferenceField::Uuid => 'I',
+        }
+    }
+
+    fn from_code(code: char) -> Option<Self> {
+        match code.to_ascii_uppercase() {
+            'T' => Some(ReferenceField::Title),
+            'U' => Some(ReferenceField::UserName),
+            'P' => Some(ReferenceField::Password),
+            'A' => Some(ReferenceField::Url),
+            'N' => Some(ReferenceField::Notes),
+            'I' => Some(ReferenceField::Uuid),
+            _ => None,
+        }
+    }
+
+    // the string field behind the code, the UUID is not one
+    fn key(self) -> Option<&'static str> {
+        match self {
+            ReferenceField::Title => Some("Title"),
+            ReferenceField::UserName => Some("UserName"),
+            ReferenceField::Password => Some("Password"),
+            ReferenceField::Url => Some("URL"),
+            ReferenceField::Notes => Some("Notes"),
+            ReferenceField::Uuid => None,
+        }
+    }
+}
+
+// Where a reference looks for its target, `O` searches the custom fields
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SearchIn {
+    Field(ReferenceField),
+    Other,
+}
+
+struct Reference<'t> {
+    wanted: ReferenceField,
+    search_in: SearchIn,
+    text: &'t str,
+}
+
+/// Creates the placeholder reading `field` of `target`, which KeePass resolves by UUID
+pub fn reference_to(target: &Entry, field: ReferenceField) -> String {
+    format!("{}{}@I:{}}}", REFERENCE_START, field.code(), uuid_hex(&target.uuid()))
+}
+
+/// Expands the references in the field `key` of `entry`, looking up their targets in the tree
+/// under `root` outside the recycle bin. A reference without a target is kept as it is, like
+/// KeePass does. Fails with `Error::CyclicReference` if a field ends up referencing itself and
+/// with `Error::NestedReference` if references lead more than `MAX_DEPTH` fields deep.
+pub(crate) fn resolve_field<'a>(
+    root: &Group,
+    meta: &Meta,
+    entry: &'a Entry,
+    key: &str,
+) -> Result<Option<ValueGuard<'a>>> {
+    let value = match entry.get(key) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    if find_reference(&value).is_none() {
+        return Ok(Some(value));
+    }
+
+    let entries = targets(root, meta);
+
+    let mut stack = vec![(entry.uuid(), key.to_owned())];
+    let mut resolved = expand(&entries, &value, &mut stack)?;
+
+    Ok(Some(ValueGuard::owned(SecStr::new(std::mem::take(&mut *resolved).into_bytes()))))
+}
+
+/// Expands the references in `text`, which does not belong to an entry
+pub(crate) fn resolve_text(root: &Group, meta: &Meta, text: &str) -> Result<Zeroizing<String>> {
+    let entries = targets(root, meta);
+
+    expand(&entries, text, &mut Vec::new())
+}
+
+fn expand(
+    entries: &[&Entry],
+    text: &str,
+    stack: &mut Vec<(UuidValue, String)>,
+) -> Result<Zeroizing<String>> {
+    let mut expanded = Zeroizing::new(String::with_capacity(text.len()));
+    let mut rest = text;
+
+    while let Some(start) = find_reference(rest) {
+        let body_start = start + REFERENCE_START.len();
+        let body_end = match rest[body_start..].find('}') {
+            Some(length) => body_start + length,
+            None => break,
+        };
+        let placeholder = &rest[start..=body_end];
+
+        push_wiped(&mut expanded, &rest[..start]);
+
+        let target = parse_reference(&rest[body_start..body_end])
+            .and_then(|reference| find_target(entries, &reference).map(|target| (reference.wanted, target)));
+
+        match target {
+            Some((wanted, target)) => {
+                push_wiped(&mut expanded, &read_field(entries, target, wanted, placeholder, stack)?);
+            }
+            None => push_wiped(&mut expanded, placeholder),
+        }
+
+        rest = &rest[body_end + 1..];
+    }
+
+    push_wiped(&mut expanded, rest);
+
+    Ok(expanded)
+}
+
+// Appends to an expansion that may hold passwords, growing it without leaving a copy behind
+pub(crate) fn push_wiped(buffer: &mut Zeroizing<String>, value: &str) {
+    if buffer.capacity() - buffer.len() < value.len() {
+        let capacity = (buffer.len() + value.len()).max(2 * buffer.capacity());
+        let mut grown = Zeroizing::new(String::with_capacity(capacity));
+        grown.push_str(buffer);
+        *buffer = grown;
+    }
+    buffer.push_str(value);
+}
+
+fn read_field(
+    entries: &[&Entry],
+    target: &Entry,
+    wanted: ReferenceField,
+    placeholder: &str,
+    stack: &mut Vec<(UuidValue, String)>,
+) -> Result<Zeroizing<String>> {
+    let key = match wanted.key() {
+        Some(key) => key,
+        None => return Ok(Zeroizing::new(uuid_hex(&target.uuid()))),
+    };
+
+    let link = (target.uuid(), key.to_owned());
+    if stack.contains(&link) {
+        return Err(Error::CyclicReference { reference: placeholder.to_owned() });
+    }
+    if stack.len() >= MAX_DEPTH {
+        return Err(Error::NestedReference { reference: placeholder.to_owned() });
+    }
+
+    let value = Zeroizing::new(target.get(key).map(|value| value.to_string()).unwrap_or_default());
+
+    stack.push(link);
+    let expanded = expand(entries, &value, stack);
+    stack.pop();
+
+    expanded
+}
+
+// placeholders are case-insensitive, upper-casing ASCII keeps the byte offsets
+fn find_reference(text: &str) -> Option<usize> {
+    Zeroizing::new(text.to_ascii_uppercase()).find(REFERENCE_START)
+}
+
+// Parses the `<wanted>@<search in>:<text>` part of a reference
+fn parse_reference(body: &str) -> Option<Reference<'_>> {
+    let mut chars = body.chars();
+
+    let wanted = ReferenceField::from_code(chars.next()?)?;
+    if chars.next()? != '@' {
+        return None;
+    }
+
+    let search_in = match chars.next()? {
+        'O' | 'o' => SearchIn::Other,
+        code => SearchIn::Field(ReferenceField::from_code(code)?),
+    };
+    if chars.next()? != ':' {
+        return None;
+    }
+
+    Some(Reference {
+        wanted,
+        search_in,
+        text: chars.as_str(),
+    })
+}
+
+// The first entry in tree order whose field contains the text, or has the UUID
+fn find_target<'e>(entries: &[&'e Entry], reference: &Reference) -> Option<&'e Entry> {
+    let text = reference.text.to_lowercase();
+    let contains = |value: Option<ValueGuard>| {
+        value.is_some_and(|value| Zeroizing::new(value.to_lowercase()).contains(text.as_str()))
+    };
+
+    entries.iter().copied().find(|entry| match reference.search_in {
+        SearchIn::Field(ReferenceField::Uuid) => uuid_hex(&entry.uuid()).eq_ignore_ascii_case(reference.text),
+        SearchIn::Field(field) => contains(field.key().and_then(|key| entry.get(key))),
+        SearchIn::Other => entry
+            .fields()
+            .filter(|(key, _)| !MemoryProtection::is_standard_field(key))
+            .any(|(key, _)| contains(entry.get(key))),
+    })
+}
+
+// Entries ordered by group and title, so the first match does not depend on hashing
+fn targets<'e>(root: &'e Group, meta: &Meta) -> Vec<&'e Entry> {
+    recycle_bin::entries_outside_recycle_bin(root, meta)
+        .map(|found| found.entry)
+        .collect()
+}
+
+fn uuid_hex(uuid: &UuidValue) -> String {
+    hex::encode_upper(uuid.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        items::StringValue,
+        test_helpers::{
+            entry,
+            recycle_bin,
+        },
+    };
+    use spectral::prelude::*;
+
+    fn resolve(root: &Group, title: &str, key: &str) -> Result<Option<String>> {
+        resolve_field(root, &Meta::default(), &root.entries[title], key)
+            .map(|value| value.map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_reference_to_uses_uuid() {
+        let mut target = entry("service", "svc", "s3cret", "");
+        target.set_uuid(&UuidValue::from("46c9b1ff-bd4a-bc4b-bb26-0c6190bad20c"));
+
+        assert_that(&reference_to(&target, ReferenceField::UserName))
+            .is_equal_to("{REF:U@I:46C9B1FFBD4ABC4BBB260C6190BAD20C}".to_owned());
+    }
+
+    #[test]
+    fn test_resolve_by_uuid_and_field_search() {
+        let service = entry("service", "svc", "s3cret", "");
+        let mut app = entry("app", "", "", "");
+        app.remove("URL")
+            .set_reference("UserName", &service, ReferenceField::UserName)
+            .set_reference("Password", &service, ReferenceField::Password)
+            .add("Notes", &StringValue::UnprotectedString("login {ref:t@u:SVC} as {REF:U@P:s3c}".to_owned()));
+
+        let mut root = Group::root();
+        root.add_entry("service", &service);
+        root.add_entry("app", &app);
+
+        assert_that(&resolve(&root, "app", "UserName").unwrap()).is_equal_to(Some("svc".to_owned()));
+        assert_that(&resolve(&root, "app", "Password").unwrap()).is_equal_to(Some("s3cret".to_owned()));
+        assert_that(&resolve(&root, "app", "Notes").unwrap())
+            .is_equal_to(Some("login service as svc".to_owned()));
+        assert_that(&resolve(&root, "app", "URL").unwrap()).is_none();
+    }
+
+    #[test]
+    fn test_resolve_nested_and_custom_fields() {
+        let mut base = entry("base", "root", "pw", "");
+        base.add("Account", &StringValue::UnprotectedString("ops-team".to_owned()));
+        let mut middle = entry("middle", "", "", "");
+        middle.add("Password", &StringValue::UnprotectedString("{REF:P@O:ops-team}".to_owned()));
+        let mut top = entry("top", "", "", "");
+        top.set_reference("Password", &middle, ReferenceField::Password)
+            .set_reference("URL", &middle, ReferenceField::Uuid);
+
+        let mut root = Group::root();
+        root.add_entry("base", &base);
+        root.add_entry("middle", &middle);
+        root.add_entry("top", &top);
+
+        assert_that(&resolve(&root, "top", "Password").unwrap()).is_equal_to(Some("pw".to_owned()));
+        assert_that(&resolve(&root, "top", "URL").unwrap())
+            .is_equal_to(Some(uuid_hex(&middle.uuid())));
+    }
+
+    #[test]
+    fn test_resolve_keeps_unresolvable_references() {
+        let mut app = entry("app", "{REF:U@T:nobody} {REF:X@T:app} {REF:U@T:app", "", "");
+        app.add("Notes", &StringValue::UnprotectedString("{REF:U@T:app}".to_owned()));
+
+        let mut root = Group::root();
+        root.add_entry("app", &app);
+
+        assert_that(&resolve(&root, "app", "UserName").unwrap())
+            .is_equal_to(Some("{REF:U@T:nobody} {REF:X@T:app} {REF:U@T:app".to_owned()));
+    }
+
+    #[test]
+    fn test_resolve_detects_cycles() {
+        let mut a = entry("a", "", "", "");
+        let mut b = entry("b", "", "", "");
+        a.set_reference("Password", &b, ReferenceField::Password)
+            .set_reference("UserName", &b, ReferenceField::UserName);
+        b.set_reference("Password", &a, ReferenceField::Password)
+            .set_reference("UserName", &a, ReferenceField::Password);
+
+        let mut root = Group::root();
+        root.add_entry("a", &a);
+        root.add_entry("b", &b);
+
+        let result = resolve(&root, "a", "Password");
+
+        assert_that(&matches!(result, Err(Error::CyclicReference { .. }))).is_true();
+        assert_that(&matches!(resolve(&root, "a", "UserName"), Err(Error::CyclicReference { .. })))
+            .is_true();
+    }
+
+    #[test]
+    fn test_resolve_stops_at_max_depth() {
+        // each password references the next entry's twice, so the expansion fans out
+        let chain = |length: usize| {
+            let mut root = Group::root();
+            let mut next: Option<Entry> = None;
+            for index in (0..length).rev() {
+                let mut link = entry(&format!("link {}", index), "", "", "");
+                link.set_uuid(&UuidValue::from(format!("00000000-0000-0000-0000-{:012}", index).as_str()));
+                let password = match &next {
+                    Some(next) => reference_to(next, ReferenceField::Password).repeat(2),
+                    None => "end".to_owned(),
+                };
+                link.add("Password", &StringValue::ProtectedString(password.as_str().into()));
+                root.add_entry(&format!("link {}", index), &link);
+                next = Some(link);
+            }
+            root
+        };
+
+        let short = chain(MAX_DEPTH);
+        let long = chain(40);
+
+        assert_that(&resolve(&short, "link 0", "Password").unwrap().unwrap().len())
+            .is_equal_to("end".len() * 2usize.pow(MAX_DEPTH as u32 - 1));
+        assert_that(&matches!(resolve(&long, "link 0", "Password"), Err(Error::NestedReference { .. })))
+            .is_true();
+    }
+
+    #[test]
+    fn test_resolve_skips_recycle_bin() {
+        let old = entry("service", "old", "old", "");
+        let mut app = entry("app", "", "", "");
+        app.add("Notes", &StringValue::UnprotectedString("{REF:U@T:service}".to_owned()));
+
+        let (mut bin, meta) = recycle_bin("Recycle Bin");
+        bin.add_entry("service", &old);
+
+        let mut root = Group::root();
+        root.add_entry("app", &app);
+        root.add_group("Recycle Bin", &bin);
+
+        let resolved = resolve_field(&root, &meta, &root.entries["app"], "Notes").unwrap();
+
+        assert_that(&resolved.unwrap().to_string()).is_equal_to("{REF:U@T:service}".to_owned());
+    }
+
+    #[test]
+    fn test_push_wiped_grows_buffer() {
+        let mut buffer = Zeroizing::new(String::with_capacity(2));
+        push_wiped(&mut buffer, "hu");
+        push_wiped(&mut buffer, "nter2");
+
+        assert_that(&buffer.as_str()).is_equal_to("hunter2");
+        assert_that(&buffer.capacity()).is_greater_than_or_equal_to(7);
+    }
+}
@@ -0,0 +1,513 @@
+use super::{
+    items::{
+        Entry,
+        Group,
+        MemoryProtection,
+        Meta,
+    },
+    quality::{
+        self,
+        QualityRating,
+    },
+    recycle_bin,
+    references::push_wiped,
+};
+use crate::{
+    errors::Error,
+    results::Result,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use regex::{
+    Regex,
+    RegexBuilder,
+};
+use zeroize::Zeroizing;
+
+/// An entry found by `Database::search`
+#[derive(Debug)]
+pub struct SearchResult<'a> {
+    /// Names of the groups between the root group and the entry
+    pub path: Vec<&'a str>,
+    pub entry: &'a Entry,
+}
+
+/// A parsed search query in KeePassXC syntax. Terms are separated by whitespace and must all
+/// match; a term may be
+///
+/// * plain text or a `"quoted phrase"`, looked up case-insensitively in the title, user name,
+///   URL, notes and tags
+/// * prefixed with `-` or `!` to exclude entries that match it
+/// * prefixed with a field: `title:`, `user:`, `pass:`, `url:`, `notes:`, `tag:`, `group:`,
+///   or `attr:` for the names and values of custom fields
+/// * a regular expression with `r:`, after the field if there is one: `url:r:^https?://`
+/// * `is:expired` or `is:weak`
+#[derive(Debug)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+struct Term {
+    exclude: bool,
+    condition: Condition,
+}
+
+#[derive(Debug)]
+enum Condition {
+    Matches(Option<Field>, Pattern),
+    Expired,
+    Weak,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Field {
+    Title,
+    UserName,
+    Password,
+    Url,
+    Notes,
+    Tags,
+    Group,
+    Attribute,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+// A whitespace separated piece of the query, quoted parts are literal from `literal_from` on
+struct Token {
+    exclude: bool,
+    text: String,
+    literal_from: usize,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query> {
+        let mut terms = Vec::new();
+
+        for token in tokenize(query) {
+            if let Some(condition) = parse_condition(&token)? {
+                terms.push(Term { exclude: token.exclude, condition });
+            }
+        }
+
+        Ok(Query { terms })
+    }
+
+    /// Whether `entry`, stored under the groups in `path`, matches all terms at time `now`
+    pub fn matches(&self, entry: &Entry, path: &[&str], now: DateTime<Utc>) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.condition.matches(entry, path, now) != term.exclude)
+    }
+}
+
+impl Condition {
+    fn matches(&self, entry: &Entry, path: &[&str], now: DateTime<Utc>) -> bool {
+        match self {
+            Condition::Matches(Some(field), pattern) => field_values(entry, path, *field)
+                .iter()
+                .any(|value| pattern.matches(value)),
+            Condition::Matches(None, pattern) => DEFAULT_FIELDS
+                .iter()
+                .flat_map(|field| field_values(entry, path, *field))
+                .any(|value| pattern.matches(&value)),
+            Condition::Expired => is_expired(entry, now),
+            // like KeePassXC, an empty password is not a weak one
+            Condition::Weak => entry
+                .password()
+                .filter(|password| !password.is_empty())
+                .is_some_and(|password| quality::estimate(&password).rating <= QualityRating::Weak),
+        }
+    }
+}
+
+impl Pattern {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Text(text) => lowercase(value).contains(text.as_str()),
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+const DEFAULT_FIELDS: [Field; 5] = [
+    Field::Title,
+    Field::UserName,
+    Field::Url,
+    Field::Notes,
+    Field::Tags,
+];
+
+fn field_from_name(name: &str) -> Option<Field> {
+    match name {
+        "title" | "t" => Some(Field::Title),
+        "username" | "user" | "u" => Some(Field::UserName),
+        "password" | "pass" | "pw" | "p" => Some(Field::Password),
+        "url" => Some(Field::Url),
+        "notes" | "n" => Some(Field::Notes),
+        "tag" | "tags" => Some(Field::Tags),
+        "group" | "g" => Some(Field::Group),
+        "attr" | "attribute" => Some(Field::Attribute),
+        _ => None,
+    }
+}
+
+// The values may be passwords, so they are wiped once the entry is checked
+fn field_values(entry: &Entry, path: &[&str], field: Field) -> Vec<Zeroizing<String>> {
+    let standard = |key: &str| entry.get(key).map(|value| wiped(&value)).into_iter().collect();
+
+    match field {
+        Field::Title => standard("Title"),
+        Field::UserName => standard("UserName"),
+        Field::Password => standard("Password"),
+        Field::Url => standard("URL"),
+        Field::Notes => standard("Notes"),
+        Field::Tags => entry
+            .tags()
+            .map(|tags| split_tags(&tags).iter().map(|tag| wiped(tag)).collect())
+            .unwrap_or_default(),
+        Field::Group => path.last().map(|name| wiped(name)).into_iter().collect(),
+        Field::Attribute => entry
+            .fields()
+            .filter(|(key, _)| !MemoryProtection::is_standard_field(key))
+            .flat_map(|(key, _)| {
+                let value = entry.get(key).map(|value| wiped(&value));
+                std::iter::once(wiped(key)).chain(value)
+            })
+            .collect(),
+    }
+}
+
+fn wiped(value: &str) -> Zeroizing<String> {
+    let mut buffer = Zeroizing::new(String::new());
+    push_wiped(&mut buffer, value);
+    buffer
+}
+
+// Lowercases a value that may be a password without leaving copies behind. Unlike
+// `str::to_lowercase` a final sigma stays 'σ', so the query text is lowercased the same way.
+fn lowercase(value: &str) -> Zeroizing<String> {
+    let mut buffer = Zeroizing::new(String::with_capacity(value.len()));
+    let mut encoded = Zeroizing::new([0u8; 4]);
+    for c in value.chars().flat_map(char::to_lowercase) {
+        push_wiped(&mut buffer, c.encode_utf8(&mut *encoded));
+    }
+    buffer
+}
+
+// KeePass separates tags with commas or semicolons
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split([',', ';'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub(crate) fn is_expired(entry: &Entry, now: DateTime<Utc>) -> bool {
+    entry.times().is_some_and(|times| {
+        times.expires == Some(true)
+            && times
+                .expiry_time
+                .and_then(|time| time.as_datetime())
+                .is_some_and(|time| time <= now)
+    })
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let first = match chars.next() {
+            Some(c) => c,
+            None => break,
+        };
+
+        let mut token = Token {
+            exclude: false,
+            text: String::new(),
+            literal_from: usize::MAX,
+        };
+        let mut quoted = false;
+        let mut next = Some(first);
+
+        if (first == '-' || first == '!') && chars.peek().is_some_and(|c| !c.is_whitespace()) {
+            token.exclude = true;
+            next = chars.next();
+        }
+
+        while let Some(c) = next {
+            if c == '"' {
+                quoted = !quoted;
+                token.literal_from = token.literal_from.min(token.text.len());
+            } else if c.is_whitespace() && !quoted {
+                break;
+            } else {
+                token.text.push(c);
+            }
+            next = chars.next();
+        }
+
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn parse_condition(token: &Token) -> Result<Option<Condition>> {
+    let mut field = None;
+    let mut rest = token.text.as_str();
+    let mut literal_from = token.literal_from;
+
+    if let Some((prefix, remainder, remainder_literal_from)) = split_prefix(rest, literal_from) {
+        if prefix == "is" {
+            return match remainder.to_lowercase().as_str() {
+                "expired" => Ok(Some(Condition::Expired)),
+                "weak" => Ok(Some(Condition::Weak)),
+                _ => Err(invalid_term(token, "unknown condition")),
+            };
+        }
+
+        if let Some(f) = field_from_name(&prefix) {
+            field = Some(f);
+            rest = remainder;
+            literal_from = remainder_literal_from;
+        }
+    }
+
+    let mut regex = false;
+    if let Some((prefix, remainder, _)) = split_prefix(rest, literal_from) {
+        if prefix == "r" {
+            regex = true;
+            rest = remainder;
+        }
+    }
+
+    if rest.is_empty() {
+        return Ok(None);
+    }
+
+    let pattern = if regex {
+        RegexBuilder::new(rest)
+            .case_insensitive(true)
+            .build()
+            .map(Pattern::Regex)
+            .map_err(|e| invalid_term(token, &e.to_string()))?
+    } else {
+        Pattern::Text(lowercase(rest).to_string())
+    };
+
+    Ok(Some(Condition::Matches(field, pattern)))
+}
+
+// Splits `prefix:` off `text`, prefixes are only recognized before the first quote
+fn split_prefix(text: &str, literal_from: usize) -> Option<(String, &str, usize)> {
+    let colon = text[..literal_from.min(text.len())].find(':')?;
+
+    Some((
+        text[..colon].to_lowercase(),
+        &text[colon + 1..],
+        literal_from.saturating_sub(colon + 1),
+    ))
+}
+
+fn invalid_term(token: &Token, reason: &str) -> Error {
+    Error::InvalidSearchQuery {
+        term: token.text.clone(),
+        reason: reason.to_owned(),
+    }
+}
+
+/// Collects the entries matching `query` in the groups that have searching enabled,
+/// leaving out the recycle bin. Results are ordered by group path and title.
+pub(crate) fn search<'a>(
+    root: &'a Group,
+    meta: &Meta,
+    query: &Query,
+    now: DateTime<Utc>,
+) -> Vec<SearchResult<'a>> {
+    recycle_bin::entries_outside_recycle_bin(root, meta)
+        .filter(|found| found.groups.iter().fold(true, |parent, group| group.searching_enabled(parent)))
+        .filter(|found| query.matches(found.entry, &found.path, now))
+        .map(|found| SearchResult { path: found.path, entry: found.entry })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        items::{
+            StringValue,
+            Times,
+            TimestampValue,
+        },
+        test_helpers::{
+            entry,
+            recycle_bin,
+        },
+    };
+    use chrono::{
+        Duration,
+        TimeZone,
+    };
+    use spectral::prelude::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 6, 15).and_hms(12, 0, 0)
+    }
+
+    fn expiring(mut entry: Entry, expiry: DateTime<Utc>) -> Entry {
+        let times = Times {
+            expires: Some(true),
+            expiry_time: Some(TimestampValue::from(expiry)),
+            ..Default::default()
+        };
+        entry.set_times(&times);
+        entry
+    }
+
+    // root: mail, bank (expired) / Work: vpn (tagged, custom field) / Hidden: secret / Bin: old
+    fn sample() -> (Group, Meta) {
+        let mut root = Group::root();
+        root.add_entry("mail", &entry("mail", "alice", "hunter2", "https://mail.example.com"));
+        root.add_entry(
+            "bank",
+            &expiring(
+                entry("bank", "alice", "7Gq!x9#Lr2$wZp4&vB8^", "https://bank.example.com"),
+                now() - Duration::days(1),
+            ),
+        );
+
+        let mut vpn = entry("vpn", "bob", "Summer2020", "vpn.example.org");
+        vpn.set_tags("work; remote");
+        vpn.add("Token Serial", &StringValue::UnprotectedString("SN-4711".to_owned()));
+        let mut work = Group::new("Work", &Default::default(), &Default::default());
+        work.add_entry("vpn", &vpn);
+        root.add_group("Work", &work);
+
+        let mut hidden = Group::new("Hidden", &Default::default(), &Default::default());
+        hidden.enable_searching = Some(false);
+        hidden.add_entry("secret", &entry("secret", "alice", "x", ""));
+        root.add_group("Hidden", &hidden);
+
+        let (mut bin, meta) = recycle_bin("Bin");
+        bin.add_entry("old", &entry("old mail", "alice", "x", ""));
+        root.add_group("Bin", &bin);
+
+        (root, meta)
+    }
+
+    fn titles(query: &str) -> Vec<String> {
+        let (root, meta) = sample();
+        let query = Query::parse(query).unwrap();
+
+        search(&root, &meta, &query, now())
+            .iter()
+            .map(|result| result.entry.title().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_terms_in_default_fields() {
+        assert_that(&titles("alice")).is_equal_to(vec!["bank".to_owned(), "mail".to_owned()]);
+        assert_that(&titles("ALICE mail")).is_equal_to(vec!["mail".to_owned()]);
+        assert_that(&titles("remote")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("hunter2")).is_empty();
+    }
+
+    #[test]
+    fn test_search_quoted_phrase_and_exclusion() {
+        assert_that(&titles("\"mail.example\"")).is_equal_to(vec!["mail".to_owned()]);
+        assert_that(&titles("example -bank !vpn")).is_equal_to(vec!["mail".to_owned()]);
+        assert_that(&titles("\"title:mail\"")).is_empty();
+    }
+
+    #[test]
+    fn test_search_field_prefixes() {
+        assert_that(&titles("user:bob")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("pass:hunter")).is_equal_to(vec!["mail".to_owned()]);
+        assert_that(&titles("url:\"example.org\"")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("tag:work")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("attr:serial")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("attr:sn-4711")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("group:work")).is_equal_to(vec!["vpn".to_owned()]);
+        assert_that(&titles("title:")).has_length(3);
+    }
+
+    #[test]
+    fn test_search_regex() {
+        assert_that(&titles("r:^(MAIL|vpn)$")).is_equal_to(vec!["mail".to_owned(), "vpn".to_owned()]);
+        assert_that(&titles("url:r:^https://b")).is_equal_to(vec!["bank".to_owned()]);
+        assert_that(&titles("url:https://bank")).is_equal_to(vec!["bank".to_owned()]);
+    }
+
+    #[test]
+    fn test_lowercase() {
+        assert_that(&lowercase("Hunter2").as_str()).is_equal_to("hunter2");
+        assert_that(&lowercase("ÄRGER ΟΔΟΣ").as_str()).is_equal_to("ärger οδοσ");
+        assert_that(&lowercase("İ").as_str()).is_equal_to("i\u{307}");
+    }
+
+    #[test]
+    fn test_search_invalid_regex_fails() {
+        let result = Query::parse("r:(unclosed");
+
+        assert_that(&matches!(result, Err(Error::InvalidSearchQuery { .. }))).is_true();
+    }
+
+    #[test]
+    fn test_search_conditions() {
+        assert_that(&titles("is:expired")).is_equal_to(vec!["bank".to_owned()]);
+        assert_that(&titles("is:weak")).is_equal_to(vec!["mail".to_owned(), "vpn".to_owned()]);
+        assert_that(&titles("-is:weak")).is_equal_to(vec!["bank".to_owned()]);
+        assert_that(&matches!(Query::parse("is:stale"), Err(Error::InvalidSearchQuery { .. })))
+            .is_true();
+    }
+
+    #[test]
+    fn test_search_weak_skips_empty_passwords() {
+        let mut root = Group::root();
+        root.add_entry("note", &entry("note", "", "", ""));
+        root.add_entry("mail", &entry("mail", "alice", "hunter2", ""));
+        let query = Query::parse("is:weak").unwrap();
+
+        let results = search(&root, &Meta::default(), &query, now());
+
+        assert_that(&results).has_length(1);
+        assert_that(&results[0].entry.title().as_deref()).is_equal_to(Some("mail"));
+    }
+
+    #[test]
+    fn test_search_skips_disabled_groups_and_recycle_bin() {
+        assert_that(&titles("")).is_equal_to(vec!["bank".to_owned(), "mail".to_owned(), "vpn".to_owned()]);
+    }
+
+    #[test]
+    fn test_search_disabled_group_with_enabled_child() {
+        let (mut root, meta) = sample();
+        let mut visible = Group::new("Visible", &Default::default(), &Default::default());
+        visible.enable_searching = Some(true);
+        visible.add_entry("note", &entry("note", "carol", "", ""));
+        root.child_groups.get_mut("Hidden").unwrap().add_group("Visible", &visible);
+
+        let query = Query::parse("carol").unwrap();
+        let results = search(&root, &meta, &query, now());
+
+        assert_that(&results).has_length(1);
+        assert_that(&results[0].path).is_equal_to(vec!["Hidden", "Visible"]);
+    }
+}
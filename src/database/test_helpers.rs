@@ -0,0 +1,33 @@
+//! Entries and groups shared by the unit tests of the database modules
+
+use super::items::{
+    Entry,
+    Group,
+    Meta,
+    StringValue,
+    UuidValue,
+};
+
+const RECYCLE_BIN_UUID: &str = "77b2ff13-c5da-4026-b383-1c4193a7bde6";
+
+/// An entry with the standard fields a test needs, the password is protected
+pub(crate) fn entry(title: &str, user: &str, password: &str, url: &str) -> Entry {
+    let mut entry = Entry::default();
+    entry.add("Title", &StringValue::UnprotectedString(title.to_owned()));
+    entry.add("UserName", &StringValue::UnprotectedString(user.to_owned()));
+    entry.add("Password", &StringValue::ProtectedString(password.into()));
+    entry.add("URL", &StringValue::UnprotectedString(url.to_owned()));
+    entry
+}
+
+/// An empty group `name` and the metadata that makes it the recycle bin
+pub(crate) fn recycle_bin(name: &str) -> (Group, Meta) {
+    let mut bin = Group::new(name, &Default::default(), &Default::default());
+    bin.uuid = UuidValue::from(RECYCLE_BIN_UUID);
+
+    let mut meta = Meta::default();
+    meta.set_recycle_bin_enabled(true);
+    meta.set_recycle_bin_uuid(&bin.uuid);
+
+    (bin, meta)
+}
@@ -6,6 +6,14 @@ use crate::{
     results::Result
 };
 
+use chrono::{
+    DateTime,
+    Datelike,
+    NaiveDate,
+    Timelike,
+    Utc,
+};
+
 use std::str;
 
 pub(crate) fn from_utf8(data: &[u8]) -> Result<String> {
@@ -27,3 +35,78 @@ pub(crate) fn ensure_length(field_type: u16, field_size: u32, expected_field_siz
         Ok(())
     }
 }
+
+/// Decodes KeePass 1.x' packed 5 byte date: 14 bit year, 4 bit month, 5 bit day, 5 bit hour,
+/// 6 bit minute and 6 bit second, most significant bit first. `None` if it is no valid date.
+pub(crate) fn from_packed_time(data: &[u8]) -> Option<DateTime<Utc>> {
+    let b: Vec<u32> = data.iter().map(|b| u32::from(*b)).collect();
+
+    let year = (b[0] << 6) | (b[1] >> 2);
+    let month = ((b[1] & 0x03) << 2) | (b[2] >> 6);
+    let day = (b[2] >> 1) & 0x1f;
+    let hour = ((b[2] & 0x01) << 4) | (b[3] >> 4);
+    let minute = ((b[3] & 0x0f) << 2) | (b[4] >> 6);
+    let second = b[4] & 0x3f;
+
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, second))
+        .map(|time| DateTime::from_utc(time, Utc))
+}
+
+/// Counterpart of `from_packed_time`
+pub(crate) fn to_packed_time(time: &DateTime<Utc>) -> [u8; 5] {
+    let year = time.year() as u32;
+    let (month, day) = (time.month(), time.day());
+    let (hour, minute, second) = (time.hour(), time.minute(), time.second().min(59));
+
+    [
+        ((year >> 6) & 0xff) as u8,
+        (((year & 0x3f) << 2) | ((month >> 2) & 0x03)) as u8,
+        (((month & 0x03) << 6) | ((day & 0x1f) << 1) | ((hour >> 4) & 0x01)) as u8,
+        (((hour & 0x0f) << 4) | ((minute >> 2) & 0x0f)) as u8,
+        (((minute & 0x03) << 6) | (second & 0x3f)) as u8,
+    ]
+}
+
+/// The expiry time KeePass 1.x stores for entries and groups that never expire
+pub(crate) fn never_expires() -> DateTime<Utc> {
+    DateTime::from_utc(NaiveDate::from_ymd(2999, 12, 28).and_hms(23, 59, 59), Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_from_packed_time() {
+        // 2021-06-15 13:45:30
+        let packed = [0x1f, 0x95, 0x9e, 0xdb, 0x5e];
+
+        assert_that(&from_packed_time(&packed))
+            .is_some()
+            .is_equal_to(DateTime::from_utc(NaiveDate::from_ymd(2021, 6, 15).and_hms(13, 45, 30), Utc));
+    }
+
+    #[test]
+    fn test_from_packed_time_never_expires() {
+        let packed = [0x2e, 0xdf, 0x39, 0x7e, 0xfb];
+
+        assert_that(&from_packed_time(&packed))
+            .is_some()
+            .is_equal_to(never_expires());
+    }
+
+    #[test]
+    fn test_to_packed_time() {
+        let time = DateTime::from_utc(NaiveDate::from_ymd(2021, 6, 15).and_hms(13, 45, 30), Utc);
+
+        assert_that(&to_packed_time(&time)).is_equal_to([0x1f, 0x95, 0x9e, 0xdb, 0x5e]);
+        assert_that(&to_packed_time(&never_expires())).is_equal_to([0x2e, 0xdf, 0x39, 0x7e, 0xfb]);
+    }
+
+    #[test]
+    fn test_from_packed_time_invalid() {
+        assert_that(&from_packed_time(&[0u8; 5])).is_none();
+    }
+}
@@ -65,6 +65,9 @@ pub enum DatabaseIntegrityError {
     InvalidVariantDictionaryValueType {
         value_type: u8,
     },
+    DuplicateVariantDictionaryKey {
+        key: String,
+    },
     InvalidKDBFieldLength {
         field_type: u16,
         field_size: u32,
@@ -91,6 +94,9 @@ pub enum DatabaseIntegrityError {
     XMLParsing {
         e: xml::reader::Error,
     },
+    XMLWriting {
+        e: xml::writer::Error,
+    },
     Base64 {
         e: base64::DecodeError,
     },
@@ -105,6 +111,7 @@ impl std::error::Error for DatabaseIntegrityError {
         match self {
             DatabaseIntegrityError::Crypto { e } => Some(e),
             DatabaseIntegrityError::XMLParsing { e } => Some(e),
+            DatabaseIntegrityError::XMLWriting { e } => Some(e),
             DatabaseIntegrityError::Base64 { e } => Some(e),
             DatabaseIntegrityError::UTF8 { e } => Some(e),
             _ => None,
@@ -183,7 +190,7 @@ impl std::fmt::Display for DatabaseIntegrityError {
                     version
                 ),
                 DatabaseIntegrityError::InvalidVariantDictionaryFormat { length } => format!(
-                    "Encountered a VariantDictionary with no content: {}",
+                    "Encountered a malformed VariantDictionary of length {}",
                     length
                 ),
                 DatabaseIntegrityError::InvalidVariantDictionaryValueType { value_type } => {
@@ -192,6 +199,10 @@ impl std::fmt::Display for DatabaseIntegrityError {
                         value_type
                     )
                 }
+                DatabaseIntegrityError::DuplicateVariantDictionaryKey { key } => format!(
+                    "Encountered a VariantDictionary with the key {:?} more than once",
+                    key
+                ),
                 DatabaseIntegrityError::InvalidKDBFieldLength { field_type, field_size, expected_field_size } =>
                     format!("Encountered a field with an invalid size: expected {}, got {} for field type {}", expected_field_size, field_size, field_type),
                 DatabaseIntegrityError::InvalidKDBGroupFieldType { field_type } =>
@@ -221,6 +232,10 @@ impl std::fmt::Display for DatabaseIntegrityError {
                     "Encountered an error when parsing the inner XML payload: {}",
                     e
                 ),
+                DatabaseIntegrityError::XMLWriting { e } => format!(
+                    "Encountered an error when writing the inner XML payload: {}",
+                    e
+                ),
                 DatabaseIntegrityError::UTF8 { e } => format!(
                     "Encountering an error when parsing an UTF-8 formatted string: {}",
                     e
@@ -255,6 +270,13 @@ impl From<xml::reader::Error> for DatabaseIntegrityError {
     }
 }
 
+impl From<xml::writer::Error> for DatabaseIntegrityError {
+    #[cfg_attr(tarpaulin, skip)]
+    fn from(e: xml::writer::Error) -> Self {
+        DatabaseIntegrityError::XMLWriting { e }
+    }
+}
+
 impl From<std::str::Utf8Error> for DatabaseIntegrityError {
     #[cfg_attr(tarpaulin, skip)]
     fn from(e: std::str::Utf8Error) -> Self {
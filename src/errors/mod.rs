@@ -4,12 +4,45 @@ mod database;
 pub use crypto::*;
 pub use database::*;
 
+use crate::api::header::FormatVersion;
+
 #[derive(Debug)]
 pub enum Error {
     IO { e: std::io::Error },
     DatabaseIntegrity { e: DatabaseIntegrityError },
     IncorrectKey,
     InvalidKeyFile,
+    KdfResourceLimit {
+        parameter: String,
+        value: u64,
+        limit: u64,
+    },
+    InvalidSearchQuery {
+        term: String,
+        reason: String,
+    },
+    CyclicReference {
+        reference: String,
+    },
+    NestedReference {
+        reference: String,
+    },
+    InvalidPlaceholder {
+        placeholder: String,
+        reason: String,
+    },
+    InvalidOtp {
+        reason: String,
+    },
+    InvalidGeneratorSettings {
+        reason: String,
+    },
+    InvalidBreachList {
+        reason: String,
+    },
+    UnsupportedFormat {
+        format: FormatVersion,
+    },
 }
 
 impl std::error::Error for Error {
@@ -34,6 +67,39 @@ impl std::fmt::Display for Error {
                 Error::IncorrectKey => "Incorrect key specified".to_owned(),
                 Error::InvalidKeyFile => "Keyfile format invalid".to_owned(),
                 Error::DatabaseIntegrity { e } => format!("{}", e),
+                Error::KdfResourceLimit { parameter, value, limit } => format!(
+                    "KDF parameter {} of {} exceeds the configured limit of {}",
+                    parameter, value, limit
+                ),
+                Error::InvalidSearchQuery { term, reason } => format!(
+                    "Invalid search term {}: {}",
+                    term, reason
+                ),
+                Error::CyclicReference { reference } => format!(
+                    "Field reference {} refers back to itself",
+                    reference
+                ),
+                Error::NestedReference { reference } => format!(
+                    "Field reference {} is nested too deeply",
+                    reference
+                ),
+                Error::InvalidPlaceholder { placeholder, reason } => format!(
+                    "Invalid placeholder {}: {}",
+                    placeholder, reason
+                ),
+                Error::InvalidOtp { reason } => format!("Invalid OTP settings: {}", reason),
+                Error::InvalidGeneratorSettings { reason } => format!(
+                    "Invalid password generator settings: {}",
+                    reason
+                ),
+                Error::InvalidBreachList { reason } => format!(
+                    "Invalid breached password list: {}",
+                    reason
+                ),
+                Error::UnsupportedFormat { format } => format!(
+                    "Saving as {:?} is not supported, convert the database first",
+                    format
+                ),
             }
         )
     }
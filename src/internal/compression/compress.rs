@@ -29,7 +29,7 @@ impl Compress for GZipCompression {
     fn compress(&self, in_buffer: &[u8], level: u32) -> Result<Vec<u8>> {
         let res = Vec::new();
         let mut encoder = GzEncoder::new(res, Compression::new(level));
-        encoder.write(in_buffer)?;
+        encoder.write_all(in_buffer)?;
         Ok(encoder.finish()?)
     }
 }
@@ -6,6 +6,11 @@ use std::io::Read;
 
 pub trait Decompress {
     fn decompress(&self, in_buffer: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decompresses as much of a truncated or damaged buffer as possible
+    fn decompress_prefix(&self, in_buffer: &[u8]) -> Vec<u8> {
+        self.decompress(in_buffer).unwrap_or_default()
+    }
 }
 
 pub struct NoCompression;
@@ -29,4 +34,20 @@ impl Decompress for GZipCompression {
         decoder.read_to_end(&mut res)?;
         Ok(res)
     }
+
+    fn decompress_prefix(&self, in_buffer: &[u8]) -> Vec<u8> {
+        let mut res = Vec::new();
+        let mut decoder = GzDecoder::new(in_buffer);
+        let mut chunk = [0u8; 4096];
+
+        // keep everything that was inflated before the stream broke off
+        while let Ok(length) = decoder.read(&mut chunk) {
+            if length == 0 {
+                break;
+            }
+            res.extend_from_slice(&chunk[..length]);
+        }
+
+        res
+    }
 }
@@ -28,6 +28,13 @@ impl Compression {
             Compression::GZip => Box::new(decompress::GZipCompression),
         }
     }
+
+    pub(crate) fn to_code(&self) -> u32 {
+        match self {
+            Compression::None => 0,
+            Compression::GZip => 1,
+        }
+    }
 }
 
 impl TryFrom<u32> for Compression {
@@ -73,6 +80,19 @@ mod tests {
         test_compress_decompress(&compressor, &decompressor, 0);
     }
 
+    #[test]
+    fn test_decompress_prefix_of_truncated_gzip() {
+        let buf: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compression::GZip.get_compression().compress(&buf, 6).unwrap();
+
+        let decompressor = Compression::GZip.get_decompression();
+        let prefix = decompressor.decompress_prefix(&compressed[..compressed.len() / 2]);
+
+        assert_that(&decompressor.decompress(&compressed[..compressed.len() / 2])).is_err();
+        assert_that(&prefix.is_empty()).is_false();
+        assert_that(&buf.starts_with(&prefix)).is_true();
+    }
+
     fn test_compress_decompress(
         compressor: &Box<dyn compress::Compress>,
         decompressor: &Box<dyn decompress::Decompress>,
@@ -4,27 +4,12 @@ use super::{
     Encrypt,
 };
 
-use crate::{
-    errors::{
-        CryptoError,
-        DatabaseIntegrityError,
-        Error
-    },
-    results::Result,
-};
+use crate::results::Result;
 
 use aes::Aes256;
+use zeroize::Zeroize;
 
-use block_modes::{
-    BlockMode,
-    Cbc,
-    block_padding::Pkcs7,
-};
-
-type Aes256Cbc = Cbc<Aes256, Pkcs7>;
-
-
-#[derive(Debug)]
+/// CBC mode cipher, the key is wiped on drop
 pub struct AES256Cipher {
     key: Vec<u8>,
     iv: Vec<u8>,
@@ -48,15 +33,11 @@ impl Decrypt for AES256Cipher {
 
     #[inline(always)]
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
-        let cipher = Aes256Cbc::new_var(&self.key, &self.iv)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
+        super::cbc_decrypt::<Aes256>(&self.key, &self.iv, ciphertext)
+    }
 
-        let mut buffer = ciphertext.to_vec();
-        Ok(
-            cipher
-            .decrypt_vec(&mut buffer)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?
-        )
+    fn decrypt_prefix(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        super::cbc_decrypt_prefix::<Aes256>(&self.key, &self.iv, ciphertext)
     }
 }
 
@@ -64,23 +45,45 @@ impl Encrypt for AES256Cipher {
 
     #[inline(always)]
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let cipher = Aes256Cbc::new_var(&self.key, &self.iv)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
-
-        let mut buffer = plaintext.to_vec();
-        Ok(
-            cipher.encrypt_vec(&mut buffer)
-        )
+        super::cbc_encrypt::<Aes256>(&self.key, &self.iv, plaintext)
     }
 }
 
 impl Cipher for AES256Cipher {}
 
+impl Zeroize for AES256Cipher {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.iv.zeroize();
+    }
+}
+
+impl Drop for AES256Cipher {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use spectral::prelude::*;
 
+    #[test]
+    fn test_decrypt_prefix_of_truncated_ciphertext() {
+        let key = [0u8; 32];
+        let iv = [1u8; 16];
+        let plaintext = [7u8; 40];
+
+        let encrypted = AES256Cipher::new(&key, &iv).unwrap().encrypt(&plaintext).unwrap();
+
+        let decrypted = AES256Cipher::new(&key, &iv).unwrap().decrypt_prefix(&encrypted[..36]);
+
+        assert_that(&decrypted)
+            .is_ok()
+            .is_equal_to(plaintext[..32].to_vec());
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let key = [0u8; 32];
@@ -112,4 +115,14 @@ mod tests {
             .is_ok()
             .is_equal_to(plaintext.as_bytes().to_vec());
     }
+
+    #[test]
+    fn test_zeroize_clears_key() {
+        let mut cipher = AES256Cipher::new([3u8; 32].as_ref(), [1u8; 16].as_ref()).unwrap();
+
+        cipher.zeroize();
+
+        assert_that(&cipher.key.is_empty()).is_true();
+        assert_that(&cipher.iv.is_empty()).is_true();
+    }
 }
@@ -0,0 +1,179 @@
+use super::{
+    Cipher,
+    Decrypt,
+    Encrypt,
+};
+
+use crate::{
+    errors::{
+        CryptoError,
+        DatabaseIntegrityError,
+        Error
+    },
+    results::Result,
+};
+
+use cipher::stream::InvalidKeyNonceLength;
+use zeroize::Zeroize;
+
+// KeePass throws away the start of the keystream, it is the weakest part of RC4
+const DROPPED_KEYSTREAM: usize = 512;
+
+/// The RC4 variant of KeePass 2.x alphas, the inner stream of some KDBX 2.x/3.0 files.
+/// Its key schedule swaps with the first state byte instead of the current one. Only
+/// for reading, converted databases are written with Salsa20 or ChaCha20.
+pub struct ArcFourVariantCipher {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl ArcFourVariantCipher {
+    /// Unlike the other inner streams the key is used as is, without hashing it first
+    pub fn with_key(key: &[u8]) -> Result<Self> {
+        if key.is_empty() {
+            return Err(Error::from(
+                DatabaseIntegrityError::from(
+                    CryptoError::from(InvalidKeyNonceLength)
+                )
+            ));
+        }
+
+        let mut state = [0u8; 256];
+        for (index, value) in state.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+
+        let mut j = 0u8;
+        for (index, key_byte) in (0..state.len()).zip(key.iter().cycle()) {
+            j = j.wrapping_add(state[index]).wrapping_add(*key_byte);
+            state.swap(0, j as usize);
+        }
+
+        let mut cipher = ArcFourVariantCipher {
+            state,
+            i: 0,
+            j: 0,
+        };
+
+        let mut dropped = [0u8; DROPPED_KEYSTREAM];
+        cipher.apply_keystream(&mut dropped);
+
+        Ok(cipher)
+    }
+
+    fn apply_keystream(&mut self, buffer: &mut [u8]) {
+        for byte in buffer.iter_mut() {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+
+            let index = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+            *byte ^= self.state[index as usize];
+        }
+    }
+}
+
+impl Decrypt for ArcFourVariantCipher {
+
+    #[inline(always)]
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut buffer = Vec::from(ciphertext);
+        self.apply_keystream(&mut buffer);
+        Ok(buffer)
+    }
+}
+
+impl Encrypt for ArcFourVariantCipher {
+
+    #[inline(always)]
+    fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut buffer = Vec::from(plaintext);
+        self.apply_keystream(&mut buffer);
+        Ok(buffer)
+    }
+}
+
+impl Cipher for ArcFourVariantCipher {}
+
+impl Zeroize for ArcFourVariantCipher {
+    fn zeroize(&mut self) {
+        self.state.zeroize();
+        self.i.zeroize();
+        self.j.zeroize();
+    }
+}
+
+impl Drop for ArcFourVariantCipher {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    const KEY: &[u8] = b"protected stream key";
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let plaintext = "this is a simple plaintext";
+
+        let encrypted = ArcFourVariantCipher::with_key(KEY)
+            .unwrap()
+            .encrypt(plaintext.as_bytes())
+            .unwrap();
+
+        assert_that(&encrypted.as_slice()).is_not_equal_to(plaintext.as_bytes());
+
+        let decrypted = ArcFourVariantCipher::with_key(KEY)
+            .unwrap()
+            .decrypt(&encrypted);
+
+        assert_that(&decrypted)
+            .is_ok()
+            .is_equal_to(plaintext.as_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_keystream_continues_across_calls() {
+        let mut whole = ArcFourVariantCipher::with_key(KEY).unwrap();
+        let mut parts = ArcFourVariantCipher::with_key(KEY).unwrap();
+
+        let encrypted = whole.encrypt(b"first valuesecond value").unwrap();
+
+        let mut pieces = parts.encrypt(b"first value").unwrap();
+        pieces.extend(parts.encrypt(b"second value").unwrap());
+
+        assert_that(&pieces).is_equal_to(encrypted);
+    }
+
+    #[test]
+    fn test_keystream() {
+        // the keystream after the dropped bytes, from the reimplementation in
+        // tests/fixture/generate_legacy_kdbx.py, not yet checked against KeePass' CryptoRandomStream
+        let keystream = ArcFourVariantCipher::with_key(KEY)
+            .unwrap()
+            .encrypt(&[0u8; 8])
+            .unwrap();
+
+        assert_that(&keystream)
+            .is_equal_to(vec![0xa6, 0x03, 0xed, 0xce, 0xb1, 0x9f, 0x07, 0x32]);
+    }
+
+    #[test]
+    fn test_empty_key_is_rejected() {
+        assert_that(&ArcFourVariantCipher::with_key(&[]).is_err()).is_true();
+    }
+
+    #[test]
+    fn test_zeroize_clears_state() {
+        let mut cipher = ArcFourVariantCipher::with_key(KEY).unwrap();
+
+        cipher.zeroize();
+
+        assert_that(&cipher.state.iter().all(|b| *b == 0)).is_true();
+    }
+}
@@ -3,6 +3,7 @@ use cipher::{
     NewStreamCipher,
     StreamCipher
 };
+use zeroize::Zeroize;
 
 use crate::{
     errors::{
@@ -20,10 +21,10 @@ use super::{
     Encrypt,
 };
 
-#[derive(Debug)]
+/// ChaCha20 keystream that continues across calls, KeePass encrypts all protected
+/// values of a database with one running stream
 pub struct ChaCha20Cipher {
-    key: Vec<u8>,
-    iv: Vec<u8>,
+    cipher: ChaCha20,
 }
 
 impl ChaCha20Cipher {
@@ -32,21 +33,34 @@ impl ChaCha20Cipher {
         key: &[u8],
         iv: &[u8]
     ) -> Result<Self> {
+        let cipher = ChaCha20::new_var(
+            key,
+            iv
+        ).map_err(|e| {
+            Error::from(
+                DatabaseIntegrityError::from(
+                    CryptoError::from(e)
+                )
+            )
+        })?;
+
         Ok(
             ChaCha20Cipher {
-                key: key.to_vec(),
-                iv: iv.to_vec()
+                cipher
             }
         )
     }
 
     pub fn with_key(key: &[u8]) -> Result<Self> {
-        let iv = sha512(&[key])?;
+        let mut iv = sha512(&[key])?;
 
         let derived_key = &iv[0..32];
         let derived_nonce = &iv[32..44];
 
-        Self::new(derived_key, derived_nonce)
+        let cipher = Self::new(derived_key, derived_nonce);
+        iv.as_mut_slice().zeroize();
+
+        cipher
     }
 }
 
@@ -54,19 +68,8 @@ impl Decrypt for ChaCha20Cipher {
 
     #[inline(always)]
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
-        let mut cipher = ChaCha20::new_var(
-            self.key.as_ref(),
-            self.iv.as_ref()
-        ).map_err(|e| {
-            Error::from(
-                DatabaseIntegrityError::from(
-                    CryptoError::from(e)
-                )
-            )
-        })?;
-
         let mut buffer = Vec::from(ciphertext);
-        cipher.decrypt(&mut buffer);
+        self.cipher.decrypt(&mut buffer);
         Ok(buffer)
     }
 }
@@ -75,18 +78,8 @@ impl Encrypt for ChaCha20Cipher {
 
     #[inline(always)]
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let mut cipher = ChaCha20::new_var(
-            self.key.as_ref(),
-            self.iv.as_ref()
-        ).map_err(|e| {
-            Error::from(
-                DatabaseIntegrityError::from(
-                    CryptoError::from(e)
-                )
-            )
-        })?;
         let mut buffer = Vec::from(plaintext);
-        cipher.encrypt(&mut buffer);
+        self.cipher.encrypt(&mut buffer);
         Ok(buffer)
     }
 }
@@ -99,6 +92,21 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_keystream_continues_across_calls() {
+        let key = [0u8; 32];
+
+        let mut whole = ChaCha20Cipher::with_key(key.as_ref()).unwrap();
+        let mut parts = ChaCha20Cipher::with_key(key.as_ref()).unwrap();
+
+        let encrypted = whole.encrypt(b"first valuesecond value").unwrap();
+
+        let mut pieces = parts.encrypt(b"first value").unwrap();
+        pieces.extend(parts.encrypt(b"second value").unwrap());
+
+        assert_that(&pieces).is_equal_to(encrypted);
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let key = [0u8; 32];
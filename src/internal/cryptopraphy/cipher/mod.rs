@@ -1,6 +1,9 @@
 mod aes256;
 pub(crate) use self::aes256::AES256Cipher;
 
+mod arcfour;
+pub(crate) use self::arcfour::ArcFourVariantCipher;
+
 mod chacha20;
 pub(crate) use self::chacha20::ChaCha20Cipher;
 
@@ -15,6 +18,31 @@ pub(crate) use self::twofish::TwofishCipher;
 
 pub(crate) use crate::results::Result;
 
+use crate::errors::{
+    CryptoError,
+    DatabaseIntegrityError,
+    Error,
+};
+
+use block_modes::{
+    BlockMode,
+    BlockModeError,
+    Cbc,
+    block_padding::{
+        Padding,
+        Pkcs7,
+    },
+    cipher::{
+        BlockCipher,
+        NewBlockCipher,
+        generic_array::{
+            GenericArray,
+            typenum::Unsigned,
+        },
+    },
+};
+use zeroize::Zeroizing;
+
 
 pub(crate) trait Encrypt {
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>>;
@@ -22,6 +50,82 @@ pub(crate) trait Encrypt {
 
 pub(crate) trait Decrypt {
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypts what is left of a ciphertext whose end is missing, as far as possible
+    fn decrypt_prefix(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt(ciphertext)
+    }
 }
 
 pub(crate) trait Cipher: Encrypt + Decrypt {}
+
+fn block_mode_error(e: BlockModeError) -> Error {
+    Error::from(DatabaseIntegrityError::from(CryptoError::from(e)))
+}
+
+/// CBC with PKCS#7 padding
+fn cbc_encrypt<C>(key: &[u8], iv: &[u8], plaintext: &[u8]) -> Result<Vec<u8>>
+    where C: BlockCipher + NewBlockCipher
+{
+    let mut mode = Cbc::<C, Pkcs7>::new_var(key, iv)
+        .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
+
+    let block_size = C::BlockSize::to_usize();
+    let pos = plaintext.len();
+
+    // PKCS#7 always appends between 1 and block_size bytes
+    let mut buffer = plaintext.to_vec();
+    buffer.resize(pos + block_size - pos % block_size, 0);
+    Pkcs7::pad(&mut buffer, pos, block_size)
+        .map_err(|_| block_mode_error(BlockModeError))?;
+
+    for block in buffer.chunks_exact_mut(block_size) {
+        mode.encrypt_blocks(std::slice::from_mut(GenericArray::from_mut_slice(block)));
+    }
+
+    Ok(buffer)
+}
+
+/// Counterpart of `cbc_encrypt`, a wrongly padded plaintext is wiped
+fn cbc_decrypt<C>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>>
+    where C: BlockCipher + NewBlockCipher
+{
+    let block_size = C::BlockSize::to_usize();
+    if ciphertext.len() % block_size != 0 {
+        return Err(block_mode_error(BlockModeError));
+    }
+
+    let buffer = cbc_decrypt_blocks::<C>(key, iv, ciphertext)?;
+
+    let length = Pkcs7::unpad(&buffer)
+        .map_err(|_| block_mode_error(BlockModeError))?
+        .len();
+
+    Ok(buffer[..length].to_vec())
+}
+
+/// Decrypts the complete blocks at the start of a truncated ciphertext, without unpadding
+fn cbc_decrypt_prefix<C>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>>
+    where C: BlockCipher + NewBlockCipher
+{
+    let block_size = C::BlockSize::to_usize();
+    let length = ciphertext.len() - ciphertext.len() % block_size;
+
+    Ok(cbc_decrypt_blocks::<C>(key, iv, &ciphertext[..length])?.to_vec())
+}
+
+fn cbc_decrypt_blocks<C>(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>>
+    where C: BlockCipher + NewBlockCipher
+{
+    let block_size = C::BlockSize::to_usize();
+
+    let mut mode = Cbc::<C, Pkcs7>::new_var(key, iv)
+        .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
+
+    let mut buffer = Zeroizing::new(ciphertext.to_vec());
+    for block in buffer.chunks_exact_mut(block_size) {
+        mode.decrypt_blocks(std::slice::from_mut(GenericArray::from_mut_slice(block)));
+    }
+
+    Ok(buffer)
+}
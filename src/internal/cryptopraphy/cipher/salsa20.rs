@@ -4,9 +4,14 @@ use super::{
     Encrypt,
 };
 
-use crate::results::Result;
-
-use generic_array::GenericArray;
+use crate::{
+    errors::{
+        CryptoError,
+        DatabaseIntegrityError,
+        Error
+    },
+    results::Result,
+};
 
 use salsa20::{
     Salsa20,
@@ -16,18 +21,26 @@ use salsa20::{
     }
 };
 
-#[derive(Debug)]
+/// Salsa20 keystream that continues across calls, KeePass encrypts all protected
+/// values of a database with one running stream
 pub struct Salsa20Cipher {
-    key: Vec<u8>,
-    iv: Vec<u8>
+    cipher: Salsa20,
 }
 
 impl Salsa20Cipher {
     pub fn new(key: &[u8], iv: &[u8]) -> Result<Self> {
+        let cipher = Salsa20::new_var(key, iv)
+            .map_err(|e| {
+                Error::from(
+                    DatabaseIntegrityError::from(
+                        CryptoError::from(e)
+                    )
+                )
+            })?;
+
         Ok(
             Salsa20Cipher {
-                key: key.to_vec(),
-                iv: iv.to_vec()
+                cipher
             }
         )
     }
@@ -37,19 +50,13 @@ impl Salsa20Cipher {
         Self::new(key, iv.as_ref())
     }
 }
-/*
 
- */
 impl Decrypt for Salsa20Cipher {
 
     #[inline(always)]
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
-        let mut cipher = Salsa20::new(
-            GenericArray::from_slice(self.key.as_slice()),
-                GenericArray::from_slice(self.iv.as_slice())
-        );
         let mut buffer = Vec::from(ciphertext);
-        cipher.decrypt(&mut buffer);
+        self.cipher.decrypt(&mut buffer);
         Ok(buffer)
     }
 }
@@ -58,12 +65,8 @@ impl Encrypt for Salsa20Cipher {
 
     #[inline(always)]
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let mut cipher = Salsa20::new(
-            GenericArray::from_slice(self.key.as_slice()),
-            GenericArray::from_slice(self.iv.as_slice())
-        );
         let mut buffer = Vec::from(plaintext);
-        cipher.encrypt(&mut buffer);
+        self.cipher.encrypt(&mut buffer);
         Ok(buffer)
     }
 }
@@ -75,6 +78,22 @@ mod tests {
     use super::*;
     use spectral::prelude::*;
 
+    #[test]
+    fn test_keystream_continues_across_calls() {
+        let key = [0u8; 32];
+        let iv = [1u8; 8];
+
+        let mut whole = Salsa20Cipher::new(key.as_ref(), iv.as_ref()).unwrap();
+        let mut parts = Salsa20Cipher::new(key.as_ref(), iv.as_ref()).unwrap();
+
+        let encrypted = whole.encrypt(b"first valuesecond value").unwrap();
+
+        let mut pieces = parts.encrypt(b"first value").unwrap();
+        pieces.extend(parts.encrypt(b"second value").unwrap());
+
+        assert_that(&pieces).is_equal_to(encrypted);
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let key = [0u8; 32];
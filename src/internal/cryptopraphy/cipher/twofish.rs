@@ -4,33 +4,22 @@ use super::{
     Encrypt,
 };
 
-use crate::{
-    errors::{
-        CryptoError,
-        DatabaseIntegrityError,
-        Error
-    },
-    results::Result,
-};
-
-use block_modes::{
-    BlockMode,
-    Cbc,
-    block_padding::Pkcs7,
-};
+use crate::results::Result;
 
 use twofish::Twofish;
+use zeroize::Zeroize;
 
-type TwofishCbc = Cbc<Twofish, Pkcs7>;
-
-#[derive(Debug)]
+/// CBC mode cipher, the key is wiped on drop
 pub struct TwofishCipher {
     key: Vec<u8>,
     iv: Vec<u8>,
 }
 
 impl TwofishCipher {
-    pub fn new(key: &[u8], iv: &[u8]) -> Result<Self> {
+    pub fn new(
+        key: &[u8],
+        iv: &[u8],
+    ) -> Result<Self> {
         Ok(
             TwofishCipher {
                 key: Vec::from(key),
@@ -44,15 +33,11 @@ impl Decrypt for TwofishCipher {
 
     #[inline(always)]
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
-        let cipher = TwofishCbc::new_var(&self.key, &self.iv)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
+        super::cbc_decrypt::<Twofish>(&self.key, &self.iv, ciphertext)
+    }
 
-        let mut buf = ciphertext.to_vec();
-        Ok(
-            cipher
-            .decrypt_vec(&mut buf)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?
-        )
+    fn decrypt_prefix(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        super::cbc_decrypt_prefix::<Twofish>(&self.key, &self.iv, ciphertext)
     }
 }
 
@@ -60,19 +45,25 @@ impl Encrypt for TwofishCipher {
 
     #[inline(always)]
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let cipher = TwofishCbc::new_var(&self.key, &self.iv)
-            .map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
-
-        let mut buf = plaintext.to_vec();
-        Ok(
-            cipher
-                .encrypt_vec(&mut buf)
-        )
+        super::cbc_encrypt::<Twofish>(&self.key, &self.iv, plaintext)
     }
 }
 
 impl Cipher for TwofishCipher {}
 
+impl Zeroize for TwofishCipher {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.iv.zeroize();
+    }
+}
+
+impl Drop for TwofishCipher {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,4 +102,14 @@ mod tests {
                 f.starts_with(plaintext.as_bytes())
             })
     }
+
+    #[test]
+    fn test_zeroize_clears_key() {
+        let mut cipher = TwofishCipher::new([3u8; 32].as_ref(), [1u8; 16].as_ref()).unwrap();
+
+        cipher.zeroize();
+
+        assert_that(&cipher.key.is_empty()).is_true();
+        assert_that(&cipher.iv.is_empty()).is_true();
+    }
 }
@@ -2,18 +2,33 @@ use crate::results::Result;
 
 use generic_array::{
     typenum::{
+        U16,
+        U20,
         U32,
         U64,
     },
     GenericArray,
 };
 
+use md4::Md4;
+use sha1::Sha1;
 use sha2::{
     Digest,
     Sha256,
     Sha512,
 };
 
+#[inline(always)]
+pub fn sha1(elements: &[&[u8]]) -> Result<GenericArray<u8, U20>> {
+    let mut digest = Sha1::new();
+
+    for element in elements {
+        digest.update(element);
+    }
+
+    Ok(digest.finalize())
+}
+
 #[inline(always)]
 pub fn sha256(elements: &[&[u8]]) -> Result<GenericArray<u8, U32>> {
     let mut digest = Sha256::new();
@@ -36,6 +51,18 @@ pub fn sha512(elements: &[&[u8]]) -> Result<GenericArray<u8, U64>> {
     Ok(digest.finalize())
 }
 
+/// MD4 (RFC 1320), broken and only here because NTLM password hashes are built on it
+#[inline(always)]
+pub fn md4(elements: &[&[u8]]) -> Result<GenericArray<u8, U16>> {
+    let mut digest = <Md4 as md4::Digest>::new();
+
+    for element in elements {
+        md4::Digest::update(&mut digest, element);
+    }
+
+    Ok(md4::Digest::finalize(digest))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,4 +150,30 @@ mod tests {
         assert_that(&subject1)
             .is_not_equal_to(subject2);
     }
+
+    #[test]
+    fn test_sha1() {
+        let result = sha1(&[b"ab", b"c"]).unwrap();
+
+        assert_that(&result.as_slice())
+            .is_equal_to(decode("a9993e364706816aba3e25717850c26c9cd0d89d").unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_md4_rfc1320() {
+        let vectors: &[(&[u8], &str)] = &[
+            (b"", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            (b"abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            (b"message digest", "d9130a8164549fe818874806e1c7014b"),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+            ),
+        ];
+
+        for (message, expected) in vectors {
+            assert_that(&md4(&[message]).unwrap().as_slice())
+                .is_equal_to(decode(expected).unwrap().as_slice());
+        }
+    }
 }
@@ -8,7 +8,11 @@ use crate::{
 };
 
 use generic_array::{
-    typenum::U32,
+    typenum::{
+        U20,
+        U32,
+        U64,
+    },
     GenericArray,
 };
 
@@ -18,12 +22,30 @@ use hmac::{
     NewMac,
 };
 
-use sha2::Sha256;
+use sha1::Sha1;
+use sha2::{
+    Sha256,
+    Sha512,
+};
 
 #[inline(always)]
 pub fn hmac(elements: &[&[u8]], key: &[u8]) -> Result<GenericArray<u8, U32>> {
-    type HmacSha256 = Hmac<Sha256>;
-    let mut mac = HmacSha256::new_varkey(key)
+    mac::<Hmac<Sha256>>(elements, key)
+}
+
+/// HMAC-SHA-1, only for protocols that mandate it like HOTP
+#[inline(always)]
+pub fn hmac_sha1(elements: &[&[u8]], key: &[u8]) -> Result<GenericArray<u8, U20>> {
+    mac::<Hmac<Sha1>>(elements, key)
+}
+
+#[inline(always)]
+pub fn hmac_sha512(elements: &[&[u8]], key: &[u8]) -> Result<GenericArray<u8, U64>> {
+    mac::<Hmac<Sha512>>(elements, key)
+}
+
+fn mac<M: Mac + NewMac>(elements: &[&[u8]], key: &[u8]) -> Result<GenericArray<u8, M::OutputSize>> {
+    let mut mac = M::new_varkey(key)
         .map_err(|e|
             Error::from(DatabaseIntegrityError::from(CryptoError::from(e)))
         )?;
@@ -132,4 +154,23 @@ mod tests {
         assert_that(&verified_result1)
             .is_not_equal_to(verified_result2);
     }
+
+    #[test]
+    fn test_hmac_sha1_rfc2202() {
+        let result = hmac_sha1(&[b"what do ya want for nothing?"], b"Jefe");
+
+        assert_that(&hex::encode(result.unwrap()))
+            .is_equal_to("effcdf6ae5eb2fa2d27416d5f184df9c259a7c79".to_owned());
+    }
+
+    #[test]
+    fn test_hmac_sha512_rfc4231() {
+        let result = hmac_sha512(&[b"what do ya want ", b"for nothing?"], b"Jefe");
+
+        assert_that(&hex::encode(result.unwrap()))
+            .is_equal_to(
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+                    .to_owned()
+            );
+    }
 }
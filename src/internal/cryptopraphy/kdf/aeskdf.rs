@@ -1,13 +1,10 @@
-use aes::Aes256;
-use block_modes::{
-    block_padding::ZeroPadding, // for KDF ZeroPadding is used
-    BlockMode,
-    Ecb,
-};
-use futures::{
-    executor::block_on,
-    join
+use aes::{
+    Aes256,
+    BlockCipher,
+    NewBlockCipher,
 };
+use block_modes::InvalidKeyIvLength;
+use zeroize::Zeroize;
 
 use crate::{
     errors::{
@@ -17,7 +14,10 @@ use crate::{
     },
     results::Result,
 };
-use crate::internal::cryptopraphy::hash::sha256;
+use crate::internal::cryptopraphy::{
+    hash::sha256,
+    wipe::into_secret,
+};
 
 use super::{
     GenericArray,
@@ -25,16 +25,12 @@ use super::{
     typenum,
 };
 
-type Mode = Ecb<Aes256, ZeroPadding>;
-
 pub struct AesKdf {
     seed: Vec<u8>,
     rounds: u64,
 }
 
 impl AesKdf {
-    const IV: [u8; 16] = [0u8; 16];
-
     pub fn new(
         seed: &[u8],
         rounds: u64,
@@ -45,35 +41,34 @@ impl AesKdf {
         }
     }
 
-    #[inline(always)]
-    async fn transform_key_raw(
-        &self,
-        raw_key: &[u8],
-    ) -> Result<Vec<u8>> {
-        let mut key = Vec::from(raw_key);
-        let key_len = raw_key.len();
-
-        let cipher =
-            Mode::new_var(
-                self.seed.as_ref(),
-                Self::IV.as_ref(),
-            ).map_err(|e| Error::from(DatabaseIntegrityError::from(CryptoError::from(e))))?;
-
-        for _ in 0..self.rounds {
-            let res = cipher
-                .clone()
-                .encrypt(
-                    &mut key,
-                    key_len
-                );
-            if  res.is_err() {
-                res.map_err(|e| Error::from(
-                    DatabaseIntegrityError::from(CryptoError::from(e))
-                ))?;
+    /// Encrypts a single 16 byte block `rounds` times in ECB mode, using AES-NI if the CPU
+    /// supports it even when the crate was not compiled with the `aes` target feature.
+    fn transform_block(
+        seed: &GenericArray<u8, typenum::U32>,
+        rounds: u64,
+        block: &mut GenericArray<u8, typenum::U16>,
+    ) {
+        #[cfg(all(feature = "aesni", any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            if is_x86_feature_detected!("aes") {
+                return Self::encrypt_rounds(aesni::Aes256::new(seed), rounds, block);
             }
         }
 
-        Ok(key.clone())
+        Self::encrypt_rounds(Aes256::new(seed), rounds, block)
+    }
+
+    #[inline(always)]
+    fn encrypt_rounds<C>(
+        cipher: C,
+        rounds: u64,
+        block: &mut GenericArray<u8, typenum::U16>,
+    )
+        where C: BlockCipher<BlockSize = typenum::U16>
+    {
+        for _ in 0..rounds {
+            cipher.encrypt_block(block);
+        }
     }
 }
 
@@ -82,14 +77,46 @@ impl Kdf for AesKdf {
         &self,
         composite_key: &GenericArray<u8, typenum::U32>,
     ) -> Result<GenericArray<u8, typenum::U32>> {
-        let (_left, _right) = composite_key.split_at(composite_key.len() / 2);
+        if self.seed.len() != 32 {
+            return Err(
+                Error::from(
+                    DatabaseIntegrityError::from(
+                        CryptoError::from(InvalidKeyIvLength)
+                    )
+                )
+            );
+        }
 
-        let future_key_left = self.transform_key_raw(_left);
-        let future_key_right = self.transform_key_raw(_right);
+        let mut seed = *GenericArray::<u8, typenum::U32>::from_slice(&self.seed);
+        let rounds = self.rounds;
 
-        let (key_left, key_right) = block_on(async { join!(future_key_left, future_key_right) });
+        let mut key_left = *GenericArray::<u8, typenum::U16>::from_slice(&composite_key[..16]);
+        let mut key_right = *GenericArray::<u8, typenum::U16>::from_slice(&composite_key[16..]);
 
-        sha256(&[&key_left?, &key_right?])
+        // both halves are independent - transform the left one on a second thread, which
+        // gets its own copies of the seed and the half and wipes them before it returns
+        let left = std::thread::spawn(move || {
+            Self::transform_block(&seed, rounds, &mut key_left);
+            let transformed = into_secret(key_left);
+
+            seed.as_mut_slice().zeroize();
+            key_left.as_mut_slice().zeroize();
+
+            transformed
+        });
+
+        Self::transform_block(&seed, rounds, &mut key_right);
+
+        let key_left = left
+            .join()
+            .expect("AES-KDF worker thread panicked");
+
+        let transformed_key = sha256(&[&key_left, &key_right]);
+
+        seed.as_mut_slice().zeroize();
+        key_right.as_mut_slice().zeroize();
+
+        transformed_key
     }
 }
 
@@ -180,6 +207,19 @@ mod tests {
             );
     }
 
+    #[test]
+    fn test_transform_key_invalid_seed() {
+        let key = [1u8; 32];
+
+        let algo = AesKdf::new(
+            [2u8; 16].as_ref(),
+            100,
+        );
+
+        assert_that(&algo.transform_key(GenericArray::from_slice(&key)))
+            .is_err();
+    }
+
     fn _test_transform_key_impl(rounds: u64) -> Result<GenericArray<u8, typenum::U32>> {
         let key = [1u8; 32].to_vec();
 
@@ -14,6 +14,9 @@ use crate::{
 };
 
 use argon2;
+use zeroize::Zeroizing;
+
+use std::convert::TryFrom;
 
 pub struct Argon2Kdf {
     pub memory: u64,
@@ -31,14 +34,35 @@ impl Kdf for Argon2Kdf {
         composite_key: &GenericArray<u8, typenum::U32>,
     ) -> Result<GenericArray<u8, typenum::U32>> {
 
+        // argon2 takes the memory in KiB, refuse values we would otherwise truncate
+        let mem_cost = u32::try_from(self.memory / 1024)
+            .map_err(|_| Error::KdfResourceLimit {
+                parameter: "memory".to_owned(),
+                value: self.memory,
+                limit: u64::from(u32::MAX) * 1024,
+            })?;
+        let time_cost = u32::try_from(self.iterations)
+            .map_err(|_| Error::KdfResourceLimit {
+                parameter: "iterations".to_owned(),
+                value: self.iterations,
+                limit: u64::from(u32::MAX),
+            })?;
+
+        // lanes are independent within a segment, so each one gets its own thread
+        let thread_mode = if self.lanes > 1 {
+            argon2::ThreadMode::Parallel
+        } else {
+            argon2::ThreadMode::Sequential
+        };
+
         let config = argon2::Config {
             ad: &[],
             hash_length: 32,
             lanes: self.lanes,
-            mem_cost: (self.memory / 1024) as u32,
+            mem_cost,
             secret: &[],
-            thread_mode: argon2::ThreadMode::from_threads(self.lanes),
-            time_cost: self.iterations as u32,
+            thread_mode,
+            time_cost,
             variant: argon2::Variant::Argon2d,
             version: self.version,
         };
@@ -48,6 +72,7 @@ impl Kdf for Argon2Kdf {
             &self.salt,
             &config
         )
+            .map(Zeroizing::new)
             .map_err(|e| Error::from(
                 DatabaseIntegrityError::from(
                     CryptoError::from(e)
@@ -96,6 +121,50 @@ mod tests {
         assert_that(&result).is_ok();
     }
 
+    #[test]
+    fn test_transform_key_parallel_lanes_match_sequential() {
+        let key = [3u8; 32];
+        let salt = [1u8; 16];
+
+        let result = Argon2Kdf {
+            memory: 1024 * 1024,
+            salt: salt.to_vec(),
+            iterations: 2,
+            lanes: 4,
+            version: argon2::Version::Version13,
+        }.transform_key(GenericArray::from_slice(&key));
+
+        let sequential = argon2::hash_raw(
+            &key,
+            &salt,
+            &argon2::Config {
+                lanes: 4,
+                mem_cost: 1024,
+                time_cost: 2,
+                thread_mode: argon2::ThreadMode::Sequential,
+                variant: argon2::Variant::Argon2d,
+                version: argon2::Version::Version13,
+                ..argon2::Config::default()
+            },
+        ).unwrap();
+
+        assert_that(&result)
+            .is_ok()
+            .is_equal_to(*GenericArray::from_slice(&sequential));
+    }
+
+    #[test]
+    fn test_transform_key_rejects_truncated_iterations() {
+        let result = _run_transform_key(u64::from(u32::MAX) + 1, LANES);
+
+        assert_that(&result)
+            .is_err()
+            .matches(|e| match e {
+                Error::KdfResourceLimit { parameter, .. } => parameter == "iterations",
+                _ => false,
+            });
+    }
+
     #[inline(always)]
     fn _run_transform_key(
         iterations: u64,
@@ -2,6 +2,7 @@ pub mod cipher;
 pub mod kdf;
 pub mod hash;
 pub mod hmac;
+pub(crate) mod wipe;
 
 pub(crate) use self::{
     cipher::*,
@@ -0,0 +1,39 @@
+use generic_array::{
+    ArrayLength,
+    GenericArray,
+};
+use zeroize::{
+    Zeroize,
+    Zeroizing,
+};
+
+/// Key material that is wiped when it goes out of scope
+pub(crate) type SecretBytes = Zeroizing<Vec<u8>>;
+
+/// Moves a derived key into a `SecretBytes` and wipes the original array
+pub(crate) fn into_secret<N>(mut value: GenericArray<u8, N>) -> SecretBytes
+    where N: ArrayLength<u8>
+{
+    let secret = Zeroizing::new(value.to_vec());
+    value.as_mut_slice().zeroize();
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_into_secret() {
+        let key = GenericArray::from([7u8; 32]);
+
+        let mut secret = into_secret(key);
+
+        assert_that(&secret.as_slice()).is_equal_to([7u8; 32].as_ref());
+
+        secret.zeroize();
+
+        assert_that(&secret.is_empty()).is_true();
+    }
+}
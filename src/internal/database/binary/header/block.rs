@@ -172,6 +172,11 @@ impl TryFromBytes for HeaderBlock4 {
 
         let block_id = value[0];
         let block_size = LittleEndian::read_u32(&value[1..5]);
+
+        if block_size as usize > value.len() - Self::OFFSET {
+            return Err(())
+        }
+
         let mut block_data = vec![];
 
         if block_size > 0 {
@@ -24,6 +24,8 @@ pub(crate) const DH_BLOCKID_STREAMSTARTBYTES: u8 = 0x09;
 pub(crate) const DH_BLOCKID_INNERRANDOMSTREAMID: u8 = 0x0a;
 /// KDFPARAMETERS - parameters for the key derivation function
 pub(crate) const DH_BLOCKID_KDFPARAMETERS: u8 = 0x0b;
+/// PUBLICCUSTOMDATA - unencrypted custom data of plugins and clients (KDBX4 only)
+pub(crate) const DH_BLOCKID_PUBLICCUSTOMDATA: u8 = 0x0c;
 
 pub(crate) const DH_INNER_BLOCKID_END: u8 = 0x00;
 pub(crate) const DH_INNER_BLOCKID_RANDOM_STREAM_ID: u8 = 0x01;
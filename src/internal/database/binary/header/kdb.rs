@@ -2,7 +2,10 @@ use crate::{
     api::kdb::KDBHeader,
     errors::DatabaseIntegrityError,
     results::Result,
-    internal::database::binary::version::get_kdbx_version,
+    internal::database::binary::version::{
+        get_kdbx_version,
+        KDBX_IDENTIFIER,
+    },
 };
 
 use byteorder::{
@@ -12,6 +15,14 @@ use byteorder::{
 
 pub const HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 16 + 16 + 4 + 4 + 32 + 32 + 4; // first 4 bytes are the KeePass magic
 
+/// Header flags, the contents hash is always SHA-256
+pub(crate) const FLAG_SHA2: u32 = 1;
+pub(crate) const FLAG_RIJNDAEL: u32 = 2;
+pub(crate) const FLAG_TWOFISH: u32 = 8;
+
+/// The file format version written by KeePass 1.x
+pub(crate) const KDB_VERSION: u32 = 0x0003_0004;
+
 pub(crate) fn read_header(data: &[u8]) -> Result<KDBHeader> {
     let (version, _, _) = get_kdbx_version(data)?;
 
@@ -43,3 +54,59 @@ pub(crate) fn read_header(data: &[u8]) -> Result<KDBHeader> {
         }
     )
 }
+
+pub(crate) fn write_header(header: &KDBHeader) -> Vec<u8> {
+    let mut res = Vec::with_capacity(HEADER_SIZE);
+
+    res.extend_from_slice(KDBX_IDENTIFIER.as_ref());
+    res.extend_from_slice(&header.version.to_le_bytes());
+    res.extend_from_slice(&header.flags.to_le_bytes());
+    res.extend_from_slice(&header.subversion.to_le_bytes());
+    res.extend_from_slice(&header.master_seed);
+    res.extend_from_slice(&header.encryption_iv);
+    res.extend_from_slice(&header.num_groups.to_le_bytes());
+    res.extend_from_slice(&header.num_entries.to_le_bytes());
+    res.extend_from_slice(&header.contents_hash);
+    res.extend_from_slice(&header.transform_seed);
+    res.extend_from_slice(&header.transform_rounds.to_le_bytes());
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_write_read_header() {
+        let header = KDBHeader {
+            version: 0xb54b_fb65,
+            flags: FLAG_SHA2 | FLAG_RIJNDAEL,
+            subversion: KDB_VERSION,
+            master_seed: vec![1u8; 16],
+            encryption_iv: vec![2u8; 16],
+            num_groups: 3,
+            num_entries: 4,
+            contents_hash: vec![5u8; 32],
+            transform_seed: vec![6u8; 32],
+            transform_rounds: 6000,
+        };
+
+        let serialized = write_header(&header);
+
+        assert_that(&serialized.len()).is_equal_to(HEADER_SIZE);
+
+        let deserialized = read_header(&serialized).unwrap();
+
+        assert_that(&deserialized.flags).is_equal_to(header.flags);
+        assert_that(&deserialized.subversion).is_equal_to(KDB_VERSION);
+        assert_that(&deserialized.master_seed).is_equal_to(&header.master_seed);
+        assert_that(&deserialized.encryption_iv).is_equal_to(&header.encryption_iv);
+        assert_that(&deserialized.num_groups).is_equal_to(3);
+        assert_that(&deserialized.num_entries).is_equal_to(4);
+        assert_that(&deserialized.contents_hash).is_equal_to(&header.contents_hash);
+        assert_that(&deserialized.transform_seed).is_equal_to(&header.transform_seed);
+        assert_that(&deserialized.transform_rounds).is_equal_to(6000);
+    }
+}
@@ -17,7 +17,10 @@ use crate::{
             BlockId,
             header::block,
             header::constants,
-            version::get_kdbx_version,
+            version::{
+                get_kdbx_version,
+                KDBX_IDENTIFIER,
+            },
         },
     },
 };
@@ -29,10 +32,24 @@ use byteorder::{
 
 use std::convert::TryFrom;
 
+use zeroize::Zeroize;
+
+impl Drop for KDBX3Header {
+    fn drop(&mut self) {
+        // decrypts every protected value of the database
+        self.protected_stream_key.zeroize();
+    }
+}
+
+/// Whether a file version predates KDBX 3.1, as written by KeePass before 2.20
+pub(crate) fn is_legacy_version(file_major_version: u16, file_minor_version: u16) -> bool {
+    file_major_version < 3 || (file_major_version == 3 && file_minor_version == 0)
+}
+
 pub(crate) fn read_header(data: &[u8]) -> Result<KDBX3Header> {
     let (version, file_major_version, file_minor_version) = get_kdbx_version(data)?;
 
-    if version != 0xb54b_fb67 || file_major_version != 3 {
+    if version != 0xb54b_fb67 || !(2..=3).contains(&file_major_version) {
         return Err(DatabaseIntegrityError::InvalidKDBXVersion {
             version,
             file_major_version,
@@ -131,8 +148,20 @@ pub(crate) fn read_header(data: &[u8]) -> Result<KDBX3Header> {
     let transform_rounds = get_or_err(transform_rounds, "Number of transformation rounds")?;
     let outer_iv = get_or_err(outer_iv, "Outer cipher IV")?;
     let protected_stream_key = get_or_err(protected_stream_key, "Protected stream key")?;
-    let stream_start = get_or_err(stream_start, "Stream start bytes")?;
-    let inner_cipher = get_or_err(inner_cipher, "Inner cipher ID")?;
+
+    // KeePass before 2.20 (file versions 2.x and 3.0) did not insist on these, it checked the
+    // stream start bytes only if present and fell back to the ArcFour variant stream
+    let (stream_start, inner_cipher) = if is_legacy_version(file_major_version, file_minor_version) {
+        (
+            stream_start.unwrap_or_default(),
+            inner_cipher.unwrap_or(InnerCipherSuite::ArcFourVariant),
+        )
+    } else {
+        (
+            get_or_err(stream_start, "Stream start bytes")?,
+            get_or_err(inner_cipher, "Inner cipher ID")?,
+        )
+    };
 
     Ok(
         KDBX3Header {
@@ -153,3 +182,135 @@ pub(crate) fn read_header(data: &[u8]) -> Result<KDBX3Header> {
     )
 }
 
+pub(crate) fn write_header(header: &KDBX3Header) -> Vec<u8> {
+    let mut res = Vec::new();
+
+    res.extend_from_slice(KDBX_IDENTIFIER.as_ref());
+    res.extend_from_slice(&header.version.to_le_bytes());
+    res.extend_from_slice(&header.file_minor_version.to_le_bytes());
+    res.extend_from_slice(&header.file_major_version.to_le_bytes());
+
+    let blocks = [
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_CIPHERID,
+            header.outer_cipher.to_vec(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_COMPRESSIONFLAGS,
+            header.compression.to_code().to_le_bytes().to_vec(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_MASTERSEED,
+            header.master_seed.clone(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_TRANSFORMSEED,
+            header.transform_seed.clone(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_TRANSFORMROUNDS,
+            header.transform_rounds.to_le_bytes().to_vec(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_ENCRYPTIONIV,
+            header.outer_iv.clone(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_PROTECTEDSTREAMKEY,
+            header.protected_stream_key.clone(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_STREAMSTARTBYTES,
+            header.stream_start.clone(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_INNERRANDOMSTREAMID,
+            header.inner_cipher.to_code().to_le_bytes().to_vec(),
+        ),
+        block::HeaderBlock3::new(
+            constants::DH_BLOCKID_END,
+            b"\r\n\r\n".to_vec(),
+        ),
+    ];
+
+    blocks
+        .iter()
+        .for_each(|block| {
+            res.extend_from_slice(
+                binary::write::<block::HeaderBlock3>(block).as_ref()
+            );
+        });
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    // written without an InnerRandomStreamID field, like KeePass 2.0x did
+    const LEGACY_FIXTURE: &[u8] = include_bytes!("../../../../../tests/fixture/legacy-aes256-arcfour-v20.kdbx");
+
+    #[test]
+    fn test_is_legacy_version() {
+        assert_that(&is_legacy_version(2, 0)).is_true();
+        assert_that(&is_legacy_version(3, 0)).is_true();
+        assert_that(&is_legacy_version(3, 1)).is_false();
+    }
+
+    #[test]
+    fn test_read_legacy_header_defaults_to_arcfour_variant() {
+        let result = read_header(LEGACY_FIXTURE);
+
+        let header = assert_that(&result)
+            .is_ok()
+            .subject;
+
+        assert_that(&header.file_major_version).is_equal_to(2);
+        assert_that(&matches!(header.inner_cipher, InnerCipherSuite::ArcFourVariant)).is_true();
+        assert_that(&header.stream_start.len()).is_equal_to(32);
+    }
+
+    #[test]
+    fn test_write_read_header() {
+        let header = KDBX3Header {
+            version: 0xb54b_fb67,
+            file_major_version: 3,
+            file_minor_version: 1,
+            outer_cipher: OuterCipherSuite::Twofish,
+            compression: Compression::GZip,
+            master_seed: vec![1u8; 32],
+            transform_seed: vec![2u8; 32],
+            transform_rounds: 6000,
+            outer_iv: vec![3u8; 16],
+            protected_stream_key: vec![4u8; 32],
+            stream_start: vec![5u8; 32],
+            inner_cipher: InnerCipherSuite::Salsa20,
+            body_start: 0,
+        };
+
+        let serialized = write_header(&header);
+        let parsed = read_header(&serialized).unwrap();
+
+        assert_that(&parsed.body_start).is_equal_to(serialized.len());
+        assert_that(&parsed.file_minor_version).is_equal_to(1);
+        assert_that(&matches!(parsed.outer_cipher, OuterCipherSuite::Twofish)).is_true();
+        assert_that(&matches!(parsed.compression, Compression::GZip)).is_true();
+        assert_that(&parsed.transform_rounds).is_equal_to(6000);
+        assert_that(&parsed.outer_iv).is_equal_to(header.outer_iv.clone());
+        assert_that(&parsed.protected_stream_key).is_equal_to(header.protected_stream_key.clone());
+        assert_that(&parsed.stream_start).is_equal_to(header.stream_start.clone());
+        assert_that(&matches!(parsed.inner_cipher, InnerCipherSuite::Salsa20)).is_true();
+    }
+
+    #[test]
+    fn test_read_header_requires_inner_cipher_from_3_1() {
+        let mut data = LEGACY_FIXTURE.to_vec();
+        data[8..12].copy_from_slice(&[0x01, 0x00, 0x03, 0x00]);
+
+        let result = read_header(&data);
+
+        assert_that(&result).is_err();
+    }
+}
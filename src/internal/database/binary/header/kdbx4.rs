@@ -20,16 +20,17 @@ use crate::{
     results::Result,
     internal::{
         database::binary::{
+            self,
             BlockData,
             BlockId,
             header::block,
             header::constants,
-            version::get_kdbx_version,
+            version::{
+                get_kdbx_version,
+                KDBX_IDENTIFIER,
+            },
         },
         structures::variant_dictionary::VariantDictionary,
-        traits::{
-            TryFromBytes
-        }
     },
 };
 
@@ -40,6 +41,15 @@ use byteorder::{
 
 use std::convert::TryFrom;
 
+use zeroize::Zeroize;
+
+impl Drop for KDBX4InnerHeader {
+    fn drop(&mut self) {
+        // decrypts every protected value of the database
+        self.inner_random_stream_key.zeroize();
+    }
+}
+
 impl TryFrom<&[u8]> for BinaryAttachment {
     type Error = Error;
 
@@ -68,13 +78,16 @@ pub(crate) fn read_outer_header(data: &[u8]) -> Result<KDBX4Header> {
     let mut master_seed: Option<Vec<u8>> = None;
     let mut outer_iv: Option<Vec<u8>> = None;
     let mut kdf: Option<KdfSettings> = None;
+    let mut public_custom_data = VariantDictionary::default();
 
     let mut pos = 12;
 
     loop {
         let block = super::super::read::<block::HeaderBlock4>(
             &data[pos..]
-        ).unwrap();
+        ).map_err(|_| Error::from(DatabaseIntegrityError::IncompleteOuterHeader {
+            missing_field: "End of header".into(),
+        }))?;
 
         pos += block.size() as usize;
 
@@ -114,6 +127,11 @@ pub(crate) fn read_outer_header(data: &[u8]) -> Result<KDBX4Header> {
 
                 kdf = Some(KdfSettings::try_from(vd)?);
             }
+            constants::DH_BLOCKID_PUBLICCUSTOMDATA => {
+                public_custom_data = VariantDictionary::from_bytes(
+                    block.block_data().as_ref()
+                )?;
+            }
             _ => {
                 return Err(
                     DatabaseIntegrityError::InvalidOuterHeaderEntry {
@@ -149,11 +167,71 @@ pub(crate) fn read_outer_header(data: &[u8]) -> Result<KDBX4Header> {
             master_seed,
             outer_iv,
             kdf,
+            public_custom_data,
             body_start: pos,
         }
     )
 }
 
+pub(crate) fn write_outer_header(header: &KDBX4Header) -> Vec<u8> {
+    let mut res = Vec::new();
+
+    res.extend_from_slice(KDBX_IDENTIFIER.as_ref());
+    res.extend_from_slice(&header.version.to_le_bytes());
+    res.extend_from_slice(&header.file_minor_version.to_le_bytes());
+    res.extend_from_slice(&header.file_major_version.to_le_bytes());
+
+    let mut blocks = vec![
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_CIPHERID,
+            header.outer_cipher.to_vec(),
+        ),
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_COMPRESSIONFLAGS,
+            header.compression.to_code().to_le_bytes().to_vec(),
+        ),
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_MASTERSEED,
+            header.master_seed.clone(),
+        ),
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_ENCRYPTIONIV,
+            header.outer_iv.clone(),
+        ),
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_KDFPARAMETERS,
+            VariantDictionary::from(&header.kdf).to_bytes(),
+        ),
+    ];
+
+    // KeePass omits the public custom data block entirely if there is nothing to store
+    if !header.public_custom_data.is_empty() {
+        blocks.push(
+            block::HeaderBlock4::new(
+                constants::DH_BLOCKID_PUBLICCUSTOMDATA,
+                header.public_custom_data.to_bytes(),
+            )
+        );
+    }
+
+    blocks.push(
+        block::HeaderBlock4::new(
+            constants::DH_BLOCKID_END,
+            b"\r\n\r\n".to_vec(),
+        )
+    );
+
+    blocks
+        .iter()
+        .for_each(|block| {
+            res.extend_from_slice(
+                binary::write::<block::HeaderBlock4>(block).as_ref()
+            );
+        });
+
+    res
+}
+
 pub(crate) fn read_inner_header(data: &[u8]) -> Result<KDBX4InnerHeader> {
     let mut pos = 0;
 
@@ -209,3 +287,158 @@ pub(crate) fn read_inner_header(data: &[u8]) -> Result<KDBX4InnerHeader> {
         }
     )
 }
+
+pub(crate) fn write_inner_header(header: &KDBX4InnerHeader) -> Vec<u8> {
+    fn write_entry(res: &mut Vec<u8>, entry_type: u8, parts: &[&[u8]]) {
+        let length: usize = parts.iter().map(|part| part.len()).sum();
+
+        res.push(entry_type);
+        res.extend_from_slice(&(length as u32).to_le_bytes());
+        parts.iter().for_each(|part| res.extend_from_slice(part));
+    }
+
+    let mut res = Vec::new();
+
+    write_entry(
+        &mut res,
+        constants::DH_INNER_BLOCKID_RANDOM_STREAM_ID,
+        &[&header.inner_random_stream.to_code().to_le_bytes()],
+    );
+    write_entry(
+        &mut res,
+        constants::DH_INNER_BLOCKID_RANDOM_STREAM_KEY,
+        &[&header.inner_random_stream_key],
+    );
+    for binary in header.binaries.iter() {
+        write_entry(
+            &mut res,
+            constants::DH_INNER_BLOCKID_BINARY_ATTACHMENT,
+            &[&[binary.flags()], binary.content()],
+        );
+    }
+    write_entry(&mut res, constants::DH_INNER_BLOCKID_END, &[]);
+
+    res
+}
+
+/// Length of the inner header at the start of `data`, `None` if it is cut off
+pub(crate) fn inner_header_length(data: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+
+    loop {
+        let entry_type = *data.get(pos)?;
+        let entry_length = LittleEndian::read_u32(data.get((pos + 1)..(pos + 5))?) as usize;
+
+        pos = pos.checked_add(5 + entry_length)?;
+        if pos > data.len() {
+            return None;
+        }
+
+        if entry_type == constants::DH_INNER_BLOCKID_END {
+            return Some(pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::structures::variant_dictionary::VariantDictionaryValue;
+    use spectral::prelude::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../../../../../tests/fixture/empty-aes256-argon2-v4.kdbx");
+
+    #[test]
+    fn test_read_outer_header_without_public_custom_data() {
+        let result = read_outer_header(FIXTURE);
+
+        let header = assert_that(&result)
+            .is_ok()
+            .subject;
+
+        assert_that(&header.public_custom_data.is_empty()).is_true();
+    }
+
+    #[test]
+    fn test_write_read_outer_header() {
+        let header = read_outer_header(FIXTURE).unwrap();
+
+        let serialized = write_outer_header(&header);
+
+        let result = read_outer_header(serialized.as_ref());
+
+        let deserialized = assert_that(&result)
+            .is_ok()
+            .subject;
+
+        assert_that(&deserialized.master_seed).is_equal_to(&header.master_seed);
+        assert_that(&deserialized.outer_iv).is_equal_to(&header.outer_iv);
+        assert_that(&deserialized.kdf.size()).is_equal_to(header.kdf.size());
+        assert_that(&deserialized.body_start).is_equal_to(serialized.len());
+    }
+
+    #[test]
+    fn test_write_read_public_custom_data() {
+        let mut header = read_outer_header(FIXTURE).unwrap();
+        header.public_custom_data.put(
+            "team",
+            VariantDictionaryValue::String("platform".to_owned()),
+        );
+        header.public_custom_data.put(
+            "color",
+            VariantDictionaryValue::UInt32(0x336699),
+        );
+
+        let serialized = write_outer_header(&header);
+
+        let deserialized = read_outer_header(serialized.as_ref()).unwrap();
+
+        assert_that(&deserialized.public_custom_data)
+            .is_equal_to(&header.public_custom_data);
+        assert_that(&deserialized.public_custom_data.get_string("team"))
+            .is_some()
+            .is_equal_to("platform");
+    }
+
+    #[test]
+    fn test_read_truncated_outer_header() {
+        let result = read_outer_header(&FIXTURE[..40]);
+
+        assert_that(&result).is_err();
+    }
+
+    #[test]
+    fn test_write_read_inner_header() {
+        let header = KDBX4InnerHeader {
+            inner_random_stream: InnerCipherSuite::ChaCha20,
+            inner_random_stream_key: vec![7u8; 64],
+            binaries: vec![
+                BinaryAttachment::new(1, b"-----BEGIN"),
+                BinaryAttachment::new(0, b""),
+            ],
+            body_start: 0,
+        };
+
+        let serialized = write_inner_header(&header);
+
+        let deserialized = read_inner_header(&serialized).unwrap();
+
+        assert_that(&matches!(deserialized.inner_random_stream, InnerCipherSuite::ChaCha20)).is_true();
+        assert_that(&deserialized.inner_random_stream_key).is_equal_to(&header.inner_random_stream_key);
+        assert_that(&deserialized.binaries.len()).is_equal_to(2);
+        assert_that(&deserialized.binaries[0].flags()).is_equal_to(1);
+        assert_that(&deserialized.binaries[0].content()).is_equal_to(b"-----BEGIN".as_ref());
+        assert_that(&deserialized.body_start).is_equal_to(serialized.len());
+        assert_that(&inner_header_length(&serialized)).is_equal_to(Some(serialized.len()));
+    }
+
+    #[test]
+    fn test_inner_header_length() {
+        let mut data = vec![constants::DH_INNER_BLOCKID_RANDOM_STREAM_ID, 4, 0, 0, 0, 3, 0, 0, 0];
+        data.extend_from_slice(&[constants::DH_INNER_BLOCKID_END, 0, 0, 0, 0]);
+        data.extend_from_slice(b"<KeePassFile>");
+
+        assert_that(&inner_header_length(&data)).is_equal_to(Some(14));
+        assert_that(&inner_header_length(&data[..12])).is_equal_to(None);
+    }
+}
@@ -8,10 +8,10 @@ use byteorder::{
     LittleEndian,
 };
 
-const KDBX_IDENTIFIER: [u8; 4] = [0x03, 0xd9, 0xa2, 0x9a];
+pub(crate) const KDBX_IDENTIFIER: [u8; 4] = [0x03, 0xd9, 0xa2, 0x9a];
 
 pub fn get_kdbx_version(data: &[u8]) -> Result<(u32, u16, u16)> {
-    if data[0..4] != KDBX_IDENTIFIER {
+    if data.len() < 12 || data[0..4] != KDBX_IDENTIFIER {
         return Err(DatabaseIntegrityError::InvalidKDBXIdentifier.into());
     }
 
@@ -10,6 +10,9 @@ pub(super) enum Node {
     Entry(Entry),
     Group(Group),
     KeyValue(String, StringValue),
+    /// An attachment of an entry, `None` until its `Value` is resolved
+    Attachment(String, Option<Vec<u8>>),
     AutoType(AutoType),
     AutoTypeAssociation(AutoTypeAssociation),
+    DeletedObject(DeletedObject),
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::{
+    api::compression::Compression,
     errors::{
         DatabaseIntegrityError,
         Error,
@@ -14,16 +15,32 @@ use secstr::SecStr;
 use quick_xml::{
     Reader,
     events::{
+        BytesStart,
         Event,
         attributes::Attribute,
     },
 };
-use std::io::Read;
+use byteorder::{
+    ByteOrder,
+    LittleEndian,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    Utc,
+};
+use std::collections::HashMap;
 
+/// Parses the XML document of a KDBX database into its metadata, root group and the tombstones
+/// of `<Root><DeletedObjects>`. Standard fields are protected in memory as the
+/// `MemoryProtection` settings of the document say. Attachments reference the
+/// `<Meta><Binaries>` of KDBX 3 by ID, or `attachments` from the KDBX 4 inner header by position.
 pub(crate) fn parse(
     xml: &[u8],
     inner_cipher: &mut dyn Cipher,
-) -> Result<Group>
+    attachments: &[&[u8]],
+) -> Result<(Meta, Group, Vec<DeletedObject>)>
 {
     let mut parser = Reader::from_reader(xml);
 
@@ -31,6 +48,12 @@ pub(crate) fn parse(
     let mut stack: Vec<Vec<u8>> = vec![];
     let mut parsed_stack: Vec<Node> = vec![];
     let mut root_group: Group = Default::default();
+    let mut meta = Meta::default();
+    let mut deleted_objects: Vec<DeletedObject> = vec![];
+    // the KDBX 3 attachment pool and the ID and compression of the one being read
+    let mut meta_binaries: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut meta_binary: Option<(String, bool)> = None;
+    let mut custom_icon: (Option<UuidValue>, Vec<u8>) = (None, vec![]);
 
     loop {
         match parser.read_event(&mut buf) {
@@ -65,26 +88,56 @@ pub(crate) fn parse(
                             )
                             .find(|attr| attr.key == b"Protected")
                             .map(|attr| attr.value)
-                            .map_or(false, |v| {
-                                std::str::from_utf8(&v).expect("").parse::<bool>().unwrap_or_default()
+                            .is_some_and(|v| {
+                                // KeePass writes "True"
+                                std::str::from_utf8(&v).is_ok_and(|v| v.eq_ignore_ascii_case("true"))
                             })
                         {
                             // Transform value to a Value::Protected
                             if let Some(&mut Node::KeyValue(_, ref mut ev)) =
                             parsed_stack.last_mut()
                             {
-                                *ev = StringValue::ProtectedString(SecStr::new(vec![]));
+                                *ev = StringValue::ProtectedString(ProtectedValue::default());
+                            }
+                        }
+
+                        if let Some(&mut Node::Attachment(_, ref mut content)) = parsed_stack.last_mut() {
+                            if let Some(reference) = attribute(e, b"Ref") {
+                                *content = resolve_attachment(&reference, &meta_binaries, attachments);
                             }
                         }
                     }
+                    b"Binary" => {
+                        if let Some(&Node::Entry(_)) = parsed_stack.last() {
+                            parsed_stack.push(Node::Attachment(String::new(), None));
+                        } else if stack.len() > 1 && stack[stack.len() - 2] == b"Binaries" {
+                            meta_binary = Some((
+                                attribute(e, b"ID").unwrap_or_default(),
+                                attribute(e, b"Compressed").is_some_and(|value| value.eq_ignore_ascii_case("true")),
+                            ));
+                        }
+                    }
+                    b"Icon" => custom_icon = (None, vec![]),
                     b"AutoType" => parsed_stack.push(Node::AutoType(Default::default())),
                     b"Association" => {
                         parsed_stack.push(Node::AutoTypeAssociation(Default::default()))
                     }
+                    b"DeletedObject" => parsed_stack.push(Node::DeletedObject(Default::default())),
                     _ => {}
                 }
             }
 
+            Ok(Event::Empty(ref e)) => {
+                // KeePass writes attachment references as `<Value Ref="0" />`
+                if let (b"Value", Some(&mut Node::Attachment(_, ref mut content))) =
+                    (e.local_name(), parsed_stack.last_mut())
+                {
+                    if let Some(reference) = attribute(e, b"Ref") {
+                        *content = resolve_attachment(&reference, &meta_binaries, attachments);
+                    }
+                }
+            }
+
             Ok(Event::End(ref e)) => {
                 stack.pop();
                 let local_name = e.local_name();
@@ -93,9 +146,22 @@ pub(crate) fn parse(
                     | b"Entry"
                     | b"String"
                     | b"AutoType"
-                    | b"Association" => true,
+                    | b"Association"
+                    | b"DeletedObject" => true,
+                    b"Binary" => matches!(parsed_stack.last(), Some(Node::Attachment(..))),
                     _ => false
                 };
+
+                match local_name {
+                    b"Binary" => meta_binary = None,
+                    b"Icon" if stack.last().map(|s| &s[..]) == Some(b"CustomIcons") => {
+                        if let Some(uuid) = custom_icon.0.take() {
+                            meta.add_custom_icon(&IconValue::new(&uuid, &custom_icon.1));
+                        }
+                    }
+                    _ => {}
+                }
+
                 if local_name_matches {
                     let finished_node = parsed_stack.pop().unwrap();
                     let parsed_stack_head = parsed_stack.last_mut();
@@ -139,12 +205,20 @@ pub(crate) fn parse(
                             {
                                 // A Entry was finished - add Node to parent Group's entries
                                 entries.insert(
-                                    finished_entry.get_title().unwrap().to_owned(),
+                                    finished_entry.title().unwrap().to_string(),
                                     finished_entry,
                                 );
                             }
                         }
 
+                        Node::Attachment(name, content) => {
+                            if let (Some(&mut Node::Entry(ref mut entry)), Some(content)) =
+                                (parsed_stack_head, content)
+                            {
+                                entry.set_binary(&Binary::new(&name, false, &content));
+                            }
+                        }
+
                         Node::AutoType(at) => {
                             if let Some(
                                 &mut Node::Entry(
@@ -165,6 +239,10 @@ pub(crate) fn parse(
                                 autotype.associations.push(ata);
                             }
                         }
+
+                        Node::DeletedObject(deleted_object) => {
+                            deleted_objects.push(deleted_object);
+                        }
                     }
                 }
             }
@@ -209,15 +287,201 @@ pub(crate) fn parse(
                                 let buf = base64::decode(&c)
                                     .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))?;
 
-                                let buf_decode = inner_cipher.decrypt(&buf)?;
+                                // wiped as soon as it is re-encrypted with the process key
+                                let buf_decode = SecStr::new(inner_cipher.decrypt(&buf)?);
 
-                                let c_decode = std::str::from_utf8(&buf_decode)
+                                std::str::from_utf8(buf_decode.unsecure())
                                     .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))?;
 
-                                *v = SecStr::from(c_decode);
+                                *v = ProtectedValue::new(buf_decode.unsecure());
                             }
                         }
                     }
+                    (
+                        Some(flag),
+                        None
+                    ) if stack.len() > 1 && stack[stack.len() - 2] == b"MemoryProtection" => {
+                        // Got a flag inside of Meta/MemoryProtection
+                        let value = c.eq_ignore_ascii_case("true");
+
+                        match flag {
+                            b"ProtectTitle" => { meta.set_protect_title(value); }
+                            b"ProtectUserName" => { meta.set_protect_user_name(value); }
+                            b"ProtectPassword" => { meta.set_protect_password(value); }
+                            b"ProtectURL" => { meta.set_protect_url(value); }
+                            b"ProtectNotes" => { meta.set_protect_notes(value); }
+                            _ => {}
+                        }
+                    }
+                    (
+                        Some(field),
+                        None
+                    ) if stack.len() > 1 && stack[stack.len() - 2] == b"Meta" => {
+                        set_meta_field(&mut meta, field, &c);
+                    }
+                    (
+                        Some(b"Binary"),
+                        None
+                    ) if meta_binary.is_some() => {
+                        let (id, compressed) = meta_binary.take().unwrap_or_default();
+                        let content = base64::decode(&c)
+                            .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))?;
+                        let content = if compressed {
+                            Compression::GZip.get_decompression().decompress(&content)?
+                        } else {
+                            content
+                        };
+                        meta_binaries.insert(id, content);
+                    }
+                    (
+                        Some(field),
+                        None
+                    ) if stack.len() > 1 && stack[stack.len() - 2] == b"Icon" => {
+                        match field {
+                            b"UUID" => custom_icon.0 = parse_uuid(&c),
+                            b"Data" => custom_icon.1 = base64::decode(&c)
+                                .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))?,
+                            _ => {}
+                        }
+                    }
+                    (
+                        Some(b"Key"),
+                        Some(&mut Node::Attachment(ref mut name, _))
+                    ) => {
+                        *name = c;
+                    }
+                    (
+                        Some(b"Value"),
+                        Some(&mut Node::Attachment(_, ref mut content))
+                    ) => {
+                        // attachments of KeePass 2.0 files are stored inline
+                        *content = base64::decode(&c).ok();
+                    }
+                    (
+                        Some(field),
+                        Some(&mut Node::Group(ref mut group))
+                    ) if stack.len() > 1 && stack[stack.len() - 2] == b"Times" => {
+                        set_time_field(&mut group.times, field, &c);
+                    }
+                    (
+                        Some(b"Notes"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        group.notes = Some(Notes::new(&c));
+                    }
+                    (
+                        Some(b"IconID"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        group.icon_id = c.parse().unwrap_or(0);
+                    }
+                    (
+                        Some(b"CustomIconUUID"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        group.custom_icon_uuid = parse_uuid(&c);
+                    }
+                    (
+                        Some(b"IsExpanded"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        group.is_expanded = c.eq_ignore_ascii_case("true");
+                    }
+                    (
+                        Some(b"LastTopVisibleEntry"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        group.last_top_visible_entry = Some(c);
+                    }
+                    (
+                        Some(b"UUID"),
+                        Some(&mut Node::Group(Group { ref mut uuid, .. }))
+                    ) => {
+                        if let Some(value) = parse_uuid(&c) {
+                            *uuid = value;
+                        }
+                    }
+                    (
+                        Some(b"EnableSearching"),
+                        Some(&mut Node::Group(ref mut group))
+                    ) => {
+                        // KeePass writes "null" for groups inheriting the parent's setting
+                        group.enable_searching = parse_optional_bool(&c);
+                    }
+                    (
+                        Some(b"UUID"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        if let Some(uuid) = parse_uuid(&c) {
+                            entry.set_uuid(&uuid);
+                        }
+                    }
+                    (
+                        Some(b"UUID"),
+                        Some(&mut Node::DeletedObject(ref mut deleted_object))
+                    ) => {
+                        if let Some(uuid) = parse_uuid(&c) {
+                            deleted_object.set_uuid(&uuid);
+                        }
+                    }
+                    (
+                        Some(b"DeletionTime"),
+                        Some(&mut Node::DeletedObject(ref mut deleted_object))
+                    ) => {
+                        if let Some(time) = parse_timestamp(&c) {
+                            deleted_object.set_deletion_time(&time);
+                        }
+                    }
+                    (
+                        Some(b"Tags"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        entry.set_tags(&c);
+                    }
+                    (
+                        Some(b"IconID"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        entry.set_icon_id(c.parse().unwrap_or(0));
+                    }
+                    (
+                        Some(b"CustomIconUUID"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        if let Some(uuid) = parse_uuid(&c) {
+                            entry.set_custom_icon_uuid(&uuid);
+                        }
+                    }
+                    (
+                        Some(b"ForegroundColor"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        if let Ok(color) = c.parse() {
+                            entry.set_foreground_color(&color);
+                        }
+                    }
+                    (
+                        Some(b"BackgroundColor"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        if let Ok(color) = c.parse() {
+                            entry.set_background_color(&color);
+                        }
+                    }
+                    (
+                        Some(b"OverrideURL"),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) => {
+                        entry.set_override_url(&c);
+                    }
+                    (
+                        Some(field),
+                        Some(&mut Node::Entry(ref mut entry))
+                    ) if stack.len() > 1 && stack[stack.len() - 2] == b"Times" => {
+                        let mut times = entry.times().unwrap_or_default();
+                        set_time_field(&mut times, field, &c);
+                        entry.set_times(&times);
+                    }
                     (
                         Some(b"Enabled"),
                         Some(&mut Node::AutoType(ref mut at))
@@ -253,5 +517,147 @@ pub(crate) fn parse(
         buf.clear();
     }
 
-    Ok(root_group)
+    root_group.apply_memory_protection(meta.memory_protection());
+
+    Ok((meta, root_group, deleted_objects))
+}
+
+fn attribute(element: &BytesStart, key: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .filter_map(|attribute| attribute.ok())
+        .find(|attribute| attribute.key == key)
+        .and_then(|attribute| String::from_utf8(attribute.value.into_owned()).ok())
+}
+
+// `<Value Ref="n"/>` points at the KDBX 3 pool by ID, or at the KDBX 4 inner header by position
+fn resolve_attachment(
+    reference: &str,
+    meta_binaries: &HashMap<String, Vec<u8>>,
+    attachments: &[&[u8]],
+) -> Option<Vec<u8>> {
+    meta_binaries.get(reference).cloned().or_else(|| {
+        let index: usize = reference.parse().ok()?;
+        attachments.get(index).map(|content| content.to_vec())
+    })
+}
+
+fn set_meta_field(meta: &mut Meta, field: &[u8], value: &str) {
+    match field {
+        b"Generator" => { meta.set_generator(value); }
+        b"DatabaseName" => {
+            let changed = meta.database_name_change();
+            meta.set_database_name(value).set_database_name_change(changed);
+        }
+        b"DatabaseNameChanged" => { meta.set_database_name_change(parse_timestamp(value)); }
+        b"DatabaseDescription" => {
+            let changed = meta.database_description_changed();
+            meta.set_database_description(value).set_database_description_changed(changed);
+        }
+        b"DatabaseDescriptionChanged" => { meta.set_database_description_changed(parse_timestamp(value)); }
+        b"DefaultUserName" => {
+            let changed = meta.default_user_name_changed();
+            meta.set_default_user_name(value).set_default_user_name_changed(changed);
+        }
+        b"DefaultUserNameChanged" => { meta.set_default_user_name_changed(parse_timestamp(value)); }
+        b"MaintenanceHistoryDays" => { meta.set_maintenance_history_days(value.parse().unwrap_or(-1)); }
+        b"Color" => {
+            if let Ok(color) = value.parse() {
+                meta.set_color(&color);
+            }
+        }
+        b"MasterKeyChanged" => { meta.set_master_key_changed_time(parse_timestamp(value)); }
+        b"MasterKeyChangeRec" => { meta.set_master_key_change_rec(value.parse().unwrap_or(-1)); }
+        b"MasterKeyChangeForce" => { meta.set_master_key_change_force(value.parse().unwrap_or(-1)); }
+        b"RecycleBinEnabled" => { meta.set_recycle_bin_enabled(value.eq_ignore_ascii_case("true")); }
+        b"RecycleBinUUID" => {
+            if let Some(uuid) = parse_uuid(value) {
+                meta.set_recycle_bin_uuid(&uuid);
+            }
+        }
+        b"EntryTemplatesGroup" => {
+            if let Some(uuid) = parse_uuid(value) {
+                let changed = meta.entry_templates_group_changed();
+                meta.set_entry_templates_group(&uuid).set_entry_templates_group_changed(changed);
+            }
+        }
+        b"EntryTemplatesGroupChanged" => { meta.set_entry_templates_group_changed(parse_timestamp(value)); }
+        b"HistoryMaxItems" => { meta.set_history_max_items(value.parse().unwrap_or(-1)); }
+        b"HistoryMaxSize" => {
+            if let Ok(size) = value.parse() {
+                meta.set_history_max_size(size);
+            }
+        }
+        b"LastSelectedGroup" => {
+            if let Some(uuid) = parse_uuid(value) {
+                meta.set_last_selected_group(&uuid);
+            }
+        }
+        b"LastTopVisibleGroup" => {
+            if let Some(uuid) = parse_uuid(value) {
+                meta.set_last_top_visible_group(uuid);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_uuid(value: &str) -> Option<UuidValue> {
+    base64::decode(value)
+        .ok()
+        .filter(|bytes| bytes.len() == 16)
+        .map(|bytes| UuidValue::from(bytes.as_slice()))
+}
+
+fn parse_optional_bool(value: &str) -> Option<bool> {
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// KDBX 4 stores times as base64 encoded seconds since 0001-01-01, older versions as ISO 8601
+fn parse_timestamp(value: &str) -> Option<TimestampValue> {
+    if let Ok(timestamp) = value.parse::<TimestampValue>() {
+        return Some(timestamp);
+    }
+
+    let bytes = base64::decode(value).ok().filter(|bytes| bytes.len() == 8)?;
+    let seconds = LittleEndian::read_i64(&bytes);
+
+    NaiveDate::from_ymd(1, 1, 1)
+        .and_hms(0, 0, 0)
+        .checked_add_signed(Duration::seconds(seconds))
+        .map(|time| TimestampValue::from(DateTime::<Utc>::from_utc(time, Utc)))
+}
+
+fn set_time_field(times: &mut Times, field: &[u8], value: &str) {
+    match field {
+        b"CreationTime" => times.creation_time = parse_timestamp(value),
+        b"LastModificationTime" => times.last_modification_time = parse_timestamp(value),
+        b"LastAccessTime" => times.last_access_time = parse_timestamp(value),
+        b"ExpiryTime" => times.expiry_time = parse_timestamp(value),
+        b"LocationChanged" => times.location_changed = parse_timestamp(value),
+        b"Expires" => times.expires = parse_optional_bool(value),
+        b"UsageCount" => times.usage_count = value.parse().unwrap_or(0),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn test_parse_timestamp_kdbx3_and_kdbx4() {
+        let expected = "2021-06-15T13:45:30Z".parse::<TimestampValue>().ok();
+
+        assert_that(&parse_timestamp("2021-06-15T13:45:30Z")).is_equal_to(expected.clone());
+        assert_that(&parse_timestamp("+qVa2A4AAAA=")).is_equal_to(expected);
+        assert_that(&parse_timestamp("not a time")).is_none();
+    }
 }
@@ -10,15 +10,632 @@ use crate::{
 
 use base64;
 use secstr::SecStr;
-use xml::{
-    name::OwnedName,
-    writer::{
-        EventWriter,
-        XmlEvent
-    }
+use std::collections::HashMap;
+use xml::writer::{
+    EmitterConfig,
+    EventWriter,
+    XmlEvent
 };
-#[allow(dead_code)]
-pub(crate) fn write_xml_block(_group: &Group, _inner_cipher: &mut dyn Cipher) -> Result<Vec<u8>> {
-    let res = vec![];
-    Ok(res)
+
+type Writer = EventWriter<Vec<u8>>;
+
+/// Where the attachment contents the `<Value Ref="n"/>` of the entries point into are stored
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum AttachmentPool {
+    /// In the KDBX 4 inner header, the caller writes them
+    InnerHeader,
+    /// In `<Meta><Binaries>` of the document, as KDBX 3 has no inner header
+    Meta,
+}
+
+/// Serializes the XML document of a KDBX database. Standard fields are protected as the
+/// `MemoryProtection` settings of `meta` say, custom fields keep their own protection.
+/// Protected values are encrypted with `inner_cipher` in document order.
+/// Returns the document and the attachment contents its `<Value Ref="n"/>` point into,
+/// which are also part of the document with `AttachmentPool::Meta`.
+pub(crate) fn write(
+    meta: &Meta,
+    root: &Group,
+    deleted_objects: &[DeletedObject],
+    inner_cipher: &mut dyn Cipher,
+    pool: AttachmentPool,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(Vec::new());
+
+    let attachments = attachment_pool(root);
+
+    start(&mut writer, "KeePassFile")?;
+    match pool {
+        AttachmentPool::InnerHeader => write_meta(&mut writer, meta, &[])?,
+        AttachmentPool::Meta => write_meta(&mut writer, meta, &attachments)?,
+    }
+    start(&mut writer, "Root")?;
+    write_group(&mut writer, root, meta.memory_protection(), &attachments, inner_cipher)?;
+    write_deleted_objects(&mut writer, deleted_objects)?;
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    Ok((writer.into_inner(), attachments))
+}
+
+// The contents of all attachments in the tree, each once, in document order
+fn attachment_pool(root: &Group) -> Vec<Vec<u8>> {
+    fn collect_entry(entry: &Entry, pool: &mut Vec<Vec<u8>>) {
+        for binary in entry.binaries() {
+            let content = binary.value();
+            if !pool.contains(&content) {
+                pool.push(content);
+            }
+        }
+        for item in entry.history_items() {
+            collect_entry(&item, pool);
+        }
+    }
+
+    fn collect_group(group: &Group, pool: &mut Vec<Vec<u8>>) {
+        for (_, entry) in sorted(&group.entries) {
+            collect_entry(entry, pool);
+        }
+        for (_, child_group) in sorted(&group.child_groups) {
+            collect_group(child_group, pool);
+        }
+    }
+
+    let mut pool = Vec::new();
+    collect_group(root, &mut pool);
+    pool
+}
+
+// sorted to keep the output (and the inner stream) deterministic
+fn sorted<T>(items: &HashMap<String, T>) -> Vec<(&String, &T)> {
+    let mut items: Vec<_> = items.iter().collect();
+    items.sort_by(|a, b| a.0.cmp(b.0));
+    items
+}
+
+// in the order KeePass writes them, `<Binaries>` only if there are attachments to store
+fn write_meta(writer: &mut Writer, meta: &Meta, attachments: &[Vec<u8>]) -> Result<()> {
+    let protection = meta.memory_protection();
+
+    start(writer, "Meta")?;
+    text_element(writer, "Generator", &meta.generator())?;
+    text_element(writer, "DatabaseName", &meta.database_name())?;
+    timestamp_element(writer, "DatabaseNameChanged", meta.database_name_change())?;
+    text_element(writer, "DatabaseDescription", &meta.database_description())?;
+    timestamp_element(writer, "DatabaseDescriptionChanged", meta.database_description_changed())?;
+    text_element(writer, "DefaultUserName", &meta.default_user_name())?;
+    timestamp_element(writer, "DefaultUserNameChanged", meta.default_user_name_changed())?;
+    text_element(writer, "MaintenanceHistoryDays", &meta.maintenance_history_days().to_string())?;
+    optional_element(writer, "Color", meta.color().map(|color| color.to_hex()).as_deref())?;
+    timestamp_element(writer, "MasterKeyChanged", meta.master_key_changed())?;
+    text_element(writer, "MasterKeyChangeRec", &meta.master_key_change_rec().to_string())?;
+    text_element(writer, "MasterKeyChangeForce", &meta.master_key_change_force().to_string())?;
+
+    start(writer, "MemoryProtection")?;
+    bool_element(writer, "ProtectTitle", protection.protect_title)?;
+    bool_element(writer, "ProtectUserName", protection.protect_user_name)?;
+    bool_element(writer, "ProtectPassword", protection.protect_password)?;
+    bool_element(writer, "ProtectURL", protection.protect_url)?;
+    bool_element(writer, "ProtectNotes", protection.protect_notes)?;
+    end(writer)?;
+
+    start(writer, "CustomIcons")?;
+    for icon in meta.custom_icons() {
+        start(writer, "Icon")?;
+        text_element(writer, "UUID", &base64::encode(icon.uuid_value().as_bytes()))?;
+        text_element(writer, "Data", &base64::encode(icon.data()))?;
+        end(writer)?;
+    }
+    end(writer)?;
+
+    bool_element(writer, "RecycleBinEnabled", meta.recycle_bin_enabled())?;
+    uuid_element(writer, "RecycleBinUUID", meta.recycle_bin_uuid())?;
+    uuid_element(writer, "EntryTemplatesGroup", meta.entry_templates_group())?;
+    timestamp_element(writer, "EntryTemplatesGroupChanged", meta.entry_templates_group_changed())?;
+    text_element(writer, "HistoryMaxItems", &meta.history_max_items().to_string())?;
+    text_element(writer, "HistoryMaxSize", &meta.history_max_size().to_string())?;
+    uuid_element(writer, "LastSelectedGroup", meta.last_selected_group())?;
+    uuid_element(writer, "LastTopVisibleGroup", meta.last_top_visible_group())?;
+
+    if !attachments.is_empty() {
+        start(writer, "Binaries")?;
+        for (id, content) in attachments.iter().enumerate() {
+            emit(writer, XmlEvent::start_element("Binary").attr("ID", &id.to_string()))?;
+            emit(writer, XmlEvent::characters(&base64::encode(content)))?;
+            end(writer)?;
+        }
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+fn write_group(
+    writer: &mut Writer,
+    group: &Group,
+    protection: &MemoryProtection,
+    attachments: &[Vec<u8>],
+    inner_cipher: &mut dyn Cipher,
+) -> Result<()> {
+    start(writer, "Group")?;
+    text_element(writer, "UUID", &base64::encode(group.uuid.as_bytes()))?;
+    text_element(writer, "Name", &group.name)?;
+    optional_element(writer, "Notes", group.notes.as_ref().map(|notes| notes.text.as_str()))?;
+    text_element(writer, "IconID", &group.icon_id.to_string())?;
+    uuid_element(writer, "CustomIconUUID", group.custom_icon_uuid.clone())?;
+    write_times(writer, &group.times)?;
+    bool_element(writer, "IsExpanded", group.is_expanded)?;
+    match group.enable_searching {
+        Some(enable_searching) => bool_element(writer, "EnableSearching", enable_searching)?,
+        None => text_element(writer, "EnableSearching", "null")?,
+    }
+    optional_element(writer, "LastTopVisibleEntry", group.last_top_visible_entry.as_deref())?;
+
+    for (_, entry) in sorted(&group.entries) {
+        write_entry(writer, entry, protection, attachments, inner_cipher)?;
+    }
+
+    for (_, child_group) in sorted(&group.child_groups) {
+        write_group(writer, child_group, protection, attachments, inner_cipher)?;
+    }
+
+    end(writer)
+}
+
+fn write_entry(
+    writer: &mut Writer,
+    entry: &Entry,
+    protection: &MemoryProtection,
+    attachments: &[Vec<u8>],
+    inner_cipher: &mut dyn Cipher,
+) -> Result<()> {
+    start(writer, "Entry")?;
+    text_element(writer, "UUID", &base64::encode(entry.uuid().as_bytes()))?;
+    text_element(writer, "IconID", &entry.icon_id().to_string())?;
+    uuid_element(writer, "CustomIconUUID", entry.custom_icon_uuid())?;
+    optional_element(writer, "ForegroundColor", entry.foreground_color().map(|color| color.to_hex()).as_deref())?;
+    optional_element(writer, "BackgroundColor", entry.background_color().map(|color| color.to_hex()).as_deref())?;
+    optional_element(writer, "OverrideURL", entry.override_url().as_deref())?;
+
+    if let Some(tags) = entry.tags() {
+        text_element(writer, "Tags", &tags)?;
+    }
+
+    if let Some(times) = entry.times() {
+        write_times(writer, &times)?;
+    }
+
+    let mut fields: Vec<_> = entry.fields().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in fields {
+        write_string(writer, key, value, protection, inner_cipher)?;
+    }
+
+    for binary in entry.binaries() {
+        let content = binary.value();
+        let reference = attachments
+            .iter()
+            .position(|attachment| *attachment == content)
+            .expect("the pool holds every attachment of the tree");
+
+        start(writer, "Binary")?;
+        text_element(writer, "Key", &binary.id())?;
+        emit(writer, XmlEvent::start_element("Value").attr("Ref", &reference.to_string()))?;
+        end(writer)?;
+        end(writer)?;
+    }
+
+    if let Some(autotype) = entry.autotype() {
+        write_autotype(writer, &autotype)?;
+    }
+
+    if entry.has_history_items() {
+        start(writer, "History")?;
+        for item in entry.history_items() {
+            write_entry(writer, &item, protection, attachments, inner_cipher)?;
+        }
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+// written as ISO 8601, which KeePass reads for all KDBX versions
+fn write_times(writer: &mut Writer, times: &Times) -> Result<()> {
+    let timestamps = [
+        ("CreationTime", &times.creation_time),
+        ("LastModificationTime", &times.last_modification_time),
+        ("LastAccessTime", &times.last_access_time),
+        ("ExpiryTime", &times.expiry_time),
+    ];
+
+    start(writer, "Times")?;
+    for (name, timestamp) in timestamps.iter() {
+        if let Some(timestamp) = timestamp {
+            text_element(writer, name, timestamp)?;
+        }
+    }
+    bool_element(writer, "Expires", times.expires.unwrap_or(false))?;
+    text_element(writer, "UsageCount", &times.usage_count.to_string())?;
+    if let Some(timestamp) = &times.location_changed {
+        text_element(writer, "LocationChanged", timestamp)?;
+    }
+
+    end(writer)
+}
+
+fn write_deleted_objects(writer: &mut Writer, deleted_objects: &[DeletedObject]) -> Result<()> {
+    start(writer, "DeletedObjects")?;
+    for deleted_object in deleted_objects {
+        start(writer, "DeletedObject")?;
+        text_element(writer, "UUID", &base64::encode(deleted_object.uuid().as_bytes()))?;
+        text_element(writer, "DeletionTime", &deleted_object.deletion_time())?;
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+fn write_string(
+    writer: &mut Writer,
+    key: &str,
+    value: &StringValue,
+    protection: &MemoryProtection,
+    inner_cipher: &mut dyn Cipher,
+) -> Result<()> {
+    let plaintext = match value {
+        StringValue::Bytes(bytes) => SecStr::new(bytes.clone()),
+        StringValue::UnprotectedString(plain) => SecStr::from(plain.as_str()),
+        StringValue::ProtectedString(protected) => protected.unprotect(),
+    };
+
+    // the database settings decide for standard fields, like KeePass does
+    let protect = if MemoryProtection::is_standard_field(key) {
+        protection.is_protected(key)
+    } else {
+        matches!(value, StringValue::ProtectedString(_))
+    };
+
+    start(writer, "String")?;
+    text_element(writer, "Key", key)?;
+
+    if protect {
+        let encrypted = base64::encode(inner_cipher.encrypt(plaintext.unsecure())?);
+
+        emit(writer, XmlEvent::start_element("Value").attr("Protected", "True"))?;
+        emit(writer, XmlEvent::characters(&encrypted))?;
+        end(writer)?;
+    } else {
+        text_element(writer, "Value", &String::from_utf8_lossy(plaintext.unsecure()))?;
+    }
+
+    end(writer)
+}
+
+fn write_autotype(writer: &mut Writer, autotype: &AutoType) -> Result<()> {
+    start(writer, "AutoType")?;
+    bool_element(writer, "Enabled", autotype.enabled)?;
+
+    if let Some(sequence) = &autotype.sequence {
+        text_element(writer, "DefaultSequence", sequence)?;
+    }
+
+    for association in autotype.associations.iter() {
+        start(writer, "Association")?;
+        if let Some(window) = &association.window {
+            text_element(writer, "Window", window)?;
+        }
+        if let Some(sequence) = &association.sequence {
+            text_element(writer, "KeystrokeSequence", sequence)?;
+        }
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+fn timestamp_element(writer: &mut Writer, name: &str, value: Option<TimestampValue>) -> Result<()> {
+    optional_element(writer, name, value.as_ref().map(|timestamp| timestamp.as_str()))
+}
+
+fn uuid_element(writer: &mut Writer, name: &str, value: Option<UuidValue>) -> Result<()> {
+    optional_element(writer, name, value.map(|uuid| base64::encode(uuid.as_bytes())).as_deref())
+}
+
+fn optional_element(writer: &mut Writer, name: &str, value: Option<&str>) -> Result<()> {
+    match value {
+        Some(value) => text_element(writer, name, value),
+        None => Ok(()),
+    }
+}
+
+fn bool_element(writer: &mut Writer, name: &str, value: bool) -> Result<()> {
+    text_element(writer, name, if value { "True" } else { "False" })
+}
+
+fn text_element(writer: &mut Writer, name: &str, value: &str) -> Result<()> {
+    start(writer, name)?;
+    if !value.is_empty() {
+        emit(writer, XmlEvent::characters(value))?;
+    }
+    end(writer)
+}
+
+fn start(writer: &mut Writer, name: &str) -> Result<()> {
+    emit(writer, XmlEvent::start_element(name))
+}
+
+fn end(writer: &mut Writer) -> Result<()> {
+    emit(writer, XmlEvent::end_element())
+}
+
+fn emit<'a, E>(writer: &mut Writer, event: E) -> Result<()>
+    where E: Into<XmlEvent<'a>>
+{
+    writer
+        .write(event)
+        .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        cryptopraphy::cipher::Salsa20Cipher,
+        database::xml::parser,
+    };
+    use spectral::prelude::*;
+
+    const STREAM_KEY: [u8; 32] = [7u8; 32];
+
+    fn sample_root() -> Group {
+        let mut entry = Entry::default();
+        entry.add("Title", &StringValue::UnprotectedString("mail".to_owned()));
+        entry.add("UserName", &StringValue::UnprotectedString("alice".to_owned()));
+        entry.add("Password", &StringValue::UnprotectedString("hunter2".to_owned()));
+        entry.add("PIN", &StringValue::ProtectedString(ProtectedValue::from("1234")));
+        entry.add("Comment", &StringValue::UnprotectedString("plain".to_owned()));
+
+        let mut group = Group::root();
+        group.add_entry("mail", &entry);
+        group
+    }
+
+    fn write_sample(meta: &Meta) -> Vec<u8> {
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        write(meta, &sample_root(), &[], &mut cipher, AttachmentPool::InnerHeader).unwrap().0
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        String::from_utf8_lossy(haystack).contains(needle)
+    }
+
+    #[test]
+    fn test_write_protects_standard_fields_by_settings() {
+        let mut meta = Meta::default();
+        meta.set_protect_user_name(true);
+
+        let xml = write_sample(&meta);
+
+        assert_that(&contains(&xml, "<ProtectUserName>True</ProtectUserName>")).is_true();
+        assert_that(&contains(&xml, "hunter2")).is_false();
+        assert_that(&contains(&xml, "alice")).is_false();
+        assert_that(&contains(&xml, "1234")).is_false();
+        assert_that(&contains(&xml, "<Value>mail</Value>")).is_true();
+        assert_that(&contains(&xml, "<Value>plain</Value>")).is_true();
+    }
+
+    #[test]
+    fn test_write_unprotects_standard_fields_by_settings() {
+        let mut meta = Meta::default();
+        meta.set_protect_password(false);
+
+        let xml = write_sample(&meta);
+
+        assert_that(&contains(&xml, "<Value>hunter2</Value>")).is_true();
+        assert_that(&contains(&xml, "1234")).is_false();
+    }
+
+    #[test]
+    fn test_write_parse_round_trip() {
+        let mut meta = Meta::default();
+        meta.set_protect_title(true);
+
+        let xml = write_sample(&meta);
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let result = parser::parse(&xml, &mut cipher, &[]);
+
+        let (parsed_meta, root, _) = assert_that(&result).is_ok().subject;
+        let entry = &root.entries["mail"];
+
+        assert_that(parsed_meta.memory_protection()).is_equal_to(meta.memory_protection());
+        assert_that(&entry.title().as_deref()).is_equal_to(Some("mail"));
+        assert_that(&entry.username().as_deref()).is_equal_to(Some("alice"));
+        assert_that(&entry.password().as_deref()).is_equal_to(Some("hunter2"));
+        assert_that(&entry.get("PIN").as_deref()).is_equal_to(Some("1234"));
+        assert_that(&entry.get("Comment").as_deref()).is_equal_to(Some("plain"));
+        assert_that(&entry.fields().filter(|(_, v)| matches!(v, StringValue::ProtectedString(_))).count())
+            .is_equal_to(3);
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_search_settings() {
+        let mut root = sample_root();
+        let times = Times {
+            expires: Some(true),
+            expiry_time: "2021-06-15T13:45:30Z".parse().ok(),
+            ..Default::default()
+        };
+        root.entries.get_mut("mail").unwrap()
+            .set_tags("private;mail")
+            .set_times(&times);
+
+        let mut bin = Group::new("Bin", &Default::default(), &Default::default());
+        bin.enable_searching = Some(false);
+        root.add_group("Bin", &bin);
+
+        let mut meta = Meta::default();
+        meta.set_recycle_bin_enabled(true);
+        meta.set_recycle_bin_uuid(&bin.uuid);
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, _) = write(&meta, &root, &[], &mut cipher, AttachmentPool::InnerHeader).unwrap();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (parsed_meta, parsed_root, _) = parser::parse(&xml, &mut cipher, &[]).unwrap();
+        let entry = &parsed_root.entries["mail"];
+
+        assert_that(&parsed_meta.recycle_bin_enabled()).is_true();
+        assert_that(&parsed_meta.recycle_bin_uuid()).is_equal_to(Some(bin.uuid.clone()));
+        assert_that(&parsed_root.uuid).is_equal_to(root.uuid.clone());
+        assert_that(&parsed_root.searching_enabled(false)).is_false();
+        assert_that(&parsed_root.searching_enabled(true)).is_true();
+        assert_that(&parsed_root.child_groups["Bin"].searching_enabled(true)).is_false();
+        assert_that(&entry.uuid()).is_equal_to(root.entries["mail"].uuid());
+        assert_that(&entry.tags()).is_equal_to(Some("private;mail".to_owned()));
+        assert_that(&entry.times()).is_equal_to(Some(times));
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_group_notes() {
+        let mut root = sample_root();
+        root.set_notes(&Notes::new("shared logins"));
+        root.entries.get_mut("mail").unwrap()
+            .add("Notes", &StringValue::UnprotectedString("entry notes".to_owned()));
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, _) = write(&Meta::default(), &root, &[], &mut cipher, AttachmentPool::InnerHeader).unwrap();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (_, parsed_root, _) = parser::parse(&xml, &mut cipher, &[]).unwrap();
+
+        assert_that(&parsed_root.notes).is_equal_to(Some(Notes::new("shared logins")));
+        assert_that(&parsed_root.entries["mail"].get("Notes").as_deref()).is_equal_to(Some("entry notes"));
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_deleted_objects() {
+        let deleted_objects = vec![
+            DeletedObject::new(
+                &UuidValue::from("2b5d0a1e-58bb-4c33-9c1b-7a0e3f4f61d2"),
+                &"2021-06-15T13:45:30Z".parse().unwrap(),
+            ),
+            DeletedObject::new(
+                &UuidValue::from("9d7c51ab-3f0e-4f5b-8a43-5e2a0c6d7b19"),
+                &"2021-07-01T08:00:00Z".parse().unwrap(),
+            ),
+        ];
+
+        let root = sample_root();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, _) = write(&Meta::default(), &root, &deleted_objects, &mut cipher, AttachmentPool::InnerHeader).unwrap();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (_, parsed_root, parsed) = parser::parse(&xml, &mut cipher, &[]).unwrap();
+
+        assert_that(&parsed).is_equal_to(deleted_objects);
+        // the tombstone UUIDs do not end up on the groups or entries
+        assert_that(&parsed_root.uuid).is_equal_to(root.uuid.clone());
+        assert_that(&parsed_root.entries["mail"].uuid()).is_equal_to(root.entries["mail"].uuid());
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_meta() {
+        let mut meta = Meta::default();
+        meta.set_generator("KeePassXC")
+            .set_database_name("Passwords")
+            .set_database_description("family")
+            .set_default_user_name("alice")
+            .set_maintenance_history_days(365)
+            .set_color(&ColorValue::new(240, 160, 0))
+            .set_master_key_changed_time("2021-06-15T13:45:30Z".parse().ok())
+            .set_master_key_change_rec(90)
+            .set_master_key_change_force(180)
+            .add_custom_icon(&IconValue::new(&UuidValue::from("c4e8f2a0-1b7d-4e69-b3a5-0f9d8e7c6b54"), b"PNG"))
+            .set_entry_templates_group(&UuidValue::from("2b5d0a1e-58bb-4c33-9c1b-7a0e3f4f61d2"))
+            .set_last_selected_group(&UuidValue::from("9d7c51ab-3f0e-4f5b-8a43-5e2a0c6d7b19"))
+            .set_history_max_items(5)
+            .set_history_max_size(1024);
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, _) = write(&meta, &sample_root(), &[], &mut cipher, AttachmentPool::InnerHeader).unwrap();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (parsed_meta, _, _) = parser::parse(&xml, &mut cipher, &[]).unwrap();
+
+        assert_that(&parsed_meta).is_equal_to(meta);
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_attachments() {
+        let mut root = sample_root();
+        root.entries.get_mut("mail").unwrap()
+            .set_binary(&Binary::new("id_rsa", false, b"private key"))
+            .set_binary(&Binary::new("notes.txt", false, b"shared"))
+            .set_foreground_color(&ColorValue::white())
+            .set_override_url("cmd://mail");
+
+        let mut vpn = Entry::default();
+        vpn.add("Title", &StringValue::UnprotectedString("vpn".to_owned()));
+        vpn.set_binary(&Binary::new("readme.txt", false, b"shared"));
+        root.add_entry("vpn", &vpn);
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, attachments) = write(&Meta::default(), &root, &[], &mut cipher, AttachmentPool::InnerHeader).unwrap();
+
+        // the same content is pooled once
+        assert_that(&attachments).is_equal_to(vec![b"private key".to_vec(), b"shared".to_vec()]);
+        assert_that(&contains(&xml, "private key")).is_false();
+
+        let pool: Vec<&[u8]> = attachments.iter().map(Vec::as_slice).collect();
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (_, parsed_root, _) = parser::parse(&xml, &mut cipher, &pool).unwrap();
+
+        let mail = &parsed_root.entries["mail"];
+        assert_that(&mail.binaries()).is_equal_to(root.entries["mail"].binaries());
+        assert_that(&mail.foreground_color()).is_equal_to(Some(ColorValue::white()));
+        assert_that(&mail.override_url()).is_equal_to(Some("cmd://mail".to_owned()));
+        assert_that(&parsed_root.entries["vpn"].binaries())
+            .is_equal_to(vec![Binary::new("readme.txt", false, b"shared")]);
+    }
+
+    #[test]
+    fn test_write_parse_round_trip_meta_binaries() {
+        let mut root = sample_root();
+        root.entries.get_mut("mail").unwrap()
+            .set_binary(&Binary::new("id_rsa", false, b"private key"));
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (xml, _) = write(&Meta::default(), &root, &[], &mut cipher, AttachmentPool::Meta).unwrap();
+
+        assert_that(&contains(&xml, "<Binaries>")).is_true();
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (_, parsed_root, _) = parser::parse(&xml, &mut cipher, &[]).unwrap();
+
+        assert_that(&parsed_root.entries["mail"].binaries()).is_equal_to(root.entries["mail"].binaries());
+    }
+
+    #[test]
+    fn test_parse_kdbx3_attachment_pool() {
+        let xml = br#"<KeePassFile>
+            <Meta><Binaries><Binary ID="7">c2VjcmV0</Binary></Binaries></Meta>
+            <Root><Group><Name>root</Name>
+                <Entry>
+                    <String><Key>Title</Key><Value>mail</Value></String>
+                    <Binary><Key>key.txt</Key><Value Ref="7" /></Binary>
+                </Entry>
+            </Group></Root>
+        </KeePassFile>"#;
+
+        let mut cipher = Salsa20Cipher::with_key(&STREAM_KEY).unwrap();
+        let (_, root, _) = parser::parse(xml, &mut cipher, &[]).unwrap();
+
+        assert_that(&root.entries["mail"].binaries())
+            .is_equal_to(vec![Binary::new("key.txt", false, b"secret")]);
+    }
 }
@@ -12,24 +12,32 @@ use crate::{
     errors::Error,
     results::Result,
 };
-use crate::internal::cryptopraphy;
+use crate::internal::cryptopraphy::{
+    self,
+    wipe::{
+        into_secret,
+        SecretBytes,
+    },
+};
+
+use zeroize::Zeroizing;
 
-pub(crate) fn parse(source: &mut dyn Read) -> Result<Vec<u8>> {
-    let mut buffer = Vec::new();
+pub(crate) fn parse(source: &mut dyn Read) -> Result<SecretBytes> {
+    let mut buffer = Zeroizing::new(Vec::new());
     source.read_to_end(&mut buffer)?;
 
     if let Ok(v) = parse_xml_keyfile(&buffer) {
         // items formatted keyfile
-        Ok(v)
+        Ok(Zeroizing::new(v))
     } else if buffer.len() == 32 {
         // legacy binary key format
-        Ok(buffer.to_vec())
+        Ok(buffer)
     } else if buffer.len() == 64 {
-        let v = ::hex::decode(&buffer).map_err(|_e| Error::InvalidKeyFile)?;
-        Ok(v)
+        let v = ::hex::decode(&*buffer).map_err(|_e| Error::InvalidKeyFile)?;
+        Ok(Zeroizing::new(v))
     } else {
         // hashed key format
-        Ok(cryptopraphy::sha256(&[&buffer])?.as_slice().to_vec())
+        Ok(into_secret(cryptopraphy::sha256(&[&buffer])?))
     }
 }
 
@@ -84,7 +92,7 @@ mod tests {
 
         assert_that(&result)
             .is_ok()
-            .is_equal_to(::base64::decode(data).unwrap());
+            .is_equal_to(Zeroizing::new(::base64::decode(data).unwrap()));
     }
 
     #[test]
@@ -104,7 +112,7 @@ mod tests {
 
         assert_that(&result)
             .is_ok()
-            .is_equal_to(expected.to_vec());
+            .is_equal_to(Zeroizing::new(expected.to_vec()));
     }
 
     #[test]
@@ -136,7 +144,7 @@ mod tests {
 
         assert_that(&result)
             .is_ok()
-            .is_equal_to(expected);
+            .is_equal_to(Zeroizing::new(expected));
     }
 
     #[test]
@@ -153,7 +161,7 @@ mod tests {
 
         assert_that(&result)
             .is_ok()
-            .is_equal_to(expected.to_vec());
+            .is_equal_to(Zeroizing::new(expected.to_vec()));
     }
 
     #[test]
@@ -1,14 +1,19 @@
 use rand::{
     thread_rng,
     Rng,
-    distributions::Alphanumeric,
+    RngCore,
 };
 
-pub(crate) fn generate_random_bytes(length: usize) -> Vec<u8> {
-    thread_rng()
-        .sample_iter(Alphanumeric)
-        .take(length)
-        .collect()
+/// Bytes over the full range from the thread-local CSPRNG, for keys, seeds, salts and IVs
+pub(crate) fn generate_secret_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// A uniformly distributed index in `0..upper` from the thread-local CSPRNG
+pub(crate) fn random_index(upper: usize) -> usize {
+    thread_rng().gen_range(0..upper)
 }
 
 #[cfg(test)]
@@ -17,50 +22,65 @@ mod tests {
     use spectral::prelude::*;
 
     #[test]
-    fn test_generate_random_bytes_16() {
-        let result = generate_random_bytes(16);
+    fn test_generate_secret_bytes() {
+        let first = generate_secret_bytes(20);
+        let second = generate_secret_bytes(20);
+
+        assert_that(&first).has_length(20);
+        assert_that(&first).is_not_equal_to(second);
+    }
+
+    #[test]
+    fn test_random_index_stays_in_range() {
+        assert_that(&(0..1000).all(|_| random_index(3) < 3)).is_true();
+        assert_that(&random_index(1)).is_equal_to(0);
+    }
+
+    #[test]
+    fn test_generate_secret_bytes_16() {
+        let result = generate_secret_bytes(16);
         assert_that(&result)
             .has_length(16);
     }
 
     #[test]
-    fn test_generate_random_bytes_24() {
-        let result = generate_random_bytes(24);
+    fn test_generate_secret_bytes_24() {
+        let result = generate_secret_bytes(24);
         assert_that(&result)
             .has_length(24);
     }
 
     #[test]
-    fn test_generate_random_bytes_32() {
-        let result = generate_random_bytes(32);
+    fn test_generate_secret_bytes_32() {
+        let result = generate_secret_bytes(32);
         assert_that(&result)
             .has_length(32);
     }
 
     #[test]
-    fn test_generate_random_bytes_48() {
-        let result = generate_random_bytes(48);
+    fn test_generate_secret_bytes_48() {
+        let result = generate_secret_bytes(48);
         assert_that(&result)
             .has_length(48);
     }
 
     #[test]
-    fn test_generate_random_bytes_64() {
-        let result = generate_random_bytes(64);
+    fn test_generate_secret_bytes_64() {
+        let result = generate_secret_bytes(64);
         assert_that(&result)
             .has_length(64);
     }
 
     #[test]
-    fn test_generate_random_bytes_128() {
-        let result = generate_random_bytes(128);
+    fn test_generate_secret_bytes_128() {
+        let result = generate_secret_bytes(128);
         assert_that(&result)
             .has_length(128);
     }
 
     #[test]
-    fn test_generate_random_bytes_256() {
-        let result = generate_random_bytes(256);
+    fn test_generate_secret_bytes_256() {
+        let result = generate_secret_bytes(256);
         assert_that(&result)
             .has_length(256);
     }
@@ -1,13 +1,10 @@
 use crate::{
+    api::traits::Sizable,
     errors::{
         DatabaseIntegrityError,
         Error,
     },
     results::Result,
-    internal::traits::{
-        AsBytes,
-        TryFromBytes
-    }
 };
 
 use byteorder::{
@@ -16,27 +13,50 @@ use byteorder::{
     WriteBytesExt
 };
 
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    io::Write
-};
+use std::io::Write;
+
+/// An ordered dictionary of typed values as used by KDBX4 for KDF parameters and
+/// (public) custom data.
+///
+/// Entries keep their insertion order and the version read is written back, so
+/// `to_bytes(from_bytes(x))` reproduces `x`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VariantDictionary {
+    version: u16,
+    data: Vec<(String, VariantDictionaryValue)>
+}
 
-#[derive(Debug)]
-pub(crate) struct VariantDictionary {
-    data: RefCell<HashMap<String, VariantDictionaryValue>>
+impl Default for VariantDictionary {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            data: Vec::new(),
+        }
+    }
 }
 
 impl VariantDictionary {
-    pub(crate) fn get<T>(
+    /// Version written by KeePass 2.35 and later
+    pub const VERSION: u16 = 0x0100;
+    /// Readers must reject dictionaries whose major version (upper byte) is unknown
+    const VERSION_CRITICAL_MASK: u16 = 0xff00;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The version this dictionary was read with, `VERSION` for a new one
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn get<T>(
         &self,
         key: &str,
     ) -> Result<T>
         where T: FromVariantDictionaryValue<T>,
     {
-        let map = self.data.borrow();
-
-        let vdv = if let Some(v) = map.get(key) {
+        let vdv = if let Some(v) = self.get_value(key) {
             v
         } else {
             return Err(
@@ -56,84 +76,161 @@ impl VariantDictionary {
             })
     }
 
-    pub(crate) fn put(
+    pub fn get_value(&self, key: &str) -> Option<&VariantDictionaryValue> {
+        self.data
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_u32(&self, key: &str) -> Option<u32> {
+        self.get_value(key).and_then(u32::from_variant_dictionary_value)
+    }
+
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get_value(key).and_then(u64::from_variant_dictionary_value)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get_value(key).and_then(bool::from_variant_dictionary_value)
+    }
+
+    pub fn get_i32(&self, key: &str) -> Option<i32> {
+        self.get_value(key).and_then(i32::from_variant_dictionary_value)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get_value(key).and_then(i64::from_variant_dictionary_value)
+    }
+
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        match self.get_value(key) {
+            Some(VariantDictionaryValue::String(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, key: &str) -> Option<&[u8]> {
+        match self.get_value(key) {
+            Some(VariantDictionaryValue::ByteArray(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Inserts a value; replacing an existing key keeps its original position
+    pub fn put(
         &mut self,
         key: &str,
         val: VariantDictionaryValue,
     ) -> Option<VariantDictionaryValue>
     {
-        let mut map = self.data.borrow_mut();
+        if let Some((_, v)) = self.data.iter_mut().find(|(k, _)| k == key) {
+            Some(std::mem::replace(v, val))
+        } else {
+            self.data.push((key.to_owned(), val));
+            None
+        }
+    }
 
-        map.insert(
-            key.to_owned(),
-            val,
-        )
+    pub fn remove(&mut self, key: &str) -> Option<VariantDictionaryValue> {
+        self.data
+            .iter()
+            .position(|(k, _)| k == key)
+            .map(|pos| self.data.remove(pos).1)
     }
-}
 
-impl Default for VariantDictionary {
-    fn default() -> Self {
-        VariantDictionary {
-            data: RefCell::new(HashMap::new())
-        }
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get_value(key).is_some()
     }
-}
 
-impl TryFromBytes for VariantDictionary {
-    type Error = Error;
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.data.iter().map(|(k, _)| k.as_str())
+    }
 
-    fn from_bytes(buffer: &[u8]) -> Result<VariantDictionary> {
-        let version = LittleEndian::read_u16(&buffer[0..2]);
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &VariantDictionaryValue)> {
+        self.data.iter().map(|(k, v)| (k.as_str(), v))
+    }
 
-        if version != 0x100 {
-            return Err(DatabaseIntegrityError::InvalidVariantDictionaryVersion { version }.into());
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn from_bytes(buffer: &[u8]) -> Result<VariantDictionary> {
+        fn take<'a>(buffer: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+            if buffer.len() < *pos + len {
+                return Err(DatabaseIntegrityError::InvalidVariantDictionaryFormat { length: buffer.len() }.into());
+            }
+            let res = &buffer[*pos..(*pos + len)];
+            *pos += len;
+            Ok(res)
         }
 
-        if buffer.len() < 9 {
-            return Err(DatabaseIntegrityError::InvalidVariantDictionaryFormat { length: buffer.len() }.into());
+        fn ensure_length(buffer: &[u8], value_buffer: &[u8], expected: usize) -> Result<()> {
+            if value_buffer.len() != expected {
+                return Err(DatabaseIntegrityError::InvalidVariantDictionaryFormat { length: buffer.len() }.into());
+            }
+            Ok(())
+        }
+
+        let mut pos = 0;
+
+        let version = LittleEndian::read_u16(take(buffer, &mut pos, 2)?);
+
+        if version & Self::VERSION_CRITICAL_MASK != Self::VERSION & Self::VERSION_CRITICAL_MASK {
+            return Err(DatabaseIntegrityError::InvalidVariantDictionaryVersion { version }.into());
         }
 
-        let mut pos = 2;
-        let mut data = HashMap::new();
+        let mut data = VariantDictionary {
+            version,
+            data: Vec::new(),
+        };
+
+        loop {
+            let value_type = take(buffer, &mut pos, 1)?[0];
 
-        while pos < buffer.len() - 9 {
-            let value_type = buffer[pos];
-            pos += 1;
+            if value_type == VariantDictionaryValue::END {
+                break;
+            }
 
-            let key_length = LittleEndian::read_u32(&buffer[pos..(pos + 4)]) as usize;
-            pos += 4;
+            let key_length = LittleEndian::read_u32(take(buffer, &mut pos, 4)?) as usize;
 
-            let key = std::str::from_utf8(&buffer[pos..(pos + key_length)])
+            let key = std::str::from_utf8(take(buffer, &mut pos, key_length)?)
                 .map_err(|e| Error::from(DatabaseIntegrityError::from(e)))?
                 .to_owned();
-            pos += key_length;
 
-            let value_length = LittleEndian::read_u32(&buffer[pos..(pos + 4)]) as usize;
-            pos += 4;
+            let value_length = LittleEndian::read_u32(take(buffer, &mut pos, 4)?) as usize;
 
-            let value_buffer = &buffer[pos..(pos + value_length)];
-            pos += value_length;
+            let value_buffer = take(buffer, &mut pos, value_length)?;
 
             let value = match value_type {
                 VariantDictionaryValue::UINT32 => {
+                    ensure_length(buffer, value_buffer, 4)?;
                     VariantDictionaryValue::UInt32(
                         LittleEndian::read_u32(value_buffer)
                     )
                 },
                 VariantDictionaryValue::UINT64 => {
+                    ensure_length(buffer, value_buffer, 8)?;
                     VariantDictionaryValue::UInt64(
                         LittleEndian::read_u64(value_buffer)
                     )
                 },
                 VariantDictionaryValue::BOOL => {
+                    ensure_length(buffer, value_buffer, 1)?;
                     VariantDictionaryValue::Bool(value_buffer != [0])
                 },
                 VariantDictionaryValue::INT32 => {
+                    ensure_length(buffer, value_buffer, 4)?;
                     VariantDictionaryValue::Int32(
                         LittleEndian::read_i32(value_buffer)
                     )
                 },
                 VariantDictionaryValue::INT64 => {
+                    ensure_length(buffer, value_buffer, 8)?;
                     VariantDictionaryValue::Int64(
                         LittleEndian::read_i64(value_buffer)
                     )
@@ -158,24 +255,21 @@ impl TryFromBytes for VariantDictionary {
                     );
                 }
             };
-            data.insert(key, value);
+            // two values for one key leave it open which one a KDF parameter has
+            if data.put(&key, value).is_some() {
+                return Err(DatabaseIntegrityError::DuplicateVariantDictionaryKey { key }.into());
+            }
         }
 
-        Ok(
-            VariantDictionary {
-                data: RefCell::new(data)
-            }
-        )
+        Ok(data)
     }
-}
-impl AsBytes for VariantDictionary {
-    fn as_bytes(&self) -> Vec<u8> {
+
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut res = Vec::new();
 
-        let _ = res.write_u16::<LittleEndian>(0x100);
+        let _ = res.write_u16::<LittleEndian>(self.version);
 
         self.data
-            .borrow()
             .iter()
             .for_each(|(key, val)| {
                 let _ = res.write_u8(val.to_code());
@@ -185,13 +279,32 @@ impl AsBytes for VariantDictionary {
                 let _ = res.write(val.to_vec().as_ref());
             });
 
-        let _ = res.write_u8(0x00);
+        let _ = res.write_u8(VariantDictionaryValue::END);
 
         res
     }
 }
 
-pub(crate) trait FromVariantDictionaryValue<T> {
+impl IntoIterator for VariantDictionary {
+    type Item = (String, VariantDictionaryValue);
+    type IntoIter = std::vec::IntoIter<(String, VariantDictionaryValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter()
+    }
+}
+
+impl Sizable for VariantDictionary {
+    fn size_in_bytes(&self) -> usize {
+        self.data
+            .iter()
+            .map(|(key, val)| key.len() + val.len())
+            .sum()
+    }
+}
+
+pub trait FromVariantDictionaryValue<T> {
+
     fn from_variant_dictionary_value(vdv: &VariantDictionaryValue) -> Option<T>;
 }
 
@@ -265,8 +378,8 @@ impl FromVariantDictionaryValue<Vec<u8>> for Vec<u8> {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub(crate) enum VariantDictionaryValue {
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VariantDictionaryValue {
     UInt32(u32),
     UInt64(u64),
     Bool(bool),
@@ -277,7 +390,8 @@ pub(crate) enum VariantDictionaryValue {
 }
 
 impl VariantDictionaryValue {
-    // 0x00 - 0x03: reserved
+    pub const END: u8 = 0x00;
+    // 0x01 - 0x03: reserved
     pub const UINT32: u8 = 0x04;
     pub const UINT64: u8 = 0x05;
     // 0x06 - 0x07: reserved
@@ -300,6 +414,10 @@ impl VariantDictionaryValue {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn to_code(&self) -> u8 {
         match self {
             Self::UInt32(_) => Self::UINT32,
@@ -406,37 +524,156 @@ mod tests {
         assert_that(&vd.get::<Vec<u8>>("test_bytearray_32")).is_ok_containing(Vec::from([42u8; 32].as_ref()));
     }
 
+    #[test]
+    fn test_typed_getters() {
+        let vd = create_variant_dictionary();
+
+        assert_that(&vd.get_u32("test_u32_max")).is_some().is_equal_to(u32::MAX);
+        assert_that(&vd.get_u64("test_u32_max")).is_none();
+        assert_that(&vd.get_string("test_str_hello")).is_some().is_equal_to("hello");
+        assert_that(&vd.get_bytes("test_bytearray_32")).is_some().matches(|b| b.len() == 32);
+        assert_that(&vd.get_bool("missing")).is_none();
+    }
+
+    #[test]
+    fn test_put_keeps_order() {
+        let mut vd = create_variant_dictionary();
+
+        let previous = vd.put("bool_false", VariantDictionaryValue::Bool(true));
+
+        assert_that(&previous).is_some().is_equal_to(VariantDictionaryValue::Bool(false));
+        assert_that(&vd.keys().nth(1)).is_some().is_equal_to("bool_false");
+        assert_that(&vd.len()).is_equal_to(14);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut vd = create_variant_dictionary();
+
+        assert_that(&vd.remove("bool_true")).is_some();
+        assert_that(&vd.contains_key("bool_true")).is_false();
+        assert_that(&vd.remove("bool_true")).is_none();
+    }
+
     #[test]
     fn test_serialize_deserialize_empty() {
         let vd = VariantDictionary::default();
 
-        let serialized = vd.as_bytes();
+        let serialized = vd.to_bytes();
+
+        assert_that(&serialized).is_equal_to(vec![0x00, 0x01, 0x00]);
 
         let parsed_data = VariantDictionary::from_bytes(serialized.as_ref());
 
         assert_that(&parsed_data)
-            .is_err();
+            .is_ok()
+            .matches(|vd| vd.is_empty());
     }
 
     #[test]
     fn test_serialize_deserialize_values() {
         let vd = create_variant_dictionary();
 
-        let serialized = vd.as_bytes();
+        let serialized = vd.to_bytes();
 
         let parsed_data = VariantDictionary::from_bytes(serialized.as_ref());
 
-        let parsed_data_spec = assert_that(&parsed_data)
+        assert_that(&parsed_data)
             .is_ok()
-            .subject;
+            .is_equal_to(&vd);
+    }
 
-        let original_data = vd.data.borrow();
+    #[test]
+    fn test_serialize_is_lossless() {
+        let serialized = create_variant_dictionary().to_bytes();
 
-        original_data
-            .iter()
-            .for_each(|(key, val)| {
-                let deserialized_data = parsed_data_spec.data.borrow();
-                assert_that(&deserialized_data.get(key)).is_some().is_equal_to(val);
+        let reserialized = VariantDictionary::from_bytes(serialized.as_ref())
+            .unwrap()
+            .to_bytes();
+
+        assert_that(&reserialized).is_equal_to(serialized);
+    }
+
+    #[test]
+    fn test_deserialize_accepts_newer_minor_version() {
+        let buffer = [0x07, 0x01, 0x00];
+
+        assert_that(&VariantDictionary::from_bytes(buffer.as_ref())).is_ok();
+    }
+
+    #[test]
+    fn test_serialize_keeps_newer_minor_version() {
+        let buffer = [0x07, 0x01, 0x08, 0x01, 0x00, 0x00, 0x00, 0x61, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00];
+
+        let parsed = VariantDictionary::from_bytes(buffer.as_ref()).unwrap();
+
+        assert_that(&parsed.version()).is_equal_to(0x0107);
+        assert_that(&parsed.to_bytes()).is_equal_to(buffer.to_vec());
+    }
+
+    #[test]
+    fn test_deserialize_rejects_unknown_major_version() {
+        let buffer = [0x00, 0x02, 0x00];
+
+        assert_that(&VariantDictionary::from_bytes(buffer.as_ref()))
+            .is_err()
+            .matches(|e| match e {
+                Error::DatabaseIntegrity {
+                    e: DatabaseIntegrityError::InvalidVariantDictionaryVersion { version }
+                } => *version == 0x200,
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_deserialize_rejects_truncated_input() {
+        let serialized = create_variant_dictionary().to_bytes();
+
+        for len in [0, 1, 2, 5, serialized.len() - 1].iter() {
+            assert_that(&VariantDictionary::from_bytes(&serialized[..*len]))
+                .is_err();
+        }
+    }
+
+    #[test]
+    fn test_deserialize_rejects_mismatched_value_length() {
+        let mut buffer = vec![0x00, 0x01, VariantDictionaryValue::UINT32];
+        buffer.extend_from_slice(&1u32.to_le_bytes());
+        buffer.push(b'k');
+        buffer.extend_from_slice(&2u32.to_le_bytes());
+        buffer.extend_from_slice(&[0xff, 0xff]);
+        buffer.push(0x00);
+
+        assert_that(&VariantDictionary::from_bytes(buffer.as_ref()))
+            .is_err();
+    }
+
+    #[test]
+    fn test_deserialize_rejects_duplicate_keys() {
+        let mut buffer = vec![0x00, 0x01];
+        for value in [1u8, 0u8].iter() {
+            buffer.push(VariantDictionaryValue::BOOL);
+            buffer.extend_from_slice(&1u32.to_le_bytes());
+            buffer.push(b'k');
+            buffer.extend_from_slice(&1u32.to_le_bytes());
+            buffer.push(*value);
+        }
+        buffer.push(0x00);
+
+        assert_that(&VariantDictionary::from_bytes(buffer.as_ref()))
+            .is_err()
+            .matches(|e| match e {
+                Error::DatabaseIntegrity {
+                    e: DatabaseIntegrityError::DuplicateVariantDictionaryKey { key }
+                } => key == "k",
+                _ => false,
             });
     }
+
+    #[test]
+    fn test_value_is_empty() {
+        assert_that(&VariantDictionaryValue::String(String::new()).is_empty()).is_true();
+        assert_that(&VariantDictionaryValue::ByteArray(vec![42]).is_empty()).is_false();
+        assert_that(&VariantDictionaryValue::Bool(false).is_empty()).is_false();
+    }
 }
@@ -11,7 +11,10 @@ use crate::{
     errors::DatabaseIntegrityError,
     results::Result,
 };
-use crate::internal::cryptopraphy;
+use crate::internal::cryptopraphy::{
+    self,
+    wipe::into_secret,
+};
 
 pub(crate) fn read_hmac_block_stream(
     data: &[u8],
@@ -28,24 +31,7 @@ pub(crate) fn read_hmac_block_stream(
         let size = LittleEndian::read_u32(size_bytes) as usize;
         let block = &data[(pos + 36)..(pos + 36 + size)];
 
-        let hmac_block_key = get_hmac_block_key(block_index, key)?;
-
-        let mut block_index_buf = [0u8; 8];
-        LittleEndian::write_u64(
-            &mut block_index_buf,
-            block_index as u64,
-        );
-
-        if hmac != cryptopraphy::hmac(
-            &[
-                &block_index_buf,
-                size_bytes,
-                &block
-            ],
-            &hmac_block_key,
-        )?
-            .as_slice()
-        {
+        if hmac != block_hmac(block_index, size_bytes, block, key)?.as_slice() {
             return Err(DatabaseIntegrityError::BlockHashMismatch { block_index }.into());
         }
 
@@ -58,6 +44,84 @@ pub(crate) fn read_hmac_block_stream(
     Ok(out)
 }
 
+/// What `check_hmac_block_stream` found in a block stream
+#[derive(Debug, Default)]
+pub(crate) struct BlockStreamCheck {
+    pub(crate) block_count: usize,
+    pub(crate) corrupted_blocks: Vec<usize>,
+    /// The stream ends inside a block or before the terminating empty block
+    pub(crate) truncated: bool,
+    /// The data of the blocks before the first corrupted one
+    pub(crate) intact_prefix: Vec<u8>,
+}
+
+/// Like `read_hmac_block_stream`, but checks every block instead of failing at the first
+/// mismatch. A block size that runs past the end of the data counts as truncation.
+pub(crate) fn check_hmac_block_stream(
+    data: &[u8],
+    key: &GenericArray<u8, typenum::U64>,
+) -> Result<BlockStreamCheck> {
+    let mut check = BlockStreamCheck {
+        truncated: true,
+        ..BlockStreamCheck::default()
+    };
+
+    let mut pos = 0;
+
+    while data.len() - pos >= 36 {
+        let hmac = &data[pos..(pos + 32)];
+        let size_bytes = &data[(pos + 32)..(pos + 36)];
+        let size = LittleEndian::read_u32(size_bytes) as usize;
+
+        if data.len() - pos - 36 < size {
+            break;
+        }
+
+        let block = &data[(pos + 36)..(pos + 36 + size)];
+
+        if hmac != block_hmac(check.block_count, size_bytes, block, key)?.as_slice() {
+            check.corrupted_blocks.push(check.block_count);
+        } else if check.corrupted_blocks.is_empty() {
+            check.intact_prefix.extend_from_slice(block);
+        }
+
+        pos += 36 + size;
+        check.block_count += 1;
+
+        // an empty block ends the stream
+        if size == 0 {
+            check.truncated = false;
+            break;
+        }
+    }
+
+    Ok(check)
+}
+
+fn block_hmac(
+    block_index: usize,
+    size_bytes: &[u8],
+    block: &[u8],
+    key: &GenericArray<u8, typenum::U64>,
+) -> Result<GenericArray<u8, typenum::U32>> {
+    let hmac_block_key = into_secret(get_hmac_block_key(block_index, key)?);
+
+    let mut block_index_buf = [0u8; 8];
+    LittleEndian::write_u64(
+        &mut block_index_buf,
+        block_index as u64,
+    );
+
+    cryptopraphy::hmac(
+        &[
+            &block_index_buf,
+            size_bytes,
+            block
+        ],
+        &hmac_block_key,
+    )
+}
+
 pub(crate) fn create_hmac_block_stream(
     data: &[u8],
     size: usize,
@@ -69,41 +133,42 @@ pub(crate) fn create_hmac_block_stream(
     let mut block_index = 0;
 
     while pos < data.len() {
-        let block = &data[pos..(pos + size)];
+        let block = &data[pos..data.len().min(pos + size)];
 
         let mut block_size = [0u8; 4];
         LittleEndian::write_u32(
             &mut block_size,
-            size as u32,
+            block.len() as u32,
         );
 
-        let mut block_index_buf = [0u8; 8];
-        LittleEndian::write_u64(
-            &mut block_index_buf,
-            block_index as u64,
-        );
+        let hmac = block_hmac(block_index, block_size.as_ref(), block, key)?;
 
-        let hmac_block_key = get_hmac_block_key(block_index, key)?;
-        let block_hmac = cryptopraphy::hmac(
-            &[
-                &block_index_buf,
-                block_size.as_ref(),
-                &block
-            ],
-            &hmac_block_key,
-        )?;
-
-        pos += size;
+        pos += block.len();
         block_index += 1;
 
-        out.extend_from_slice(block_hmac.as_slice());
-        out.extend_from_slice(&(size as u32).to_le_bytes());
+        out.extend_from_slice(hmac.as_slice());
+        out.extend_from_slice(&block_size);
         out.extend_from_slice(block);
     }
 
     Ok(out)
 }
 
+/// Like `create_hmac_block_stream`, followed by the empty block that ends a KDBX 4 payload
+pub(crate) fn write_hmac_block_stream(
+    data: &[u8],
+    size: usize,
+    key: &GenericArray<u8, typenum::U64>,
+) -> Result<Vec<u8>> {
+    let mut out = create_hmac_block_stream(data, size, key)?;
+
+    let block_index = data.chunks(size).count();
+    out.extend_from_slice(block_hmac(block_index, &[0u8; 4], &[], key)?.as_slice());
+    out.extend_from_slice(&[0u8; 4]);
+
+    Ok(out)
+}
+
 pub(crate) fn get_hmac_block_key(
     block_index: usize,
     key: &GenericArray<u8, typenum::U64>,
@@ -210,4 +275,64 @@ mod tests {
             .is_ok()
             .is_equal_to(DATA_VEC_LONG.to_vec());
     }
+
+    fn terminated(blocks: Vec<u8>, block_index: usize, key: &GenericArray<u8, typenum::U64>) -> Vec<u8> {
+        let mut data = blocks;
+        data.extend_from_slice(block_hmac(block_index, &[0u8; 4], &[], key).unwrap().as_slice());
+        data.extend_from_slice(&[0u8; 4]);
+        data
+    }
+
+    #[test]
+    fn test_check_hmac_block_stream_intact() {
+        let key = GenericArray::from_slice(&[0u8; 64]);
+        let data = terminated(create_hmac_block_stream(DATA_VEC_LONG, 64, key).unwrap(), 2, key);
+
+        let check = check_hmac_block_stream(&data, key).unwrap();
+
+        assert_that(&check.block_count).is_equal_to(3);
+        assert_that(&check.corrupted_blocks).is_equal_to(vec![]);
+        assert_that(&check.truncated).is_false();
+        assert_that(&check.intact_prefix).is_equal_to(DATA_VEC_LONG.to_vec());
+    }
+
+    #[test]
+    fn test_check_hmac_block_stream_records_every_corrupted_block() {
+        let key = GenericArray::from_slice(&[0u8; 64]);
+        let mut data = terminated(create_hmac_block_stream(DATA_VEC_LONG, 64, key).unwrap(), 2, key);
+        data[100 + 40] ^= 0xff;
+
+        let check = check_hmac_block_stream(&data, key).unwrap();
+
+        assert_that(&check.block_count).is_equal_to(3);
+        assert_that(&check.corrupted_blocks).is_equal_to(vec![1]);
+        assert_that(&check.truncated).is_false();
+        assert_that(&check.intact_prefix).is_equal_to(DATA_VEC_LONG[..64].to_vec());
+    }
+
+    #[test]
+    fn test_write_hmac_block_stream_splits_and_terminates() {
+        let key = GenericArray::from_slice(&[0u8; 64]);
+        let data = write_hmac_block_stream(&DATA_VEC_LONG[..100], 64, key).unwrap();
+
+        let check = check_hmac_block_stream(&data, key).unwrap();
+
+        assert_that(&check.block_count).is_equal_to(3);
+        assert_that(&check.corrupted_blocks).is_equal_to(vec![]);
+        assert_that(&check.truncated).is_false();
+        assert_that(&read_hmac_block_stream(&data, key).unwrap()).is_equal_to(DATA_VEC_LONG[..100].to_vec());
+    }
+
+    #[test]
+    fn test_check_hmac_block_stream_truncated() {
+        let key = GenericArray::from_slice(&[0u8; 64]);
+        let data = create_hmac_block_stream(DATA_VEC_LONG, 64, key).unwrap();
+
+        let check = check_hmac_block_stream(&data[..150], key).unwrap();
+
+        assert_that(&check.block_count).is_equal_to(1);
+        assert_that(&check.corrupted_blocks).is_equal_to(vec![]);
+        assert_that(&check.truncated).is_true();
+        assert_that(&check.intact_prefix).is_equal_to(DATA_VEC_LONG[..64].to_vec());
+    }
 }
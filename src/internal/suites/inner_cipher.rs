@@ -18,6 +18,11 @@ impl InnerCipherSuite {
                     cryptopraphy::cipher::PlainCipher::with_key(key)?
                 )
             ),
+            InnerCipherSuite::ArcFourVariant => Ok(
+                Box::new(
+                    cryptopraphy::cipher::ArcFourVariantCipher::with_key(key)?
+                )
+            ),
             InnerCipherSuite::Salsa20 => Ok(
                 Box::new(
                     cryptopraphy::cipher::Salsa20Cipher::with_key(key)?
@@ -29,6 +34,15 @@ impl InnerCipherSuite {
             ),
         }
     }
+
+    pub(crate) fn to_code(&self) -> u32 {
+        match self {
+            InnerCipherSuite::Plain => 0,
+            InnerCipherSuite::ArcFourVariant => 1,
+            InnerCipherSuite::Salsa20 => 2,
+            InnerCipherSuite::ChaCha20 => 3,
+        }
+    }
 }
 
 impl TryFrom<u32> for InnerCipherSuite {
@@ -37,6 +51,7 @@ impl TryFrom<u32> for InnerCipherSuite {
     fn try_from(v: u32) -> Result<InnerCipherSuite> {
         match v {
             0 => Ok(InnerCipherSuite::Plain),
+            1 => Ok(InnerCipherSuite::ArcFourVariant),
             2 => Ok(InnerCipherSuite::Salsa20),
             3 => Ok(InnerCipherSuite::ChaCha20),
             _ => Err(DatabaseIntegrityError::InvalidInnerCipherID { cid: v }.into()),
@@ -73,6 +88,14 @@ mod tests {
             .is_equal_to(plaintext.to_vec());
     }
 
+    #[test]
+    fn test_arcfour_variant_is_read() {
+        let suite = InnerCipherSuite::try_from(1);
+
+        assert_that(&matches!(suite, Ok(InnerCipherSuite::ArcFourVariant))).is_true();
+        assert_that(&suite.unwrap().get_cipher([7u8; 32].as_ref()).is_ok()).is_true();
+    }
+
     #[test]
     fn test_salsa20_cipher_expect_success() {
         let key = [0u8;32].as_ref();
@@ -1,13 +1,34 @@
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use generic_array::{
+    GenericArray,
+    typenum,
+};
+use zeroize::Zeroize;
 
 use crate::{
-    api::suites::KdfSettings,
+    api::suites::{
+        KdfKind,
+        KdfLimits,
+        KdfSettings,
+    },
     errors::{
+        CryptoError,
         DatabaseIntegrityError,
         Error
     },
     internal::{
-        structures::variant_dictionary,
+        random::generate_secret_bytes,
+        structures::variant_dictionary::{
+            self,
+            VariantDictionaryValue,
+        },
         suites::{
             KDF_AES_KDBX3,
             KDF_AES_KDBX4,
@@ -18,7 +39,45 @@ use crate::{
 };
 use crate::internal::cryptopraphy;
 
+/// Smallest benchmark run that is extrapolated to the calibration target
+const CALIBRATION_MIN_PROBE: Duration = Duration::from_millis(100);
+/// Rounds of the first AES-KDF benchmark run
+const CALIBRATION_AES_ROUNDS: u64 = 10_000;
+/// Argon2 memory used during calibration if the caller allows more (64 MiB, the KeePassXC default)
+const CALIBRATION_ARGON2_MEMORY: u64 = 64 * 1024 * 1024;
+/// Argon2 requires at least 8 KiB of memory per lane
+const ARGON2_MIN_MEMORY_PER_LANE: u64 = 8 * 1024;
+
 impl KdfSettings {
+    /// Benchmarks the given key derivation function on this machine and returns settings that
+    /// take roughly `target` to derive a key, like KeePassXC's "1 second delay" button.
+    ///
+    /// For Argon2 the memory is capped at `max_memory` bytes and one lane per CPU is used,
+    /// only the iterations are calibrated. `max_memory` is ignored for AES-KDF.
+    pub fn calibrate(
+        kind: KdfKind,
+        target: Duration,
+        max_memory: u64,
+    ) -> Result<KdfSettings> {
+        match kind {
+            KdfKind::Aes => calibrate_aes(target),
+            KdfKind::Argon2 => calibrate_argon2(target, max_memory),
+        }
+    }
+
+    /// Derives the transformed key from a (SHA-256 hashed) composite key
+    pub fn transform_key(&self, composite_key: &[u8; 32]) -> Result<[u8; 32]> {
+        let mut transformed_key = self
+            .get_kdf()
+            .transform_key(GenericArray::from_slice(composite_key))?;
+
+        let mut result = [0u8; 32];
+        result.copy_from_slice(&transformed_key);
+        transformed_key.as_mut_slice().zeroize();
+
+        Ok(result)
+    }
+
     pub(crate) fn get_kdf(&self) -> Box<dyn cryptopraphy::kdf::Kdf> {
         match self {
             KdfSettings::Aes { seed, rounds } => {
@@ -50,6 +109,134 @@ impl KdfSettings {
     }
 }
 
+impl KdfLimits {
+    /// Fails with `Error::KdfResourceLimit` if deriving a key with `settings` would exceed
+    /// these limits. AES-KDF only costs time and is never rejected.
+    pub(crate) fn check(&self, settings: &KdfSettings) -> Result<()> {
+        if let KdfSettings::Argon2 { memory, iterations, lanes, .. } = settings {
+            // argon2 takes the memory in KiB and the iterations as u32
+            let max_memory = std::cmp::min(self.max_memory, u64::from(u32::MAX) * 1024);
+            let max_iterations = std::cmp::min(self.max_iterations, u64::from(u32::MAX));
+
+            check_limit("memory", *memory, max_memory)?;
+            check_limit("iterations", *iterations, max_iterations)?;
+            check_limit("lanes", u64::from(*lanes), u64::from(self.max_lanes))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_limit(parameter: &str, value: u64, limit: u64) -> Result<()> {
+    if value > limit {
+        return Err(
+            Error::KdfResourceLimit {
+                parameter: parameter.to_owned(),
+                value,
+                limit,
+            }
+        );
+    }
+
+    Ok(())
+}
+
+fn calibrate_aes(target: Duration) -> Result<KdfSettings> {
+    let seed = generate_secret_bytes(32);
+
+    let (rounds, elapsed) = benchmark(
+        CALIBRATION_AES_ROUNDS,
+        probe_duration(target),
+        |rounds| KdfSettings::Aes { seed: seed.clone(), rounds },
+    )?;
+
+    Ok(
+        KdfSettings::Aes {
+            seed,
+            rounds: extrapolate(rounds, elapsed, target),
+        }
+    )
+}
+
+fn calibrate_argon2(target: Duration, max_memory: u64) -> Result<KdfSettings> {
+    let cpus = std::thread::available_parallelism()
+        .map(|n| n.get() as u64)
+        .unwrap_or(1);
+    let lanes = std::cmp::min(cpus, max_memory / ARGON2_MIN_MEMORY_PER_LANE);
+
+    if lanes == 0 {
+        return Err(
+            DatabaseIntegrityError::from(
+                CryptoError::from(argon2::Error::MemoryTooLittle)
+            ).into()
+        );
+    }
+
+    // argon2 works on whole KiB blocks
+    let memory = std::cmp::min(max_memory, CALIBRATION_ARGON2_MEMORY) / 1024 * 1024;
+    let salt = generate_secret_bytes(32);
+    let version = argon2::Version::Version13;
+
+    let (iterations, elapsed) = benchmark(
+        1,
+        probe_duration(target),
+        |iterations| KdfSettings::Argon2 {
+            memory,
+            salt: salt.clone(),
+            iterations,
+            lanes: lanes as u32,
+            version,
+        },
+    )?;
+
+    Ok(
+        KdfSettings::Argon2 {
+            memory,
+            salt,
+            iterations: extrapolate(iterations, elapsed, target),
+            lanes: lanes as u32,
+            version,
+        }
+    )
+}
+
+fn probe_duration(target: Duration) -> Duration {
+    std::cmp::min(target, CALIBRATION_MIN_PROBE)
+}
+
+// Doubles the work factor until a key derivation takes at least `probe`
+fn benchmark<F>(
+    initial: u64,
+    probe: Duration,
+    settings: F,
+) -> Result<(u64, Duration)>
+    where F: Fn(u64) -> KdfSettings
+{
+    let composite_key = GenericArray::<u8, typenum::U32>::default();
+
+    let mut work = initial;
+    loop {
+        let kdf = settings(work).get_kdf();
+
+        let start = Instant::now();
+        kdf.transform_key(&composite_key)?;
+        let elapsed = start.elapsed();
+
+        if elapsed >= probe || work >= u64::MAX / 2 {
+            return Ok((work, elapsed));
+        }
+
+        work *= 2;
+    }
+}
+
+fn extrapolate(work: u64, elapsed: Duration, target: Duration) -> u64 {
+    let elapsed = std::cmp::max(elapsed.as_nanos(), 1);
+    let scaled = work as u128 * target.as_nanos() / elapsed;
+
+    std::cmp::max(1, std::cmp::min(scaled, u64::MAX as u128) as u64)
+}
+
 impl TryFrom<variant_dictionary::VariantDictionary> for KdfSettings {
     type Error = Error;
 
@@ -93,16 +280,183 @@ impl TryFrom<variant_dictionary::VariantDictionary> for KdfSettings {
     }
 }
 
+impl From<&KdfSettings> for variant_dictionary::VariantDictionary {
+    fn from(settings: &KdfSettings) -> Self {
+        let mut vd = variant_dictionary::VariantDictionary::default();
+
+        match settings {
+            KdfSettings::Aes { seed, rounds } => {
+                vd.put("$UUID", VariantDictionaryValue::ByteArray(KDF_AES_KDBX4.to_vec()));
+                vd.put("R", VariantDictionaryValue::UInt64(*rounds));
+                vd.put("S", VariantDictionaryValue::ByteArray(seed.clone()));
+            }
+            KdfSettings::Argon2 {
+                memory,
+                salt,
+                iterations,
+                lanes,
+                version,
+            } => {
+                vd.put("$UUID", VariantDictionaryValue::ByteArray(KDF_ARGON2.to_vec()));
+                vd.put("M", VariantDictionaryValue::UInt64(*memory));
+                vd.put("S", VariantDictionaryValue::ByteArray(salt.clone()));
+                vd.put("I", VariantDictionaryValue::UInt64(*iterations));
+                vd.put("P", VariantDictionaryValue::UInt32(*lanes));
+                vd.put("V", VariantDictionaryValue::UInt32(version.as_u32()));
+            }
+        }
+
+        vd
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use hmac::crypto_mac::generic_array::{
-        GenericArray,
-        typenum,
-    };
     use spectral::prelude::*;
 
     use super::*;
 
+    fn argon2_settings(memory: u64, iterations: u64, lanes: u32) -> KdfSettings {
+        KdfSettings::Argon2 {
+            memory,
+            salt: vec![1u8; 32],
+            iterations,
+            lanes,
+            version: argon2::Version::Version13,
+        }
+    }
+
+    #[test]
+    fn test_limits_accept_keepassxc_defaults() {
+        let settings = argon2_settings(64 * 1024 * 1024, 10, 8);
+
+        assert_that(&KdfLimits::default().check(&settings)).is_ok();
+    }
+
+    #[test]
+    fn test_limits_reject_memory() {
+        let limits = KdfLimits {
+            max_memory: 32 * 1024 * 1024,
+            ..KdfLimits::default()
+        };
+        let settings = argon2_settings(64 * 1024 * 1024, 10, 2);
+
+        let result = limits.check(&settings);
+
+        assert_that(&result)
+            .is_err()
+            .matches(|e| match e {
+                Error::KdfResourceLimit { parameter, value, limit } => {
+                    parameter == "memory"
+                        && *value == 64 * 1024 * 1024
+                        && *limit == 32 * 1024 * 1024
+                }
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_limits_reject_lanes() {
+        let settings = argon2_settings(64 * 1024 * 1024, 10, 1024);
+
+        let result = KdfLimits::default().check(&settings);
+
+        assert_that(&result)
+            .is_err()
+            .matches(|e| match e {
+                Error::KdfResourceLimit { parameter, .. } => parameter == "lanes",
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_limits_reject_iterations_beyond_argon2_range() {
+        let limits = KdfLimits {
+            max_iterations: u64::MAX,
+            ..KdfLimits::default()
+        };
+        let settings = argon2_settings(1024 * 1024, u64::from(u32::MAX) + 1, 2);
+
+        let result = limits.check(&settings);
+
+        assert_that(&result)
+            .is_err()
+            .matches(|e| match e {
+                Error::KdfResourceLimit { parameter, limit, .. } => {
+                    parameter == "iterations" && *limit == u64::from(u32::MAX)
+                }
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_limits_ignore_aes() {
+        let limits = KdfLimits {
+            max_memory: 0,
+            max_iterations: 0,
+            max_lanes: 0,
+        };
+        let settings = KdfSettings::Aes {
+            seed: vec![1u8; 32],
+            rounds: u64::MAX,
+        };
+
+        assert_that(&limits.check(&settings)).is_ok();
+    }
+
+    #[test]
+    fn test_calibrate_aes() {
+        let result = KdfSettings::calibrate(
+            KdfKind::Aes,
+            Duration::from_millis(50),
+            0,
+        );
+
+        assert_that(&result)
+            .is_ok()
+            .matches(|settings| match settings {
+                KdfSettings::Aes { seed, rounds } => seed.len() == 32 && *rounds > 0,
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_calibrate_argon2_respects_max_memory() {
+        let result = KdfSettings::calibrate(
+            KdfKind::Argon2,
+            Duration::from_millis(50),
+            1024 * 1024 + 512,
+        );
+
+        assert_that(&result)
+            .is_ok()
+            .matches(|settings| match settings {
+                KdfSettings::Argon2 { memory, iterations, lanes, .. } => {
+                    *memory == 1024 * 1024 && *iterations >= 1 && *lanes >= 1
+                }
+                _ => false,
+            });
+    }
+
+    #[test]
+    fn test_calibrate_argon2_insufficient_memory() {
+        let result = KdfSettings::calibrate(
+            KdfKind::Argon2,
+            Duration::from_millis(50),
+            1024,
+        );
+
+        assert_that(&result).is_err();
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        assert_that(&extrapolate(1000, Duration::from_millis(100), Duration::from_secs(1)))
+            .is_equal_to(10_000);
+        assert_that(&extrapolate(1000, Duration::from_secs(1), Duration::from_nanos(1)))
+            .is_equal_to(1);
+    }
+
     #[test]
     fn test_argon2_settings() {
         let compose_key = GenericArray::<u8, typenum::U32>::from([46u8; 32]);
@@ -37,6 +37,16 @@ impl OuterCipherSuite {
     }
 }
 
+impl OuterCipherSuite {
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        match self {
+            OuterCipherSuite::AES256 => suites::CIPHERSUITE_AES256.to_vec(),
+            OuterCipherSuite::Twofish => suites::CIPHERSUITE_TWOFISH.to_vec(),
+            OuterCipherSuite::ChaCha20 => suites::CIPHERSUITE_CHACHA20.to_vec(),
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for OuterCipherSuite {
     type Error = Error;
     fn try_from(v: &[u8]) -> Result<OuterCipherSuite> {
@@ -287,8 +297,6 @@ mod tests {
         let key = [0u8; 32];
         let iv = [0u8; 0];
 
-        let plaintext = "This is my plaintext".as_bytes();
-
         let suite = OuterCipherSuite::ChaCha20;
 
         let selected_cipher = suite
@@ -297,15 +305,10 @@ mod tests {
                 iv.as_ref(),
             );
 
+        // the stream cipher is set up right away, so bad parameters are rejected early
         assert_that(
             &selected_cipher.is_err()
-        ).is_false();
-
-        let mut cipher_box = selected_cipher.unwrap();
-        let result = cipher_box.encrypt(plaintext);
-
-        assert_that(&result)
-            .is_err();
+        ).is_true();
     }
 
     #[test]
@@ -313,8 +316,6 @@ mod tests {
         let key = [0u8; 16];
         let iv = [0u8; 0];
 
-        let plaintext = "This is my plaintext".as_bytes();
-
         let suite = OuterCipherSuite::ChaCha20;
 
         let selected_cipher = suite
@@ -323,18 +324,10 @@ mod tests {
                 iv.as_ref(),
             );
 
+        // the stream cipher is set up right away, so bad parameters are rejected early
         assert_that(
             &selected_cipher.is_err()
-        ).is_false();
-
-        let mut cipher = selected_cipher.unwrap();
-
-        let result = cipher.encrypt(plaintext);
-
-        assert_that(
-            &result
-        )
-            .is_err();
+        ).is_true();
     }
 
     #[test]
@@ -12,4 +12,5 @@ mod database;
 mod errors;
 mod internal;
 mod results;
+mod wordlists;
 
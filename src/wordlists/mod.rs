@@ -0,0 +1,4 @@
+//! Wordlists bundled with the crate, shared by the password generator and the quality estimator
+
+/// The 2048 words of the BIP-39 English list, one per line
+pub(crate) const BIP39_ENGLISH: &str = include_str!("bip39_english.txt");
@@ -0,0 +1,67 @@
+// shared by the integration tests, each of which uses only some of the helpers
+#![allow(dead_code)]
+
+use rustpass::{
+    Database,
+    Result,
+};
+
+use std::{
+    fs::File,
+    io::Read
+};
+
+/// A KDBX 3.1 fixture protected with `PASSWORD`. Its root group "Legacy" holds the entry
+/// "mail" of "alice" with the protected password "hunter2" and two attachments, and the
+/// entry "vpn" with neither user name nor password.
+pub const SAMPLE_FIXTURE: &str = "attachments-aes256-salsa20-v31.kdbx";
+
+/// The password of the fixtures written by tests/fixture/generate_legacy_kdbx.py
+pub const PASSWORD: &str = "legacy";
+
+/// Opens `SAMPLE_FIXTURE`
+pub fn open_sample() -> Database {
+    open_database(SAMPLE_FIXTURE, PASSWORD).expect(".kdbx opens")
+}
+
+/// Opens a fixture that is protected with a password alone
+pub fn open_database(
+    dbfile: &str,
+    password: &str,
+) -> Result<Database> {
+    let data = read_fixture(dbfile);
+
+    Database::open(
+        &mut data.as_ref() as &mut &[u8],
+        Some(password),
+        None
+    )
+}
+
+/// Opens a fixture that is protected with a key file alone
+pub fn open_fixture(
+    dbfile: &str,
+    keyfile: &str,
+) -> Database {
+    let mut key_file: File = File::open(format!("tests/fixture/{}", keyfile))
+        .expect(".key is present");
+
+    let data = read_fixture(dbfile);
+
+    Database::open(
+        &mut data.as_ref() as &mut &[u8],
+        None,
+        Some(&mut key_file)
+    ).expect(".kdbx opens")
+}
+
+pub fn read_fixture(dbfile: &str) -> Vec<u8> {
+    let mut kdbx_file: File = File::open(format!("tests/fixture/{}", dbfile))
+        .expect(".kdbx is present");
+
+    let mut data: Vec<u8> = vec![];
+    kdbx_file.read_to_end(&mut data)
+        .expect(".kdbx is readable");
+
+    data
+}
@@ -0,0 +1,268 @@
+#!/usr/bin/env python3
+"""Writes the KDBX 2.x/3.x fixtures the way KeePass 2.x before 2.20 did.
+
+    legacy-aes256-arcfour-v20.kdbx   file version 2.0, no InnerRandomStreamID field, so the
+                                     ArcFour variant default applies
+    legacy-aes256-salsa20-v30.kdbx   file version 3.0 with a Salsa20 inner stream
+    legacy-aes256-salsa20-v30-no-stream-start.kdbx
+                                     file version 3.0 without the optional StreamStartBytes
+                                     field, so a wrong key first shows in the block hashes
+    attachments-aes256-salsa20-v31.kdbx
+                                     file version 3.1, the same entry carries two attachments
+                                     of the Meta pool and a second entry "vpn" shares one of
+                                     them, the pool also holds an unused binary
+
+These are not files written by KeePass itself, only the layout is reproduced. They
+should be replaced by files saved with KeePass 2.19 or earlier, noting the KeePass
+version that wrote them.
+
+All are protected with the password "legacy" and hold one entry "mail" in a group
+"Legacy", whose password "hunter2" is a protected value. Seeds and keys are fixed so the
+output is reproducible. Needs the `cryptography` package.
+"""
+
+import base64
+import gzip
+import hashlib
+import os
+import struct
+
+from cryptography.hazmat.primitives import padding
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+
+PASSWORD = b"legacy"
+AES_CIPHER_ID = bytes.fromhex("31c1f2e6bf714350be5805216afc5aff")
+SALSA20_IV = bytes([0xE8, 0x30, 0x09, 0x4B, 0x97, 0x20, 0x5D, 0x2A])
+
+XML = """<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+\t<Meta>
+\t\t<Generator>KeePass</Generator>
+\t\t<DatabaseName>Legacy</DatabaseName>
+\t\t<MemoryProtection>
+\t\t\t<ProtectTitle>False</ProtectTitle>
+\t\t\t<ProtectUserName>False</ProtectUserName>
+\t\t\t<ProtectPassword>True</ProtectPassword>
+\t\t\t<ProtectURL>False</ProtectURL>
+\t\t\t<ProtectNotes>False</ProtectNotes>
+\t\t</MemoryProtection>
+\t</Meta>
+\t<Root>
+\t\t<Group>
+\t\t\t<UUID>lmU+9n0aeESKZvcEze+bRg==</UUID>
+\t\t\t<Name>Legacy</Name>
+\t\t\t<IconID>49</IconID>
+\t\t\t<IsExpanded>True</IsExpanded>
+\t\t\t<Entry>
+\t\t\t\t<UUID>+wSUOv6qf0OzW8/ZHAs2sA==</UUID>
+\t\t\t\t<IconID>0</IconID>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>Title</Key>
+\t\t\t\t\t<Value>mail</Value>
+\t\t\t\t</String>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>UserName</Key>
+\t\t\t\t\t<Value>alice</Value>
+\t\t\t\t</String>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>Password</Key>
+\t\t\t\t\t<Value Protected="True">{password}</Value>
+\t\t\t\t</String>
+\t\t\t</Entry>
+\t\t</Group>
+\t</Root>
+</KeePassFile>
+"""
+
+ATTACHMENTS_XML = """<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+\t<Meta>
+\t\t<Generator>KeePass</Generator>
+\t\t<DatabaseName>Attachments</DatabaseName>
+\t\t<MemoryProtection>
+\t\t\t<ProtectTitle>False</ProtectTitle>
+\t\t\t<ProtectUserName>False</ProtectUserName>
+\t\t\t<ProtectPassword>True</ProtectPassword>
+\t\t\t<ProtectURL>False</ProtectURL>
+\t\t\t<ProtectNotes>False</ProtectNotes>
+\t\t</MemoryProtection>
+\t\t<Binaries>
+\t\t\t<Binary ID="0" Compressed="True">{compressed}</Binary>
+\t\t\t<Binary ID="1">{unused}</Binary>
+\t\t\t<Binary ID="2">{shared}</Binary>
+\t\t</Binaries>
+\t</Meta>
+\t<Root>
+\t\t<Group>
+\t\t\t<UUID>lmU+9n0aeESKZvcEze+bRg==</UUID>
+\t\t\t<Name>Legacy</Name>
+\t\t\t<IconID>49</IconID>
+\t\t\t<IsExpanded>True</IsExpanded>
+\t\t\t<Entry>
+\t\t\t\t<UUID>+wSUOv6qf0OzW8/ZHAs2sA==</UUID>
+\t\t\t\t<IconID>0</IconID>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>Title</Key>
+\t\t\t\t\t<Value>mail</Value>
+\t\t\t\t</String>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>UserName</Key>
+\t\t\t\t\t<Value>alice</Value>
+\t\t\t\t</String>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>Password</Key>
+\t\t\t\t\t<Value Protected="True">{password}</Value>
+\t\t\t\t</String>
+\t\t\t\t<Binary>
+\t\t\t\t\t<Key>ca.pem</Key>
+\t\t\t\t\t<Value Ref="2" />
+\t\t\t\t</Binary>
+\t\t\t\t<Binary>
+\t\t\t\t\t<Key>recovery.txt</Key>
+\t\t\t\t\t<Value Ref="0" />
+\t\t\t\t</Binary>
+\t\t\t</Entry>
+\t\t\t<Entry>
+\t\t\t\t<UUID>bmfJ2wcdQAy3wDP8v4i6Vw==</UUID>
+\t\t\t\t<IconID>0</IconID>
+\t\t\t\t<String>
+\t\t\t\t\t<Key>Title</Key>
+\t\t\t\t\t<Value>vpn</Value>
+\t\t\t\t</String>
+\t\t\t\t<Binary>
+\t\t\t\t\t<Key>ca.pem</Key>
+\t\t\t\t\t<Value Ref="2" />
+\t\t\t\t</Binary>
+\t\t\t</Entry>
+\t\t</Group>
+\t</Root>
+</KeePassFile>
+"""
+
+
+class ArcFourVariant:
+    """CryptoRandomStream with CrsAlgorithm.ArcFourVariant of KeePass 2.x"""
+
+    def __init__(self, key):
+        self.state = list(range(256))
+        j = 0
+        for w in range(256):
+            j = (j + self.state[w] + key[w % len(key)]) & 0xFF
+            self.state[0], self.state[j] = self.state[j], self.state[0]
+        self.i = self.j = 0
+        self.keystream(512)
+
+    def keystream(self, length):
+        out = bytearray()
+        for _ in range(length):
+            self.i = (self.i + 1) & 0xFF
+            self.j = (self.j + self.state[self.i]) & 0xFF
+            self.state[self.i], self.state[self.j] = self.state[self.j], self.state[self.i]
+            out.append(self.state[(self.state[self.i] + self.state[self.j]) & 0xFF])
+        return bytes(out)
+
+
+class Salsa20:
+    """CryptoRandomStream with CrsAlgorithm.Salsa20, keyed with SHA-256 of the stream key"""
+
+    def __init__(self, key):
+        self.key = hashlib.sha256(key).digest()
+        self.counter = 0
+        self.buffer = b""
+
+    @staticmethod
+    def _rotl(v, c):
+        return ((v << c) & 0xFFFFFFFF) | (v >> (32 - c))
+
+    def _block(self):
+        c = struct.unpack("<4I", b"expand 32-byte k")
+        k = struct.unpack("<8I", self.key)
+        n = struct.unpack("<2I", SALSA20_IV)
+        b = struct.unpack("<2I", struct.pack("<Q", self.counter))
+        x = [c[0], k[0], k[1], k[2], k[3], c[1], n[0], n[1],
+             b[0], b[1], c[2], k[4], k[5], k[6], k[7], c[3]]
+        s = list(x)
+        for _ in range(10):
+            for a, b_, c_, d in ((0, 4, 8, 12), (5, 9, 13, 1), (10, 14, 2, 6), (15, 3, 7, 11),
+                                 (0, 1, 2, 3), (5, 6, 7, 4), (10, 11, 8, 9), (15, 12, 13, 14)):
+                s[b_] ^= self._rotl((s[a] + s[d]) & 0xFFFFFFFF, 7)
+                s[c_] ^= self._rotl((s[b_] + s[a]) & 0xFFFFFFFF, 9)
+                s[d] ^= self._rotl((s[c_] + s[b_]) & 0xFFFFFFFF, 13)
+                s[a] ^= self._rotl((s[d] + s[c_]) & 0xFFFFFFFF, 18)
+        self.counter += 1
+        return struct.pack("<16I", *[(s[i] + x[i]) & 0xFFFFFFFF for i in range(16)])
+
+    def keystream(self, length):
+        while len(self.buffer) < length:
+            self.buffer += self._block()
+        out, self.buffer = self.buffer[:length], self.buffer[length:]
+        return out
+
+
+def header_field(field_id, data):
+    return struct.pack("<BH", field_id, len(data)) + data
+
+
+def transform_key(composite, seed, rounds):
+    encryptor = Cipher(algorithms.AES(seed), modes.ECB()).encryptor()
+    key = composite
+    for _ in range(rounds):
+        key = encryptor.update(key)
+    return hashlib.sha256(key).digest()
+
+
+def write(path, minor, major, stream, stream_id, template=XML, with_stream_start=True):
+    master_seed = bytes(range(32))
+    transform_seed = bytes(range(32, 64))
+    transform_rounds = 6000
+    iv = bytes(range(64, 80))
+    stream_key = bytes(range(80, 112))
+    stream_start = bytes(range(112, 144)) if with_stream_start else b""
+
+    header = struct.pack("<IIHH", 0x9AA2D903, 0xB54BFB67, minor, major)
+    header += header_field(2, AES_CIPHER_ID)
+    header += header_field(3, struct.pack("<I", 1))
+    header += header_field(4, master_seed)
+    header += header_field(5, transform_seed)
+    header += header_field(6, struct.pack("<Q", transform_rounds))
+    header += header_field(7, iv)
+    header += header_field(8, stream_key)
+    if with_stream_start:
+        header += header_field(9, stream_start)
+    if stream_id is not None:
+        header += header_field(10, struct.pack("<I", stream_id))
+    header += header_field(0, b"\r\n\r\n")
+
+    cipher = stream(stream_key)
+    secret = b"hunter2"
+    protected = bytes(a ^ b for a, b in zip(secret, cipher.keystream(len(secret))))
+    xml = template.format(
+        password=base64.b64encode(protected).decode(),
+        compressed=base64.b64encode(gzip.compress(b"recovery codes", mtime=0)).decode(),
+        unused=base64.b64encode(b"unused").decode(),
+        shared=base64.b64encode(b"-----BEGIN CERTIFICATE-----").decode(),
+    ).encode("utf-8")
+
+    block = gzip.compress(xml, mtime=0)
+    blocks = struct.pack("<I", 0) + hashlib.sha256(block).digest() + struct.pack("<I", len(block)) + block
+    blocks += struct.pack("<I", 1) + bytes(32) + struct.pack("<I", 0)
+
+    composite = hashlib.sha256(hashlib.sha256(PASSWORD).digest()).digest()
+    master_key = hashlib.sha256(master_seed + transform_key(composite, transform_seed, transform_rounds)).digest()
+
+    padder = padding.PKCS7(128).padder()
+    plaintext = padder.update(stream_start + blocks) + padder.finalize()
+    encryptor = Cipher(algorithms.AES(master_key), modes.CBC(iv)).encryptor()
+    payload = encryptor.update(plaintext) + encryptor.finalize()
+
+    with open(path, "wb") as f:
+        f.write(header + payload)
+
+
+if __name__ == "__main__":
+    here = os.path.dirname(os.path.abspath(__file__))
+    write(os.path.join(here, "legacy-aes256-arcfour-v20.kdbx"), 0, 2, ArcFourVariant, None)
+    write(os.path.join(here, "legacy-aes256-salsa20-v30.kdbx"), 0, 3, Salsa20, 2)
+    write(os.path.join(here, "legacy-aes256-salsa20-v30-no-stream-start.kdbx"), 0, 3, Salsa20, 2,
+          with_stream_start=False)
+    write(os.path.join(here, "attachments-aes256-salsa20-v31.kdbx"), 1, 3, Salsa20, 2, ATTACHMENTS_XML)
@@ -0,0 +1,33 @@
+use spectral::prelude::*;
+
+use rustpass::breaches::{
+    BreachHash,
+    BreachList,
+};
+
+mod common;
+
+use common::open_sample;
+
+// a few well-known passwords in the Have I Been Pwned SHA-1 format, the counts are made up
+const BREACH_LIST: &str = "tests/fixture/pwned-passwords-sha1-sample.txt";
+
+#[test]
+fn it_finds_breached_passwords_offline() {
+    let database = open_sample();
+    let mut list = BreachList::open(BREACH_LIST, BreachHash::Sha1).unwrap();
+
+    let breached = database.breached_passwords(&mut list).unwrap();
+
+    assert_that(&breached).has_length(1);
+    assert_that(&breached[0].entry.title().as_deref()).is_equal_to(Some("mail"));
+    assert_that(&breached[0].occurrences).is_equal_to(17043);
+}
+
+#[test]
+fn it_looks_up_single_passwords() {
+    let mut list = BreachList::open(BREACH_LIST, BreachHash::Sha1).unwrap();
+
+    assert_that(&list.occurrences("password").unwrap()).is_equal_to(9545824);
+    assert_that(&list.occurrences("7Gq!x9#Lr2$wZp4&vB8^").unwrap()).is_equal_to(0);
+}
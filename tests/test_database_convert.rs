@@ -0,0 +1,203 @@
+use spectral::prelude::*;
+
+use rustpass::{
+    Database,
+    header::{
+        FormatVersion,
+        Header,
+        InnerHeader,
+    },
+    suites::{
+        InnerCipherSuite,
+        KdfSettings,
+    },
+};
+
+mod common;
+
+use common::{
+    PASSWORD,
+    open_fixture,
+    open_sample,
+};
+
+#[test]
+fn it_converts_kdbx3_to_kdbx4() {
+    let mut database = open_fixture(
+        "empty-aes256-aeskdf-v31.kdbx",
+        "empty-aes256-aeskdf-v31.key",
+    );
+
+    let transform_rounds = match &database.header {
+        Header::KDBX3(header) => header.transform_rounds,
+        _ => panic!("expected a KDBX3 header"),
+    };
+
+    let result = database.convert_to(FormatVersion::KDBX4);
+
+    assert_that(&result)
+        .is_ok();
+    assert_that(&database.header.format_version())
+        .is_equal_to(FormatVersion::KDBX4);
+
+    match &database.header {
+        Header::KDBX4(header) => {
+            assert_that(&matches!(header.kdf, KdfSettings::Aes { rounds, .. } if rounds == transform_rounds))
+                .is_true();
+        }
+        _ => panic!("expected a KDBX4 header"),
+    }
+
+    assert_that(&matches!(database.inner_header, InnerHeader::KDBX4(_)))
+        .is_true();
+}
+
+#[test]
+fn it_converts_kdbx4_to_kdbx3() {
+    let mut database = open_fixture(
+        "empty-chacha20-argon2-v4.kdbx",
+        "empty-chacha20-argon2-v4.key",
+    );
+
+    let result = database.convert_to(FormatVersion::KDBX3);
+
+    assert_that(&result)
+        .is_ok();
+
+    match &database.header {
+        Header::KDBX3(header) => {
+            assert_that(&header.file_minor_version).is_equal_to(1);
+            assert_that(&header.outer_iv.len()).is_equal_to(16);
+            assert_that(&header.transform_rounds).is_greater_than(0);
+            assert_that(&matches!(header.inner_cipher, InnerCipherSuite::Salsa20)).is_true();
+        }
+        _ => panic!("expected a KDBX3 header"),
+    }
+
+    assert_that(&matches!(database.inner_header, InnerHeader::None))
+        .is_true();
+}
+
+#[test]
+fn it_converts_kdbx3_to_kdbx4_with_argon2() {
+    let mut database = open_fixture(
+        "empty-aes256-aeskdf-v31.kdbx",
+        "empty-aes256-aeskdf-v31.key",
+    );
+
+    let salt = vec![0u8; 32];
+    let kdf = KdfSettings::Argon2 {
+        memory: 1024 * 1024,
+        salt: salt.clone(),
+        iterations: 2,
+        lanes: 2,
+        version: argon2::Version::Version13,
+    };
+
+    assert_that(&database.convert_to_kdbx4(&kdf))
+        .is_ok();
+
+    let mut data: Vec<u8> = vec![];
+    database.save(&mut data, Some("argon2"), None)
+        .expect("converted database is written");
+
+    let reopened = Database::open(&mut data.as_ref() as &mut &[u8], Some("argon2"), None)
+        .expect("converted database opens");
+
+    match &reopened.header {
+        Header::KDBX4(header) => match &header.kdf {
+            KdfSettings::Argon2 { memory, salt: reopened_salt, iterations, lanes, .. } => {
+                assert_that(memory).is_equal_to(1024 * 1024);
+                assert_that(iterations).is_equal_to(2);
+                assert_that(lanes).is_equal_to(2);
+                // the salt of the template is not reused
+                assert_that(reopened_salt).is_not_equal_to(&salt);
+            }
+            _ => panic!("expected Argon2"),
+        },
+        _ => panic!("expected a KDBX4 header"),
+    }
+}
+
+#[test]
+fn it_converts_kdbx4_to_kdbx3_and_reopens_attachments() {
+    let mut database = open_sample();
+    database.convert_to(FormatVersion::KDBX4).unwrap();
+    let mail = database.root.entries["mail"].binaries();
+
+    assert_that(&database.convert_to(FormatVersion::KDBX3))
+        .is_ok();
+
+    let mut data: Vec<u8> = vec![];
+    database.save(&mut data, Some(PASSWORD), None)
+        .expect("converted database is written");
+
+    let reopened = Database::open(&mut data.as_ref() as &mut &[u8], Some(PASSWORD), None)
+        .expect("converted database opens");
+
+    assert_that(&reopened.header.format_version())
+        .is_equal_to(FormatVersion::KDBX3);
+    assert_that(&reopened.root.entries["mail"].binaries())
+        .is_equal_to(mail);
+}
+
+#[test]
+fn it_converts_kdbx4_to_kdb_and_reopens() {
+    let mut database = open_fixture(
+        "empty-twofish-aeskdf-v4.kdbx",
+        "empty-twofish-aeskdf-v4.key",
+    );
+
+    assert_that(&database.convert_to(FormatVersion::KDB))
+        .is_ok();
+
+    let mut data: Vec<u8> = vec![];
+    database.save_kdb(&mut data, Some("legacy device"), None)
+        .expect("converted database is written");
+
+    let reopened = Database::open(
+        &mut data.as_ref() as &mut &[u8],
+        Some("legacy device"),
+        None
+    ).expect("converted database opens");
+
+    assert_that(&reopened.header.format_version())
+        .is_equal_to(FormatVersion::KDB);
+}
+
+#[test]
+fn it_upgrades_kdbx31_attachments_and_saves() {
+    let mut database = open_sample();
+
+    let attachments = |database: &Database, title: &str| -> Vec<(String, Vec<u8>)> {
+        database.root.entries[title]
+            .binaries()
+            .iter()
+            .map(|binary| (binary.id(), binary.value()))
+            .collect()
+    };
+    let mail = attachments(&database, "mail");
+    let vpn = attachments(&database, "vpn");
+
+    assert_that(&mail).is_equal_to(vec![
+        ("ca.pem".to_owned(), b"-----BEGIN CERTIFICATE-----".to_vec()),
+        ("recovery.txt".to_owned(), b"recovery codes".to_vec()),
+    ]);
+
+    assert_that(&database.convert_to(FormatVersion::KDBX4))
+        .is_ok();
+
+    let mut saved: Vec<u8> = vec![];
+    database.save(&mut saved, Some(PASSWORD), None)
+        .expect("upgraded database is written");
+
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], Some(PASSWORD), None)
+        .expect("upgraded database opens");
+
+    assert_that(&reopened.header.format_version())
+        .is_equal_to(FormatVersion::KDBX4);
+    assert_that(&attachments(&reopened, "mail"))
+        .is_equal_to(mail);
+    assert_that(&attachments(&reopened, "vpn"))
+        .is_equal_to(vpn);
+}
@@ -0,0 +1,47 @@
+use spectral::prelude::*;
+
+use rustpass::generator::{
+    PassphraseProfile,
+    PasswordProfile,
+    Pattern,
+    Wordlist,
+};
+
+#[test]
+fn it_generates_passwords_from_profiles() {
+    let profile = PasswordProfile {
+        length: 32,
+        symbols: true,
+        exclude_look_alikes: true,
+        ..PasswordProfile::default()
+    };
+
+    let first = profile.generate().unwrap();
+    let second = profile.generate().unwrap();
+
+    assert_that(&first.password.chars().count()).is_equal_to(32);
+    assert_that(&first.password.as_str()).is_not_equal_to(second.password.as_str());
+    assert_that(&(first.entropy > 190.0)).is_true();
+}
+
+#[test]
+fn it_generates_passwords_from_patterns() {
+    let pattern = Pattern::parse("u{4}-d{4}").unwrap();
+
+    let generated = pattern.generate();
+
+    assert_that(&generated.password.len()).is_equal_to(9);
+    assert_that(&generated.password.chars().nth(4)).is_equal_to(Some('-'));
+    assert_that(&((generated.entropy - pattern.entropy()).abs() < 1e-9)).is_true();
+}
+
+#[test]
+fn it_generates_passphrases() {
+    let wordlist = Wordlist::bundled();
+    let profile = PassphraseProfile::default();
+
+    let generated = profile.generate(&wordlist).unwrap();
+
+    assert_that(&generated.password.split('-').count()).is_equal_to(profile.words);
+    assert_that(&((generated.entropy - 77.0).abs() < 1e-9)).is_true();
+}
@@ -0,0 +1,34 @@
+use spectral::prelude::*;
+
+use rustpass::health::HealthPolicy;
+
+use chrono::Duration;
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_reports_database_health() {
+    let database = open_sample();
+
+    let report = database.health_report(&HealthPolicy::default()).unwrap();
+
+    assert_that(&report.reused).is_empty();
+    assert_that(&report.expired).is_empty();
+    assert_that(&report.missing_username).has_length(1);
+    assert_that(&report.missing_username[0].entry.title().as_deref()).is_equal_to(Some("vpn"));
+}
+
+#[test]
+fn it_flags_missing_fields_and_skips_unknown_ages() {
+    let database = open_sample();
+    let policy = HealthPolicy { stale_after: Duration::zero() };
+
+    let report = database.health_report(&policy).unwrap();
+
+    // the fixture entries have neither a URL nor modification times
+    assert_that(&report.missing_url).has_length(2);
+    assert_that(&report.stale).is_empty();
+    assert_that(&report.is_healthy()).is_false();
+}
@@ -0,0 +1,41 @@
+use spectral::prelude::*;
+
+use rustpass::{
+    Database,
+    header::Header,
+};
+
+mod common;
+
+use common::open_fixture;
+
+#[test]
+fn it_saves_and_reopens_kdb() {
+    let database = open_fixture(
+        "empty-aes256-aeskdf-v31.kdbx",
+        "empty-aes256-aeskdf-v31.key",
+    );
+
+    let mut data: Vec<u8> = vec![];
+    let result = database.save_kdb(
+        &mut data,
+        Some("legacy device"),
+        None
+    );
+
+    assert_that(&result)
+        .is_ok();
+
+    let result = Database::open(
+        &mut data.as_ref() as &mut &[u8],
+        Some("legacy device"),
+        None
+    );
+
+    let reopened = assert_that(&result)
+        .is_ok()
+        .subject;
+
+    assert_that(&matches!(reopened.header, Header::KDB(_)))
+        .is_true();
+}
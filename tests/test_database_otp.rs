@@ -0,0 +1,24 @@
+use spectral::prelude::*;
+
+use rustpass::items::otp::TotpSettings;
+
+use std::time::SystemTime;
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_provisions_and_reads_totp_codes() {
+    let mut database = open_sample();
+    let settings = TotpSettings::generate();
+    let now = SystemTime::now();
+
+    let entry = database.root.entries.get_mut("mail").unwrap();
+    assert_that(&entry.totp(now).unwrap()).is_none();
+
+    entry.set_totp(&settings, "Legacy", "alice").unwrap();
+
+    assert_that(&entry.totp(now).unwrap()).is_equal_to(Some(settings.code(now).unwrap()));
+    assert_that(&entry.hotp().unwrap()).is_none();
+}
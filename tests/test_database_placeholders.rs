@@ -0,0 +1,25 @@
+use spectral::prelude::*;
+
+use rustpass::items::placeholders::{
+    PlaceholderContext,
+    Template,
+};
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_expands_placeholders_of_found_entries() {
+    let database = open_sample();
+    let template = Template::compile("{USERNAME}:{PASSWORD}@{TITLE}.{GROUP_PATH}").unwrap();
+
+    let results = database.search("mail").unwrap();
+    let path = [database.root.name.as_str()];
+    let mut context = PlaceholderContext::new(results[0].entry, &path);
+    context.set_root(&database.root, &database.meta);
+
+    let value = template.evaluate(&context).unwrap();
+
+    assert_that(&value.as_str()).is_equal_to("alice:hunter2@mail.Legacy");
+}
@@ -0,0 +1,32 @@
+use spectral::prelude::*;
+
+use rustpass::quality::{
+    PatternKind,
+    QualityRating,
+};
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_rates_entry_passwords() {
+    let database = open_sample();
+
+    let quality = database.root.entries["mail"].password_quality().unwrap();
+
+    assert_that(&quality.rating).is_equal_to(QualityRating::Poor);
+    assert_that(&quality.weaknesses[0].kind).is_equal_to(PatternKind::CommonPassword);
+}
+
+#[test]
+fn it_reports_the_whole_database() {
+    let database = open_sample();
+
+    let report = database.password_quality_report();
+
+    assert_that(&report).has_length(1);
+    assert_that(&report[0].path).is_empty();
+    assert_that(&report[0].entry.title().as_deref()).is_equal_to(Some("mail"));
+    assert_that(&(report[0].quality.bits < 40.0)).is_true();
+}
@@ -0,0 +1,81 @@
+use spectral::prelude::*;
+
+use chrono::Duration;
+
+use rustpass::{
+    header::FormatVersion,
+    Database,
+};
+
+mod common;
+
+use common::{
+    PASSWORD,
+    open_sample,
+};
+
+#[test]
+fn it_moves_deleted_entries_to_the_recycle_bin() {
+    let mut database = open_sample();
+    database.meta.set_recycle_bin_enabled(true);
+    let uuid = database.root.entries["mail"].uuid();
+
+    assert_that(&database.delete_entry(&["mail"])).is_true();
+
+    let bin = &database.root.child_groups["Recycle Bin"];
+    assert_that(&database.meta.recycle_bin_uuid()).is_equal_to(Some(bin.uuid.clone()));
+    assert_that(&bin.entries["mail"].uuid()).is_equal_to(uuid.clone());
+    assert_that(&database.deleted_objects).is_empty();
+
+    assert_that(&database.delete_entry(&["Recycle Bin", "mail"])).is_true();
+
+    assert_that(&database.deleted_objects).has_length(1);
+    assert_that(&database.deleted_objects[0].uuid()).is_equal_to(uuid);
+}
+
+#[test]
+fn it_deletes_for_good_without_a_recycle_bin() {
+    let mut database = open_sample();
+    database.meta.set_recycle_bin_enabled(false);
+
+    assert_that(&database.delete_entry(&["mail"])).is_true();
+    database.empty_recycle_bin();
+
+    assert_that(&database.root.entries.contains_key("mail")).is_false();
+    assert_that(&database.root.child_groups).is_empty();
+    assert_that(&database.deleted_objects).has_length(1);
+}
+
+#[test]
+fn it_prunes_old_tombstones() {
+    let mut database = open_sample();
+    assert_that(&database.deleted_objects).is_empty();
+
+    database.delete_entry(&["mail"]);
+    database.prune_deleted_objects(Duration::days(30));
+    assert_that(&database.deleted_objects).has_length(1);
+
+    database.prune_deleted_objects(Duration::seconds(-60));
+    assert_that(&database.deleted_objects).is_empty();
+}
+
+#[test]
+fn it_keeps_tombstones_across_save_and_open() {
+    let mut database = open_sample();
+    database.convert_to(FormatVersion::KDBX4).unwrap();
+    database.meta.set_recycle_bin_enabled(false);
+    let uuid = database.root.entries["mail"].uuid();
+
+    assert_that(&database.delete_entry(&["mail"])).is_true();
+
+    let mut saved: Vec<u8> = vec![];
+    database.save(&mut saved, Some(PASSWORD), None).unwrap();
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], Some(PASSWORD), None).unwrap();
+
+    assert_that(&reopened.root.entries.contains_key("mail")).is_false();
+    assert_that(&reopened.deleted_objects).has_length(1);
+    assert_that(&reopened.deleted_objects[0].uuid()).is_equal_to(uuid);
+    assert_that(&reopened.deleted_objects[0].deletion_time().as_datetime()).is_equal_to(
+        database.deleted_objects[0].deletion_time().as_datetime()
+    );
+}
@@ -0,0 +1,27 @@
+use spectral::prelude::*;
+
+use rustpass::references::ReferenceField;
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_resolves_references_to_shared_credentials() {
+    let mut database = open_sample();
+
+    let mut shared = database.root.entries["mail"].clone();
+    shared
+        .set_reference("UserName", &database.root.entries["mail"], ReferenceField::UserName)
+        .set_reference("Password", &database.root.entries["mail"], ReferenceField::Password)
+        .set_uuid(&Default::default());
+    database.root.add_entry("shared", &shared);
+
+    let shared = &database.root.entries["shared"];
+    let password = database.resolve_field(shared, "Password").unwrap();
+    let user_name = database.resolve_field(shared, "UserName").unwrap();
+
+    assert_that(&shared.password().unwrap().starts_with("{REF:P@I:")).is_true();
+    assert_that(&password.as_deref()).is_equal_to(Some("hunter2"));
+    assert_that(&user_name.as_deref()).is_equal_to(Some("alice"));
+}
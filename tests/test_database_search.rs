@@ -0,0 +1,37 @@
+use spectral::prelude::*;
+
+use rustpass::Error;
+
+mod common;
+
+use common::open_sample;
+
+#[test]
+fn it_finds_entries_by_term_and_field() {
+    let database = open_sample();
+
+    let by_term = database.search("ALICE").unwrap();
+    let by_password = database.search("pass:hunter").unwrap();
+
+    assert_that(&by_term).has_length(1);
+    assert_that(&by_term[0].path).is_empty();
+    assert_that(&by_term[0].entry.title().as_deref()).is_equal_to(Some("mail"));
+    assert_that(&by_password).has_length(1);
+}
+
+#[test]
+fn it_excludes_matching_entries() {
+    let database = open_sample();
+
+    assert_that(&database.search("alice -title:mail").unwrap()).is_empty();
+    assert_that(&database.search("is:weak").unwrap()).has_length(1);
+}
+
+#[test]
+fn it_rejects_invalid_regex() {
+    let database = open_sample();
+
+    let result = database.search("url:r:[a-");
+
+    assert_that(&matches!(result, Err(Error::InvalidSearchQuery { .. }))).is_true();
+}
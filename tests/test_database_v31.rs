@@ -2,7 +2,8 @@ use spectral::prelude::*;
 
 use rustpass::{
     Database,
-    Result
+    Result,
+    header::Header,
 };
 
 use std::{
@@ -25,6 +26,34 @@ fn it_open_aes256_aeskdf_v31() {
     database.close();
 }
 
+#[test]
+fn it_saves_and_reopens_v31() {
+    let mut database = _open_database(
+        "empty-aes256-aeskdf-v31.kdbx",
+        "empty-aes256-aeskdf-v31.key",
+        None
+    ).unwrap();
+
+    database.root.name = "Saved".to_owned();
+
+    let mut saved = Vec::new();
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v31.key").expect(".key is present");
+    assert_that(&database.save(&mut saved, None, Some(&mut key_file))).is_ok();
+
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v31.key").expect(".key is present");
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], None, Some(&mut key_file)).unwrap();
+
+    assert_that(&reopened.root.name.as_str()).is_equal_to("Saved");
+    match (&database.header, &reopened.header) {
+        (Header::KDBX3(original), Header::KDBX3(header)) => {
+            assert_that(&header.file_minor_version).is_equal_to(1);
+            assert_that(&header.transform_rounds).is_equal_to(original.transform_rounds);
+            assert_that(&header.master_seed).is_not_equal_to(original.master_seed.clone());
+        }
+        _ => panic!("expected KDBX3 headers"),
+    }
+}
+
 fn _open_database(
     dbfile: &str,
     keyfile: &str,
@@ -2,7 +2,17 @@ use spectral::prelude::*;
 
 use rustpass::{
     Database,
-    Result
+    Error,
+    Result,
+    header::{
+        FormatVersion,
+        Header,
+    },
+    suites::{
+        KdfLimits,
+        KdfSettings,
+    },
+    variant_dictionary::VariantDictionaryValue,
 };
 
 use std::{
@@ -99,6 +109,115 @@ fn it_open_twofish_argon2_v4() {
     database.close();
 }
 
+#[test]
+fn it_rejects_argon2_v4_above_memory_limit() {
+    let mut key_file = File::open("tests/fixture/empty-aes256-argon2-v4.key")
+        .expect(".key is present");
+    let data = std::fs::read("tests/fixture/empty-aes256-argon2-v4.kdbx")
+        .expect(".kdbx is present");
+
+    let limits = KdfLimits {
+        max_memory: 1024,
+        ..KdfLimits::default()
+    };
+
+    let result = Database::open_with_limits(
+        &mut data.as_ref() as &mut &[u8],
+        None,
+        Some(&mut key_file),
+        &limits,
+    );
+
+    assert_that(&result)
+        .is_err()
+        .matches(|e| match e {
+            Error::KdfResourceLimit { parameter, limit, .. } => {
+                parameter == "memory" && *limit == 1024
+            }
+            _ => false,
+        });
+}
+
+#[test]
+fn it_saves_and_reopens_v4() {
+    let mut database = _open_database(
+        "empty-aes256-aeskdf-v4.kdbx",
+        "empty-aes256-aeskdf-v4.key",
+        None
+    ).unwrap();
+
+    database.root.name = "Saved".to_owned();
+    if let Header::KDBX4(header) = &mut database.header {
+        header.public_custom_data.put("team", VariantDictionaryValue::String("platform".to_owned()));
+    }
+
+    let mut saved = Vec::new();
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v4.key").expect(".key is present");
+    assert_that(&database.save(&mut saved, None, Some(&mut key_file))).is_ok();
+
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v4.key").expect(".key is present");
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], None, Some(&mut key_file)).unwrap();
+
+    assert_that(&reopened.root.name.as_str()).is_equal_to("Saved");
+    match &reopened.header {
+        Header::KDBX4(header) => {
+            assert_that(&header.public_custom_data.get_string("team")).is_equal_to(Some("platform"));
+        }
+        _ => panic!("expected a KDBX4 header"),
+    }
+}
+
+#[test]
+fn it_saves_with_a_full_range_kdf_seed() {
+    let database = _open_database(
+        "empty-aes256-aeskdf-v4.kdbx",
+        "empty-aes256-aeskdf-v4.key",
+        None
+    ).unwrap();
+
+    let mut saved = Vec::new();
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v4.key").expect(".key is present");
+    database.save(&mut saved, None, Some(&mut key_file)).unwrap();
+
+    let mut key_file = File::open("tests/fixture/empty-aes256-aeskdf-v4.key").expect(".key is present");
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], None, Some(&mut key_file)).unwrap();
+
+    match &reopened.header {
+        Header::KDBX4(header) => match &header.kdf {
+            KdfSettings::Aes { seed, .. } => {
+                assert_that(&seed.len()).is_equal_to(32);
+                assert_that(&seed.iter().all(u8::is_ascii_alphanumeric)).is_false();
+            }
+            _ => panic!("expected AES-KDF"),
+        },
+        _ => panic!("expected a KDBX4 header"),
+    }
+}
+
+#[test]
+fn it_saves_protected_fields_and_metadata() {
+    let data = std::fs::read("tests/fixture/legacy-aes256-salsa20-v30.kdbx").expect(".kdbx is present");
+    let mut database = Database::open(&mut data.as_ref() as &mut &[u8], Some("legacy"), None).unwrap();
+    database.convert_to(FormatVersion::KDBX4).unwrap();
+
+    database.meta
+        .set_database_name("Legacy")
+        .set_default_user_name("alice")
+        .set_protect_user_name(true)
+        .set_history_max_items(3);
+
+    let mut saved = Vec::new();
+    assert_that(&database.save(&mut saved, Some("legacy"), None)).is_ok();
+
+    let reopened = Database::open(&mut saved.as_ref() as &mut &[u8], Some("legacy"), None).unwrap();
+    let entry = &reopened.root.entries["mail"];
+
+    assert_that(&entry.username().as_deref()).is_equal_to(Some("alice"));
+    assert_that(&entry.password().as_deref()).is_equal_to(Some("hunter2"));
+    assert_that(&reopened.meta).is_equal_to(&database.meta);
+    assert_that(&reopened.meta.protect_user_name()).is_true();
+}
+
 fn _open_database(
     dbfile: &str,
     keyfile: &str,
@@ -0,0 +1,204 @@
+use spectral::prelude::*;
+
+use rustpass::{
+    Database,
+    DatabaseIntegrityError,
+    Error,
+    Result,
+    compression::Compression,
+    header::Header,
+    integrity::IntegrityReport,
+    suites::KdfLimits,
+};
+
+use std::fs::File;
+
+mod common;
+
+use common::{
+    open_fixture,
+    read_fixture,
+};
+
+const DBFILE: &str = "empty-aes256-aeskdf-v4.kdbx";
+const KEYFILE: &str = "empty-aes256-aeskdf-v4.key";
+
+// the terminating empty block: HMAC and a zero size
+const END_BLOCK_LENGTH: usize = 36;
+
+// the size of the HMAC blocks written by `Database::save`
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+#[test]
+fn it_verifies_intact_kdbx4() {
+    let data = read_fixture(DBFILE);
+
+    let report = _verify(&data).expect("file is verified");
+
+    assert_that(&report.is_intact()).is_true();
+    assert_that(&report.block_count).is_equal_to(2);
+    assert_that(&String::from_utf8_lossy(&report.recovered_xml).contains("</KeePassFile>"))
+        .is_true();
+}
+
+#[test]
+fn it_reports_corrupted_block() {
+    let mut data = read_fixture(DBFILE);
+    let position = data.len() - END_BLOCK_LENGTH - 1;
+    data[position] ^= 0xff;
+
+    let report = _verify(&data).expect("file is verified");
+
+    assert_that(&report.is_intact()).is_false();
+    assert_that(&report.header_hmac_valid).is_true();
+    assert_that(&report.corrupted_blocks).is_equal_to(vec![0]);
+    assert_that(&report.truncated).is_false();
+    assert_that(&report.recovered_xml.is_empty()).is_true();
+}
+
+#[test]
+fn it_reports_truncated_file_and_recovers_xml() {
+    let data = read_fixture(DBFILE);
+    let data = &data[..data.len() - END_BLOCK_LENGTH / 2];
+
+    let report = _verify(data).expect("file is verified");
+
+    assert_that(&report.truncated).is_true();
+    assert_that(&report.corrupted_blocks).is_equal_to(vec![]);
+    assert_that(&report.block_count).is_equal_to(1);
+    assert_that(&String::from_utf8_lossy(&report.recovered_xml).contains("</KeePassFile>"))
+        .is_true();
+}
+
+#[test]
+fn it_reports_damaged_header() {
+    let mut data = read_fixture(DBFILE);
+    // the end of header marker, neither seeds nor key derivation depend on it
+    let position = _header_length(&data) - 1;
+    data[position] ^= 0xff;
+
+    let report = _verify(&data).expect("file is verified");
+
+    assert_that(&report.header_hash_valid).is_false();
+    assert_that(&report.header_hmac_valid).is_false();
+    assert_that(&report.corrupted_blocks).is_equal_to(vec![]);
+    assert_that(&report.recovered_xml.is_empty()).is_false();
+}
+
+#[test]
+fn it_reports_corrupted_middle_block() {
+    let mut data = _save_multi_block_fixture();
+    // the outer header, its hash and HMAC, then the first block of 1 MiB and its HMAC and size
+    let position = _header_length(&data) + 64 + 36 + BLOCK_SIZE + 36 + 100;
+    data[position] ^= 0xff;
+
+    let report = _verify(&data).expect("file is verified");
+
+    assert_that(&report.header_hmac_valid).is_true();
+    assert_that(&report.block_count).is_equal_to(4);
+    assert_that(&report.corrupted_blocks).is_equal_to(vec![1]);
+    assert_that(&report.truncated).is_false();
+    assert_that(&report.recovered_xml.is_empty()).is_false();
+    assert_that(&String::from_utf8_lossy(&report.recovered_xml).contains("</KeePassFile>"))
+        .is_false();
+}
+
+#[test]
+fn it_rejects_wrong_key_on_damaged_header() {
+    let mut data = read_fixture(DBFILE);
+    let position = _header_length(&data) - 1;
+    data[position] ^= 0xff;
+
+    let result = Database::verify(
+        &mut &data[..],
+        Some("guess"),
+        None
+    );
+
+    assert_that(&matches!(
+        result,
+        Err(Error::DatabaseIntegrity { e: DatabaseIntegrityError::HeaderHashMismatch })
+    )).is_true();
+}
+
+#[test]
+fn it_rejects_wrong_key_on_intact_header() {
+    let data = read_fixture(DBFILE);
+
+    let result = Database::verify(
+        &mut &data[..],
+        Some("guess"),
+        None
+    );
+
+    assert_that(&matches!(result, Err(Error::IncorrectKey))).is_true();
+}
+
+// walks the outer header blocks: id (1 byte), size (4 bytes), data
+#[test]
+fn it_rejects_argon2_above_memory_limit() {
+    let data = read_fixture("empty-aes256-argon2-v4.kdbx");
+    let mut key_file = File::open("tests/fixture/empty-aes256-argon2-v4.key")
+        .expect(".key is present");
+
+    let limits = KdfLimits {
+        max_memory: 1024,
+        ..KdfLimits::default()
+    };
+
+    let result = Database::verify_with_limits(
+        &mut &data[..],
+        None,
+        Some(&mut key_file),
+        &limits,
+    );
+
+    assert_that(&result)
+        .is_err()
+        .matches(|e| match e {
+            Error::KdfResourceLimit { parameter, limit, .. } => {
+                parameter == "memory" && *limit == 1024
+            }
+            _ => false,
+        });
+}
+
+fn _header_length(data: &[u8]) -> usize {
+    let mut pos = 12;
+    loop {
+        let id = data[pos];
+        let size = u32::from_le_bytes([data[pos + 1], data[pos + 2], data[pos + 3], data[pos + 4]]) as usize;
+        pos += 5 + size;
+        if id == 0 {
+            return pos;
+        }
+    }
+}
+
+fn _verify(data: &[u8]) -> Result<IntegrityReport> {
+    let mut key_file: File = File::open(format!("tests/fixture/{}", KEYFILE))
+        .expect(".key is present");
+
+    Database::verify(
+        &mut &data[..],
+        None,
+        Some(&mut key_file)
+    )
+}
+
+// an uncompressed payload of two full blocks and a partial one, ended by the empty block
+fn _save_multi_block_fixture() -> Vec<u8> {
+    let mut database = open_fixture(DBFILE, KEYFILE);
+    if let Header::KDBX4(ref mut header) = database.header {
+        header.compression = Compression::None;
+    }
+    database.root.name = "x".repeat(BLOCK_SIZE * 5 / 2);
+
+    let mut key_file: File = File::open(format!("tests/fixture/{}", KEYFILE))
+        .expect(".key is present");
+    let mut data: Vec<u8> = vec![];
+    database.save(&mut data, None, Some(&mut key_file))
+        .expect("database is written");
+
+    data
+}
@@ -0,0 +1,102 @@
+use spectral::prelude::*;
+
+use rustpass::{
+    Database,
+    Error,
+};
+
+use hex_literal::hex;
+
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    sync::{
+        Mutex,
+        atomic::{
+            AtomicBool,
+            AtomicUsize,
+            Ordering,
+        },
+    },
+};
+
+// written by tests/fixture/generate_legacy_kdbx.py
+const PASSWORD: &str = "legacy";
+const WRONG_PASSWORD: &str = "correct horse battery staple";
+
+// SHA-256 of each password and the composite key made of it alone
+const SECRETS: [[u8; 32]; 4] = [
+    hex!("c49fea7425fa7f8699897a97c159c6690267d9003bb78c53fafa8fc15c325d84"),
+    hex!("039ab65d8f6f82bb7325ea1e67ce53a58f3f8b6d1b3f5b6cead2dd5cec5cb928"),
+    hex!("c4bbcb1fbec99d65bf59d85c8cb62ee2db963f0fe106f483d9afa73bd4e39a8a"),
+    hex!("b867db875479bcc0287352cdaa4a1755689b8338777d0915e9acd9f6edbc96cb"),
+];
+
+static SCANNING: AtomicBool = AtomicBool::new(false);
+static LEAKED: AtomicUsize = AtomicUsize::new(0);
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Counts heap blocks that still hold one of the `SECRETS` when they are freed
+struct ScanningAllocator;
+
+unsafe impl GlobalAlloc for ScanningAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if SCANNING.load(Ordering::SeqCst) {
+            // spare capacity may be uninitialized, but a secret in it counts just the same
+            let block = std::slice::from_raw_parts(ptr, layout.size());
+            if SECRETS.iter().any(|secret| block.windows(secret.len()).any(|window| window == secret)) {
+                LEAKED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: ScanningAllocator = ScanningAllocator;
+
+#[test]
+fn it_notices_key_material_that_is_not_wiped() {
+    let leaked = _leaks_while(|_| drop(std::hint::black_box(SECRETS[0].to_vec())));
+
+    assert_that(&leaked).is_equal_to(1);
+}
+
+#[test]
+fn it_wipes_key_material_when_the_database_is_dropped() {
+    let leaked = _leaks_while(|data| {
+        let database = Database::open(&mut &data[..], Some(PASSWORD), None);
+        assert_that(&database.is_ok()).is_true();
+    });
+
+    assert_that(&leaked).is_equal_to(0);
+}
+
+#[test]
+fn it_wipes_key_material_when_the_key_is_wrong() {
+    let leaked = _leaks_while(|data| {
+        let database = Database::open(&mut &data[..], Some(WRONG_PASSWORD), None);
+        assert_that(&matches!(database, Err(Error::IncorrectKey))).is_true();
+    });
+
+    assert_that(&leaked).is_equal_to(0);
+}
+
+fn _leaks_while(open: impl FnOnce(&[u8])) -> usize {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let data = std::fs::read("tests/fixture/legacy-aes256-salsa20-v30.kdbx").expect(".kdbx is present");
+
+    LEAKED.store(0, Ordering::SeqCst);
+    SCANNING.store(true, Ordering::SeqCst);
+    open(&data);
+    SCANNING.store(false, Ordering::SeqCst);
+
+    LEAKED.load(Ordering::SeqCst)
+}